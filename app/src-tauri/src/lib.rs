@@ -1,5 +1,9 @@
+use msnr_core::transport::{
+    discover_serial_ports, probe_serial_ports, resolve_serial_port, DetectedSerialPort,
+};
 use msnr_core::{
-    set_log_callback, Config, Engine, IpTransport, SerialTransport, Transport, TransportMode,
+    clear_log_callback, set_log_callback, Config, Engine, HttpTransport, IpTransport, LogCallback,
+    PreflightReport, SerialTransport, StopHandle, Transport, TransportMode,
 };
 use std::sync::Arc;
 use tauri::{Emitter, State};
@@ -7,13 +11,56 @@ use tokio::sync::Mutex as AsyncMutex;
 
 struct AppState {
     engine_handle: Arc<AsyncMutex<Option<tokio::task::JoinHandle<()>>>>,
+    stop_handle: Arc<AsyncMutex<Option<StopHandle>>>,
 }
 
 #[tauri::command]
-fn get_serial_ports() -> Vec<String> {
-    serialport::available_ports()
-        .map(|ports| ports.into_iter().map(|p| p.port_name).collect())
-        .unwrap_or_default()
+async fn get_serial_ports(probe: Option<bool>, baud_rate: Option<u32>) -> Vec<DetectedSerialPort> {
+    let ports = discover_serial_ports();
+    if probe.unwrap_or(false) {
+        probe_serial_ports(ports, baud_rate.unwrap_or(115200)).await
+    } else {
+        ports
+    }
+}
+
+fn build_transport(config: &Config) -> Result<Box<dyn Transport>, String> {
+    let transport_impl: Box<dyn Transport> = match config.transport_mode {
+        TransportMode::Serial => {
+            let port_name = resolve_serial_port(
+                config.serial_port.as_deref(),
+                config.serial_number.as_deref(),
+            )
+            .map_err(|e| e.to_string())?;
+            Box::new(SerialTransport::with_settings(
+                port_name,
+                config.serial_baud_rate,
+                config.serial_flow_control,
+            ))
+        }
+        TransportMode::Ip => {
+            let ip = config.ip.clone().unwrap_or("127.0.0.1".to_string());
+            let port = config.port.unwrap_or(4403);
+            Box::new(IpTransport::new(ip, port))
+        }
+        TransportMode::Http => {
+            if let Some(url) = &config.http_url {
+                Box::new(HttpTransport::new(url.clone()).map_err(|e| e.to_string())?)
+            } else {
+                return Err("HTTP URL not specified".to_string());
+            }
+        }
+    };
+    Ok(transport_impl)
+}
+
+/// Forwards log records to the frontend; each record carries its engine session so the
+/// frontend can tell concurrent engines (a run and a pre-flight check) apart.
+fn log_sink(app_handle: &tauri::AppHandle) -> LogCallback {
+    let console_handle = app_handle.clone();
+    Arc::new(move |record| {
+        let _ = console_handle.emit("console-log", record.clone());
+    })
 }
 
 #[tauri::command]
@@ -21,7 +68,7 @@ async fn start_test(
     config: Config,
     state: State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<(), String> {
+) -> Result<String, String> {
     let mut handle_guard = state.engine_handle.lock().await;
 
     // Check if there is an active handle
@@ -31,29 +78,19 @@ async fn start_test(
         }
     }
 
-    let transport_impl: Box<dyn Transport> = match config.transport_mode {
-        TransportMode::Serial => {
-            if let Some(s) = &config.serial_port {
-                Box::new(SerialTransport::new(s.clone()))
-            } else {
-                return Err("Serial port not specified".to_string());
-            }
-        }
-        TransportMode::Ip => {
-            let ip = config.ip.clone().unwrap_or("127.0.0.1".to_string());
-            let port = config.port.unwrap_or(4403);
-            Box::new(IpTransport::new(ip, port))
+    let transport_impl = build_transport(&config)?;
+    let mut engine = Engine::with_log_sink(config, transport_impl, log_sink(&app_handle));
+    let session = engine.session_id().to_string();
+    let mut events = engine.subscribe();
+    let stop = engine.stop_handle();
+    *state.stop_handle.lock().await = Some(stop.clone());
+    let events_handle = app_handle.clone();
+    // Ends when the engine is dropped at the end of the run task
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let _ = events_handle.emit("engine-event", event);
         }
-    };
-
-    {
-        let console_handle = app_handle.clone();
-        set_log_callback(Arc::new(move |line| {
-            let _ = console_handle.emit("console-log", line.clone());
-        }));
-    }
-
-    let mut engine = Engine::new(config, transport_impl);
+    });
     let app_handle_clone = app_handle.clone();
 
     let handle = tokio::spawn(async move {
@@ -63,20 +100,43 @@ async fn start_test(
             })
             .await
         {
-            let _ = app_handle.emit("test-error", e.to_string());
+            // A requested stop ends the run with an error, but the user already knows
+            if !stop.is_stopped() {
+                let _ = app_handle.emit("test-error", e.to_string());
+            }
         }
         let _ = app_handle.emit("test-complete", ());
     });
 
     *handle_guard = Some(handle);
-    Ok(())
+    Ok(session)
+}
+
+/// Runs the pre-flight checks; the radio connection is released before returning.
+#[tauri::command]
+async fn run_preflight(
+    config: Config,
+    state: State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<PreflightReport, String> {
+    if let Some(handle) = state.engine_handle.lock().await.as_ref() {
+        if !handle.is_finished() {
+            return Err("Test already running".to_string());
+        }
+    }
+
+    let transport_impl = build_transport(&config)?;
+    Engine::with_log_sink(config, transport_impl, log_sink(&app_handle))
+        .preflight()
+        .await
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn stop_test(state: State<'_, AppState>) -> Result<(), String> {
-    let mut handle_guard = state.engine_handle.lock().await;
-    if let Some(handle) = handle_guard.take() {
-        handle.abort();
+    // The run restores the remote config before it ends and emits "test-complete"
+    if let Some(stop) = state.stop_handle.lock().await.take() {
+        stop.stop();
     }
     Ok(())
 }
@@ -87,12 +147,24 @@ pub fn run() {
         .plugin(tauri_plugin_opener::init())
         .manage(AppState {
             engine_handle: Arc::new(AsyncMutex::new(None)),
+            stop_handle: Arc::new(AsyncMutex::new(None)),
+        })
+        .setup(|app| {
+            // Fallback for logs outside an engine, e.g. serial port probing
+            set_log_callback(log_sink(app.handle()));
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_serial_ports,
             start_test,
+            run_preflight,
             stop_test
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app_handle, event| {
+            if let tauri::RunEvent::Exit = event {
+                clear_log_callback();
+            }
+        });
 }
@@ -5,7 +5,7 @@ import './App.css';
 import { ConfigForm } from './components/ConfigForm';
 import { Dashboard } from './components/Dashboard';
 import { ResultModal } from './components/ResultModal';
-import { Config, ProgressState, AverageStats } from './types';
+import { Config, ProgressState, AverageStats, PreflightReport, EngineEvent, LogEntry, LogLevel, LogRecord } from './types';
 
 const createEmptyProgress = (): ProgressState => ({
   total_progress: 0,
@@ -38,37 +38,46 @@ function App() {
   });
 
   const [isRunning, setIsRunning] = useState(false);
+  const [isChecking, setIsChecking] = useState(false);
   const [progress, setProgress] = useState<ProgressState>(() => createEmptyProgress());
-  const [logs, setLogs] = useState<string[]>([]);
+  const [logs, setLogs] = useState<LogEntry[]>([]);
+  const [activeSession, setActiveSession] = useState<string | null>(null);
   const [resetToken, setResetToken] = useState(0);
   const [summaryStats, setSummaryStats] = useState<AverageStats | null>(null);
   const [showSummary, setShowSummary] = useState(false);
   const resetProgress = () => setProgress(createEmptyProgress());
+  const appendLog = (level: LogLevel, message: string, session: string | null = null) =>
+    setLogs(prev => [...prev, { time: new Date().toLocaleTimeString(), level, session, message }].slice(-500));
 
   useEffect(() => {
     const unlisten = listen<ProgressState>('test-progress', (event) => {
       setProgress(event.payload);
-      if (event.payload.phase === 'Done' && event.payload.average_stats) {
-        setSummaryStats(event.payload.average_stats);
+    });
+
+    const unlistenEngine = listen<EngineEvent>('engine-event', (event) => {
+      const engineEvent = event.payload;
+      if (engineEvent.type === 'Finished') {
+        setSummaryStats(engineEvent.stats);
         setShowSummary(true);
       }
     });
 
     const unlistenComplete = listen('test-complete', () => {
       setIsRunning(false);
-      setLogs(prev => [...prev, `[${new Date().toLocaleTimeString()}] Test Completed`]);
+      appendLog('Info', 'Test Completed');
     });
 
     const unlistenError = listen<string>('test-error', (event) => {
-      setLogs(prev => [...prev, `[${new Date().toLocaleTimeString()}] Error: ${event.payload}`]);
+      appendLog('Error', `Error: ${event.payload}`);
     });
 
-    const unlistenConsole = listen<string>('console-log', (event) => {
-      setLogs(prev => [...prev, `[${new Date().toLocaleTimeString()}] ${event.payload}`].slice(-100));
+    const unlistenConsole = listen<LogRecord>('console-log', (event) => {
+      appendLog(event.payload.level, event.payload.message, event.payload.session ?? null);
     });
 
     return () => {
       unlisten.then(f => f());
+      unlistenEngine.then(f => f());
       unlistenComplete.then(f => f());
       unlistenError.then(f => f());
       unlistenConsole.then(f => f());
@@ -81,20 +90,35 @@ function App() {
       resetProgress();
       setSummaryStats(null);
       setShowSummary(false);
-      await invoke('start_test', { config });
+      const session = await invoke<string>('start_test', { config });
+      setActiveSession(session);
       setIsRunning(true);
-      setLogs(prev => [...prev, `[${new Date().toLocaleTimeString()}] Test Started`]);
+      appendLog('Info', 'Test Started');
     } catch (e) {
       console.error(e);
-      setLogs(prev => [...prev, `[${new Date().toLocaleTimeString()}] Error: ${e}`]);
+      appendLog('Error', `Error: ${e}`);
+    }
+  };
+
+  const handlePreflight = async () => {
+    setIsChecking(true);
+    try {
+      const report = await invoke<PreflightReport>('run_preflight', { config });
+      const failed = report.checks.filter(check => check.status === 'Fail').length;
+      appendLog(failed === 0 ? 'Info' : 'Error', `Pre-flight ${failed === 0 ? 'PASSED' : `FAILED (${failed} check(s))`}`);
+    } catch (e) {
+      console.error(e);
+      appendLog('Error', `Error: ${e}`);
+    } finally {
+      setIsChecking(false);
     }
   };
 
   const handleStop = async () => {
     try {
       await invoke('stop_test');
-      setIsRunning(false);
-      setLogs(prev => [...prev, `[${new Date().toLocaleTimeString()}] Test Stopped`]);
+      // Stays running until the engine has restored the remote config
+      appendLog('Info', 'Stopping test, restoring remote config...');
     } catch (e) {
       console.error(e);
     }
@@ -106,10 +130,12 @@ function App() {
         config={config}
         setConfig={setConfig}
         isRunning={isRunning}
+        isChecking={isChecking}
         onStart={handleStart}
         onStop={handleStop}
+        onPreflight={handlePreflight}
       />
-      <Dashboard progress={progress} logs={logs} resetToken={resetToken} />
+      <Dashboard progress={progress} logs={logs} activeSession={activeSession} resetToken={resetToken} />
       {showSummary && summaryStats && (
         <ResultModal stats={summaryStats} onClose={() => setShowSummary(false)} />
       )}
@@ -1,22 +1,34 @@
 import React, { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { Config } from '../types';
+import { Config, DetectedSerialPort } from '../types';
 
 interface Props {
     config: Config;
     setConfig: (config: Config) => void;
     isRunning: boolean;
+    isChecking: boolean;
     onStart: () => void;
     onStop: () => void;
+    onPreflight: () => void;
 }
 
-export const ConfigForm: React.FC<Props> = ({ config, setConfig, isRunning, onStart, onStop }) => {
-    const [serialPorts, setSerialPorts] = useState<string[]>([]);
+export const ConfigForm: React.FC<Props> = ({ config, setConfig, isRunning, isChecking, onStart, onStop, onPreflight }) => {
+    const [serialPorts, setSerialPorts] = useState<DetectedSerialPort[]>([]);
 
     useEffect(() => {
-        invoke<string[]>('get_serial_ports').then(setSerialPorts).catch(console.error);
+        invoke<DetectedSerialPort[]>('get_serial_ports').then(setSerialPorts).catch(console.error);
     }, []);
 
+    const handleSerialPortChange = (portName: string) => {
+        const port = serialPorts.find(p => p.port_name === portName);
+        setConfig({ ...config, serial_port: portName, serial_number: port?.serial_number ?? undefined });
+    };
+
+    const describePort = (port: DetectedSerialPort) => {
+        const details = [port.chip, port.serial_number && `S/N ${port.serial_number}`].filter(Boolean);
+        return details.length ? `${port.port_name} (${details.join(', ')})` : port.port_name;
+    };
+
     const handleChange = (field: keyof Config, value: any) => {
         setConfig({ ...config, [field]: value });
     };
@@ -47,6 +59,7 @@ export const ConfigForm: React.FC<Props> = ({ config, setConfig, isRunning, onSt
                 >
                     <option value="Ip">IP Network</option>
                     <option value="Serial">Serial Port</option>
+                    <option value="Http">HTTP API</option>
                 </select>
             </div>
 
@@ -71,16 +84,27 @@ export const ConfigForm: React.FC<Props> = ({ config, setConfig, isRunning, onSt
                         />
                     </div>
                 </>
+            ) : config.transport_mode === 'Http' ? (
+                <div className="form-group">
+                    <label>Node URL</label>
+                    <input
+                        type="text"
+                        value={config.http_url || ''}
+                        placeholder="http://meshtastic.local"
+                        onChange={(e) => handleChange('http_url', e.target.value)}
+                        disabled={isRunning}
+                    />
+                </div>
             ) : (
                 <div className="form-group">
                     <label>Serial Port</label>
                     <select
                         value={config.serial_port || ''}
-                        onChange={(e) => handleChange('serial_port', e.target.value)}
+                        onChange={(e) => handleSerialPortChange(e.target.value)}
                         disabled={isRunning}
                     >
                         <option value="">Select Port</option>
-                        {serialPorts.map(p => <option key={p} value={p}>{p}</option>)}
+                        {serialPorts.map(p => <option key={p.port_name} value={p.port_name}>{describePort(p)}</option>)}
                     </select>
                 </div>
             )}
@@ -97,6 +121,31 @@ export const ConfigForm: React.FC<Props> = ({ config, setConfig, isRunning, onSt
                 </select>
             </div>
 
+            <div className="form-group">
+                <label>量測方式</label>
+                <select
+                    value={config.measurement_mode ?? 'Traceroute'}
+                    onChange={(e) => handleChange('measurement_mode', e.target.value)}
+                    disabled={isRunning}
+                >
+                    <option value="Traceroute">Traceroute</option>
+                    <option value="Passive">Passive (只監聽，不發送)</option>
+                    <option value="RangeTest">Range Test (遠端模組)</option>
+                </select>
+            </div>
+
+            <div className="form-group">
+                <label>
+                    <input
+                        type="checkbox"
+                        checked={config.dry_run ?? false}
+                        onChange={(e) => handleChange('dry_run', e.target.checked)}
+                        disabled={isRunning}
+                    />
+                    {' '}Dry run（只讀取設定、不寫入，階段縮短為 30 秒，結果寫入 .dryrun 檔）
+                </label>
+            </div>
+
             <div className="form-group">
                 <label>Interval (秒)</label>
                 <input
@@ -169,7 +218,10 @@ export const ConfigForm: React.FC<Props> = ({ config, setConfig, isRunning, onSt
                 </div>
             )}
 
-            <button onClick={isRunning ? onStop : onStart} disabled={false}>
+            <button onClick={onPreflight} disabled={isRunning || isChecking}>
+                {isChecking ? 'Checking...' : 'Pre-flight Check'}
+            </button>
+            <button onClick={isRunning ? onStop : onStart} disabled={isChecking}>
                 {isRunning ? 'Stop Test' : 'Start Test'}
             </button>
         </div>
@@ -1,18 +1,40 @@
 import React, { useState, useEffect, useRef } from 'react';
-import { ProgressState } from '../types';
+import { LogEntry, LogLevel, ProgressState } from '../types';
 import { SignalChart, SignalData } from './SignalChart';
 import { Activity, Clock, Terminal } from 'lucide-react';
 
 interface Props {
     progress: ProgressState;
-    logs: string[];
+    logs: LogEntry[];
+    /** Session of the last started run; its lines are shown by default. */
+    activeSession: string | null;
     resetToken: number;
 }
 
-export const Dashboard: React.FC<Props> = ({ progress, logs, resetToken }) => {
+const LOG_LEVELS: LogLevel[] = ['Debug', 'Info', 'Warn', 'Error'];
+
+/** Session filter value that shows every engine's lines. */
+const ALL_SESSIONS = '';
+
+export const Dashboard: React.FC<Props> = ({ progress, logs, activeSession, resetToken }) => {
     const [history, setHistory] = useState<SignalData[]>([]);
     const logWindowRef = useRef<HTMLDivElement | null>(null);
     const [isLogPinned, setIsLogPinned] = useState(true);
+    const [minLevel, setMinLevel] = useState<LogLevel>('Info');
+    const [sessionFilter, setSessionFilter] = useState<string>(ALL_SESSIONS);
+    const sessions = Array.from(new Set([
+        ...logs.flatMap(log => (log.session ? [log.session] : [])),
+        ...(activeSession ? [activeSession] : []),
+    ]));
+    // App messages carry no session and are always shown
+    const visibleLogs = logs.filter(log =>
+        LOG_LEVELS.indexOf(log.level) >= LOG_LEVELS.indexOf(minLevel) &&
+        (sessionFilter === ALL_SESSIONS || !log.session || log.session === sessionFilter)
+    );
+
+    useEffect(() => {
+        setSessionFilter(activeSession ?? ALL_SESSIONS);
+    }, [activeSession]);
 
     useEffect(() => {
         const snrTowards = progress.snr_towards;
@@ -63,7 +85,7 @@ export const Dashboard: React.FC<Props> = ({ progress, logs, resetToken }) => {
         const container = logWindowRef.current;
         if (!container) return;
         container.scrollTop = container.scrollHeight;
-    }, [logs, isLogPinned]);
+    }, [visibleLogs.length, isLogPinned]);
 
     const formatTime = (secs: number) => {
         const m = Math.floor(secs / 60);
@@ -161,15 +183,34 @@ export const Dashboard: React.FC<Props> = ({ progress, logs, resetToken }) => {
             <div className="logs-section glass">
                 <div className="section-header">
                     <Terminal size={18} /> <h3>System Logs</h3>
+                    <select
+                        className="log-session-select"
+                        value={sessionFilter}
+                        onChange={(e) => setSessionFilter(e.target.value)}
+                    >
+                        <option value={ALL_SESSIONS}>All sessions</option>
+                        {sessions.map(session => (
+                            <option key={session} value={session}>{session}</option>
+                        ))}
+                    </select>
+                    <select
+                        className="log-level-select"
+                        value={minLevel}
+                        onChange={(e) => setMinLevel(e.target.value as LogLevel)}
+                    >
+                        {LOG_LEVELS.map(level => (
+                            <option key={level} value={level}>{level}+</option>
+                        ))}
+                    </select>
                 </div>
                 <div className="log-window" ref={logWindowRef}>
-                    {logs.map((log, i) => (
-                        <div key={i} className="log-entry">
-                            <span className="log-time">{log.substring(0, log.indexOf(']') + 1)}</span>
-                            <span className="log-msg">{log.substring(log.indexOf(']') + 1)}</span>
+                    {visibleLogs.map((log, i) => (
+                        <div key={i} className={`log-entry log-${log.level.toLowerCase()}`}>
+                            <span className="log-time">[{log.time}]</span>
+                            <span className="log-msg">{log.message}</span>
                         </div>
                     ))}
-                    {logs.length === 0 && <div className="log-entry empty">No logs yet...</div>}
+                    {visibleLogs.length === 0 && <div className="log-entry empty">No logs yet...</div>}
                 </div>
             </div>
         </div>
@@ -14,6 +14,11 @@ const formatDb = (val?: number | null) => {
     return `${val.toFixed(2)} dB`;
 };
 
+const formatMs = (val?: number | null) => {
+    if (!hasValue(val)) return '--';
+    return `${val.toFixed(0)} ms`;
+};
+
 const computeDelta = (on?: number | null, off?: number | null) => {
     if (!hasValue(on) || !hasValue(off)) return undefined;
     return on - off;
@@ -22,6 +27,7 @@ const computeDelta = (on?: number | null, off?: number | null) => {
 export const ResultModal: React.FC<ResultModalProps> = ({ stats, onClose }) => {
     const roofDelta = computeDelta(stats.lna_on_roof_to_mtn, stats.lna_off_roof_to_mtn);
     const mtnDelta = computeDelta(stats.lna_on_mtn_to_roof, stats.lna_off_mtn_to_roof);
+    const rttDelta = computeDelta(stats.lna_on_rtt_median_ms, stats.lna_off_rtt_median_ms);
 
     return (
         <div className="modal-backdrop">
@@ -33,18 +39,49 @@ export const ResultModal: React.FC<ResultModalProps> = ({ stats, onClose }) => {
                         <h4>LNA OFF（{stats.lna_off_samples} 筆）</h4>
                         <div>Roof → Mtn: {formatDb(stats.lna_off_roof_to_mtn)}</div>
                         <div>Mtn → Roof: {formatDb(stats.lna_off_mtn_to_roof)}</div>
+                        <div>RTT (median): {formatMs(stats.lna_off_rtt_median_ms)}</div>
                     </div>
                     <div>
                         <h4>LNA ON（{stats.lna_on_samples} 筆）</h4>
                         <div>Roof → Mtn: {formatDb(stats.lna_on_roof_to_mtn)}</div>
                         <div>Mtn → Roof: {formatDb(stats.lna_on_mtn_to_roof)}</div>
+                        <div>RTT (median): {formatMs(stats.lna_on_rtt_median_ms)}</div>
                     </div>
                     <div className="delta-card">
                         <h4>差值 (ON - OFF)</h4>
                         <div>Roof → Mtn: {formatDb(roofDelta)}</div>
                         <div>Mtn → Roof: {formatDb(mtnDelta)}</div>
+                        <div>RTT (median): {formatMs(rttDelta)}</div>
+                        {hasValue(stats.delta_ci95_half_width_db) && (
+                            <div>95% CI: ±{formatDb(stats.delta_ci95_half_width_db)}</div>
+                        )}
                     </div>
                 </div>
+                {stats.links && stats.links.length > 0 && (
+                    <div className="link-stats">
+                        <h4>各鏈路 SNR</h4>
+                        {stats.links.map((link) => (
+                            <div key={`${link.from}-${link.to}`}>
+                                {link.from} → {link.to}: {formatDb(link.lna_off_snr)} / {formatDb(link.lna_on_snr)}
+                                {' '}(Δ {formatDb(computeDelta(link.lna_on_snr, link.lna_off_snr))})
+                                {hasValue(link.lna_off_neighbor_snr ?? link.lna_on_neighbor_snr) && (
+                                    <> | NeighborInfo: {formatDb(link.lna_off_neighbor_snr)} / {formatDb(link.lna_on_neighbor_snr)}</>
+                                )}
+                            </div>
+                        ))}
+                    </div>
+                )}
+                {stats.confounded_cycles && stats.confounded_cycles.length > 0 && (
+                    <p className="modal-subtitle">
+                        ⚠️ Cycle {stats.confounded_cycles.join(', ')} 的 OFF/ON 頻道使用率差異過大，比較結果可能受干擾
+                    </p>
+                )}
+                {((stats.lna_off_reboots ?? 0) + (stats.lna_on_reboots ?? 0)) > 0 && (
+                    <p className="modal-subtitle">
+                        ⚠️ 受控節點重啟 {(stats.lna_off_reboots ?? 0) + (stats.lna_on_reboots ?? 0)} 次，已排除
+                        {' '}{((stats.lna_off_reboot_excluded_secs ?? 0) + (stats.lna_on_reboot_excluded_secs ?? 0)).toFixed(0)} 秒的量測資料
+                    </p>
+                )}
                 <div className="modal-actions">
                     <button onClick={onClose}>關閉</button>
                 </div>
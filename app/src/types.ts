@@ -1,19 +1,43 @@
 export interface Config {
-    transport_mode: 'Ip' | 'Serial';
+    transport_mode: 'Ip' | 'Serial' | 'Http';
     ip?: string;
     port?: number;
     serial_port?: string;
+    serial_number?: string;
+    serial_baud_rate?: number;
+    serial_flow_control?: 'None' | 'Software' | 'Hardware';
+    http_url?: string;
     topology: 'Relay' | 'Direct';
+    measurement_mode?: 'Traceroute' | 'Passive' | 'RangeTest';
     test_mode: any; // Simplified for now
     interval_ms: number;
     phase_duration_ms: number;
     cycles: number;
+    target_ci_half_width_db?: number | null;
+    max_duration_ms?: number | null;
+    dry_run?: boolean;
+    /** LNA toggle timings; unset values are derived from the modem preset. */
+    lna_max_attempts?: number | null;
+    lna_wait_timeout_ms?: number | null;
+    lna_ack_timeout_ms?: number | null;
+    settle_ms?: number | null;
+    owner_info_timeout_ms?: number | null;
+    range_test_settle_ms?: number | null;
+    duty_cycle_limit_percent?: number | null;
+    duty_cycle_policy?: 'Stretch' | 'Refuse';
+    channel_util_confound_percent?: number;
+    gps_nmea_port?: string | null;
+    gps_nmea_baud_rate?: number;
+    geo_color_metric?: 'Snr' | 'Rssi';
     output_path: string;
     output_format: 'Csv' | 'Json';
     target_node_id?: string;
     local_node_id?: string;
     roof_node_id?: string;
     mountain_node_id?: string;
+    /** Expected relay hops in travel order; '*' matches any node. */
+    expected_route?: string[] | null;
+    expected_route_back?: string[] | null;
     lna_control_target: 'Disabled' | 'Roof' | 'Mountain';
 }
 
@@ -28,6 +52,24 @@ export interface ProgressState {
     average_stats?: AverageStats;
 }
 
+export type RejectReason =
+    | { kind: 'SnrFloor' }
+    | { kind: 'RouteMismatch'; direction: string; detail: string }
+    | { kind: 'LateReply' };
+
+/** Typed engine events, emitted as `engine-event`; `test-progress` is derived from these. */
+export type EngineEvent =
+    | { type: 'PhaseStarted'; cycle: number; total_cycles: number; phase: string; phase_num: number; eta_seconds: number }
+    | { type: 'PhaseProgress'; cycle: number; phase: string; step: number; total_steps: number; phase_progress: number; total_progress: number; eta_seconds: number }
+    | { type: 'LnaSetRequested'; node: string; enable: boolean; attempt: number; max_attempts: number }
+    | { type: 'LnaVerified'; node: string; enable: boolean }
+    | { type: 'TracerouteSent'; observer: string; target: string; request_id: number }
+    | { type: 'SampleAccepted'; observer: string; phase: string; request_id: number; rtt_ms: number; snr_towards: number[]; snr_back: number[] }
+    | { type: 'SampleRejected'; request_id: number; reason: RejectReason }
+    | { type: 'StatsUpdated'; stats: AverageStats }
+    | { type: 'Warning'; message: string }
+    | { type: 'Finished'; stats: AverageStats };
+
 export interface AverageStats {
     lna_off_samples: number;
     lna_off_roof_to_mtn?: number | null;
@@ -35,4 +77,112 @@ export interface AverageStats {
     lna_on_samples: number;
     lna_on_roof_to_mtn?: number | null;
     lna_on_mtn_to_roof?: number | null;
+    lna_off_airtime_secs?: number;
+    lna_on_airtime_secs?: number;
+    lna_off_rtt_median_ms?: number | null;
+    lna_off_rtt_p90_ms?: number | null;
+    lna_on_rtt_median_ms?: number | null;
+    lna_on_rtt_p90_ms?: number | null;
+    links?: LinkStats[];
+    heard?: HeardNodeStats[];
+    channel_util?: ChannelUtilStats[];
+    confounded_cycles?: number[];
+    lna_off_range_test?: RangeTestSummary | null;
+    lna_on_range_test?: RangeTestSummary | null;
+    delta_ci95_half_width_db?: number | null;
+    lna_off_reboots?: number;
+    lna_on_reboots?: number;
+    lna_off_reboot_excluded_secs?: number;
+    lna_on_reboot_excluded_secs?: number;
+    observers?: ObserverStats[];
+}
+
+export interface RangeTestSummary {
+    expected: number;
+    received: number;
+    loss_ratio?: number | null;
+    rx_snr?: number | null;
+    rx_rssi?: number | null;
+}
+
+export interface ChannelUtilStats {
+    node: string;
+    lna_off_percent?: number | null;
+    lna_on_percent?: number | null;
+}
+
+export interface HeardNodeStats {
+    node: string;
+    lna_off_samples: number;
+    lna_off_rx_snr?: number | null;
+    lna_off_rx_rssi?: number | null;
+    lna_on_samples: number;
+    lna_on_rx_snr?: number | null;
+    lna_on_rx_rssi?: number | null;
+}
+
+export interface ObserverStats {
+    name: string;
+    node?: string | null;
+    lna_off_samples: number;
+    lna_off_snr_at_local?: number | null;
+    lna_off_snr_at_observer?: number | null;
+    lna_off_rtt_median_ms?: number | null;
+    lna_on_samples: number;
+    lna_on_snr_at_local?: number | null;
+    lna_on_snr_at_observer?: number | null;
+    lna_on_rtt_median_ms?: number | null;
+}
+
+export interface LinkStats {
+    from: string;
+    to: string;
+    lna_off_samples: number;
+    lna_off_snr?: number | null;
+    lna_on_samples: number;
+    lna_on_snr?: number | null;
+    lna_off_neighbor_samples?: number;
+    lna_off_neighbor_snr?: number | null;
+    lna_on_neighbor_samples?: number;
+    lna_on_neighbor_snr?: number | null;
+}
+
+export interface PreflightCheck {
+    name: string;
+    status: 'Pass' | 'Warn' | 'Fail';
+    detail: string;
+}
+
+export interface PreflightReport {
+    checks: PreflightCheck[];
+}
+
+export type LogLevel = 'Debug' | 'Info' | 'Warn' | 'Error';
+
+/** A structured log line from msnr-core, emitted as `console-log`. */
+export interface LogRecord {
+    timestamp: string;
+    level: LogLevel;
+    target: string;
+    session?: string | null;
+    message: string;
+}
+
+export interface LogEntry {
+    time: string;
+    level: LogLevel;
+    /** Engine session that logged the line; app messages have none. */
+    session?: string | null;
+    message: string;
+}
+
+export interface DetectedSerialPort {
+    port_name: string;
+    vid?: number | null;
+    pid?: number | null;
+    manufacturer?: string | null;
+    product?: string | null;
+    serial_number?: string | null;
+    chip?: string | null;
+    node_num?: number | null;
 }
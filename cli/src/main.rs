@@ -1,8 +1,14 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use msnr_core::{
-    Config, Engine, IpTransport, SerialTransport, Transport, TransportMode,
-    config::LnaControlTarget,
+    Checkpoint, Config, Engine, HttpTransport, IpTransport, LogLevel, NodeId, SerialFlowControl,
+    SerialTransport, StopHandle, Transport, TransportMode,
+    config::{DutyCyclePolicy, LnaControlTarget, MeasurementMode, PathHop},
+    coverage::{self, CoverageGrid},
+    geo::GeoColorMetric,
+    scheduler::{self, Schedule, Scheduler, TimeWindow},
+    set_log_level,
+    transport::{discover_serial_ports, probe_serial_ports, resolve_serial_port},
 };
 
 #[derive(Parser)]
@@ -10,132 +16,359 @@ use msnr_core::{
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Least severe messages to print (debug, info, warn, error); session log files keep everything
+    #[arg(long, global = true, default_value = "info")]
+    log_level: LogLevel,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Run the test engine
     Run {
-        /// Transport mode (ip or serial)
-        #[arg(long, default_value = "ip")]
-        transport: String,
+        #[command(flatten)]
+        test: TestArgs,
+
+        /// Additional radio to log from, as NAME=tcp://HOST:PORT, NAME=serial:PORT or
+        /// NAME=http://HOST (repeatable)
+        #[arg(long = "observer")]
+        observers: Vec<String>,
+    },
+    /// Merge exported .geojson sample layers into a coverage grid (GeoJSON polygons + CSV)
+    Coverage {
+        /// Sample layers written by previous runs (e.g. results.geojson)
+        #[arg(required = true)]
+        inputs: Vec<std::path::PathBuf>,
 
-        /// IP address (for ip mode)
-        #[arg(long, default_value = "192.168.1.100")]
-        ip: String,
+        /// Use geohash cells of this precision instead of metre squares
+        #[arg(long)]
+        geohash: Option<usize>,
 
-        /// Port (for ip mode)
-        #[arg(long, default_value_t = 4403)]
-        port: u16,
+        /// Edge length of square cells in metres
+        #[arg(long, default_value_t = 100.0)]
+        cell_size: f64,
 
-        /// Serial port (for serial mode)
+        /// Output path without extension; writes <output>.geojson and <output>.csv
+        #[arg(long, default_value = "coverage")]
+        output: std::path::PathBuf,
+    },
+    /// Continue an interrupted run from its checkpoint (e.g. results.checkpoint.json)
+    Resume {
+        /// Checkpoint written next to the interrupted run's output
+        checkpoint: std::path::PathBuf,
+
+        /// Additional radio to log from, as for `run` (repeatable)
+        #[arg(long = "observer")]
+        observers: Vec<String>,
+    },
+    /// Check connectivity, admin access, routes and airtime without changing any config
+    Doctor {
+        #[command(flatten)]
+        test: TestArgs,
+    },
+    /// Run tests unattended on a schedule, one timestamped output file per run
+    Daemon {
+        /// Test config as JSON, with the same fields the desktop app uses
         #[arg(long)]
-        serial: Option<String>,
+        config: std::path::PathBuf,
 
-        /// Target Node ID (for Direct topology)
+        /// First run, as local YYYY-MM-DD HH:MM or RFC 3339 (default: now)
         #[arg(long)]
-        target: Option<String>,
+        start_at: Option<String>,
 
-        /// Roof Node ID (for Relay topology)
+        /// Start a new run this many hours after the previous one started (default: run once)
         #[arg(long)]
-        roof: Option<String>,
+        every_hours: Option<f64>,
 
-        /// Mountain Node ID (for Relay topology)
+        /// Only run within this daily local window, e.g. 01:00-05:00, stopping runs at its end
         #[arg(long)]
-        mountain: Option<String>,
+        window: Option<TimeWindow>,
 
-        /// Topology (Relay or Direct)
-        /// Topology (Relay or Direct)
-        #[arg(long, default_value = "Relay")]
-        topology: String,
+        /// Where the schedule is persisted so a restarted daemon keeps its cadence
+        #[arg(long, default_value = "msnr-schedule.json")]
+        state: std::path::PathBuf,
 
-        /// Phase Duration in seconds
-        #[arg(long, default_value_t = 600)]
-        duration: u64,
+        /// Additional radio to log from, as for `run` (repeatable)
+        #[arg(long = "observer")]
+        observers: Vec<String>,
+    },
+    /// List serial ports and flag likely Meshtastic devices
+    Ports {
+        /// Open each likely device and ask for its node number
+        #[arg(long)]
+        probe: bool,
 
-        /// Number of Cycles
-        #[arg(long, default_value_t = 2)]
-        cycles: u32,
+        /// Serial baud rate used when probing
+        #[arg(long, default_value_t = 115200)]
+        baud: u32,
+    },
+}
 
-        /// Traceroute Interval in seconds
-        #[arg(long, default_value_t = 45)]
-        interval: u64,
+/// Connection and test parameters shared by `run` and `doctor`.
+#[derive(Args)]
+struct TestArgs {
+    /// Transport mode (ip, serial or http)
+    #[arg(long, default_value = "ip")]
+    transport: String,
 
-        /// LNA control target (none, roof, mountain)
-        #[arg(long, default_value = "roof", value_parser = ["none", "roof", "mountain"])]
-        lna_target: String,
-    },
+    /// IP address (for ip mode)
+    #[arg(long, default_value = "192.168.1.100")]
+    ip: String,
+
+    /// Port (for ip mode)
+    #[arg(long, default_value_t = 4403)]
+    port: u16,
+
+    /// Serial port (for serial mode)
+    #[arg(long)]
+    serial: Option<String>,
+
+    /// USB serial number of the local node; overrides --serial (for serial mode)
+    #[arg(long)]
+    serial_number: Option<String>,
+
+    /// Serial baud rate (for serial mode)
+    #[arg(long, default_value_t = 115200)]
+    baud: u32,
+
+    /// Serial flow control (none, software, hardware)
+    #[arg(long, default_value = "none", value_parser = ["none", "software", "hardware"])]
+    flow_control: String,
+
+    /// Node web root, e.g. http://meshtastic.local (for http mode)
+    #[arg(long)]
+    http_url: Option<String>,
+
+    /// Target Node ID (for Direct topology)
+    #[arg(long)]
+    target: Option<NodeId>,
+
+    /// Roof Node ID (for Relay topology)
+    #[arg(long)]
+    roof: Option<NodeId>,
+
+    /// Mountain Node ID (for Relay topology)
+    #[arg(long)]
+    mountain: Option<NodeId>,
+
+    /// Expected forward relay hops, comma-separated; `*` matches any node (default: roof)
+    #[arg(long, value_delimiter = ',')]
+    path: Option<Vec<PathHop>>,
+
+    /// Expected return relay hops, comma-separated (default: reversed --path)
+    #[arg(long, value_delimiter = ',')]
+    return_path: Option<Vec<PathHop>>,
+
+    /// Topology (Relay or Direct)
+    #[arg(long, default_value = "Relay")]
+    topology: String,
+
+    /// Phase Duration in seconds
+    #[arg(long, default_value_t = 600)]
+    duration: u64,
+
+    /// Number of Cycles (the minimum when --target-ci is set)
+    #[arg(long, default_value_t = 2)]
+    cycles: u32,
+
+    /// Keep cycling until the 95% CI of the ON−OFF delta is within ±this many dB
+    #[arg(long, requires = "max_duration")]
+    target_ci: Option<f32>,
+
+    /// Maximum total run time in seconds for adaptive stopping
+    #[arg(long, requires = "target_ci")]
+    max_duration: Option<u64>,
+
+    /// Read remote configs and log what would change, but never send SetConfig;
+    /// phases are cut to 30 s and results go to <stem>.dryrun.* without a checkpoint
+    #[arg(long)]
+    dry_run: bool,
+
+    /// How to measure: send traceroutes, passively log existing traffic, or run the
+    /// remote node's Range Test module (traceroute, passive, rangetest)
+    #[arg(long, default_value = "traceroute", value_parser = ["traceroute", "passive", "rangetest"])]
+    measurement: String,
+
+    /// Traceroute Interval in seconds
+    #[arg(long, default_value_t = 45)]
+    interval: u64,
+
+    /// LNA control target (none, roof, mountain)
+    #[arg(long, default_value = "roof", value_parser = ["none", "roof", "mountain"])]
+    lna_target: String,
+
+    /// Duty-cycle limit for our own transmissions in percent (e.g. 10 for EU868)
+    #[arg(long)]
+    duty_cycle: Option<f32>,
+
+    /// What to do when the interval exceeds the duty-cycle limit (stretch, refuse)
+    #[arg(long, default_value = "stretch", value_parser = ["stretch", "refuse"])]
+    duty_cycle_policy: String,
+
+    /// Flag cycles whose OFF/ON channel utilization differs by more than this many percentage points
+    #[arg(long, default_value_t = 5.0)]
+    channel_util_threshold: f32,
+
+    /// NMEA GPS serial port, used when the local node has no recent position
+    #[arg(long)]
+    gps: Option<String>,
+
+    /// NMEA GPS baud rate
+    #[arg(long, default_value_t = 9600)]
+    gps_baud: u32,
+
+    /// Reading used to colour the GeoJSON/KML layers (snr, rssi)
+    #[arg(long, default_value = "snr", value_parser = ["snr", "rssi"])]
+    geo_color: String,
+
+    /// How many times to try each RX Boosted Gain change (default 10)
+    #[arg(long)]
+    lna_attempts: Option<u32>,
+
+    /// Seconds to wait for the remote LoRa config (default derived from the modem preset)
+    #[arg(long)]
+    lna_wait_timeout: Option<u64>,
+
+    /// Seconds to wait for the SetConfig ACK (default derived from the modem preset)
+    #[arg(long)]
+    lna_ack_timeout: Option<u64>,
+
+    /// Seconds to let the node settle after a config change (default derived from the modem preset)
+    #[arg(long)]
+    settle: Option<u64>,
+
+    /// Seconds to wait for the local node's owner info (default 3)
+    #[arg(long)]
+    owner_timeout: Option<u64>,
+
+    /// Seconds to let the node reboot after a range test config change (default 30)
+    #[arg(long)]
+    range_test_settle: Option<u64>,
+}
+
+impl TestArgs {
+    fn to_config(&self) -> Config {
+        let mut config = Config::default();
+
+        // Set Test Parameters
+        config.phase_duration_ms = self.duration * 1000;
+        config.cycles = self.cycles;
+        config.target_ci_half_width_db = self.target_ci;
+        config.max_duration_ms = self.max_duration.map(|secs| secs * 1000);
+        config.interval_ms = self.interval * 1000;
+        config.dry_run = self.dry_run;
+        config.lna_max_attempts = self.lna_attempts;
+        config.lna_wait_timeout_ms = self.lna_wait_timeout.map(|secs| secs * 1000);
+        config.lna_ack_timeout_ms = self.lna_ack_timeout.map(|secs| secs * 1000);
+        config.settle_ms = self.settle.map(|secs| secs * 1000);
+        config.owner_info_timeout_ms = self.owner_timeout.map(|secs| secs * 1000);
+        config.range_test_settle_ms = self.range_test_settle.map(|secs| secs * 1000);
+        config.measurement_mode = match self.measurement.as_str() {
+            "passive" => MeasurementMode::Passive,
+            "rangetest" => MeasurementMode::RangeTest,
+            _ => MeasurementMode::Traceroute,
+        };
+
+        let lna_target_value = self.lna_target.to_lowercase();
+        config.lna_control_target = match lna_target_value.as_str() {
+            "none" => LnaControlTarget::Disabled,
+            "mountain" => LnaControlTarget::Mountain,
+            _ => LnaControlTarget::Roof,
+        };
+
+        config.duty_cycle_limit_percent = self.duty_cycle;
+        config.channel_util_confound_percent = self.channel_util_threshold;
+        config.gps_nmea_port = self.gps.clone();
+        config.gps_nmea_baud_rate = self.gps_baud;
+        config.geo_color_metric = match self.geo_color.as_str() {
+            "rssi" => GeoColorMetric::Rssi,
+            _ => GeoColorMetric::Snr,
+        };
+        config.duty_cycle_policy = match self.duty_cycle_policy.as_str() {
+            "refuse" => DutyCyclePolicy::Refuse,
+            _ => DutyCyclePolicy::Stretch,
+        };
+
+        // Set Node IDs
+        config.target_node_id = self.target;
+        config.roof_node_id = self.roof;
+        config.mountain_node_id = self.mountain;
+        config.expected_route = self.path.clone();
+        config.expected_route_back = self.return_path.clone();
+
+        // Set Topology
+        config.topology = match self.topology.to_lowercase().as_str() {
+            "direct" => msnr_core::config::Topology::Direct,
+            _ => msnr_core::config::Topology::Relay,
+        };
+
+        // Set Transport; the serial port itself is resolved when the transport is built
+        match self.transport.as_str() {
+            "serial" => {
+                config.transport_mode = TransportMode::Serial;
+                config.serial_port = self.serial.clone();
+                config.serial_number = self.serial_number.clone();
+                config.serial_baud_rate = self.baud;
+                config.serial_flow_control = match self.flow_control.as_str() {
+                    "software" => SerialFlowControl::Software,
+                    "hardware" => SerialFlowControl::Hardware,
+                    _ => SerialFlowControl::None,
+                };
+            }
+            "http" => {
+                config.transport_mode = TransportMode::Http;
+                config.http_url = self.http_url.clone();
+            }
+            _ => {
+                config.transport_mode = TransportMode::Ip;
+                config.ip = Some(self.ip.clone());
+                config.port = Some(self.port);
+            }
+        }
+
+        config
+    }
+}
+
+/// The first Ctrl-C stops the run once the remote config is restored; a second exits at once.
+fn stop_on_ctrl_c(stop: StopHandle) {
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!("\nStopping, restoring remote config (Ctrl-C again to quit immediately)...");
+        stop.stop();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
     eprintln!("CLI parsed successfully.");
+    set_log_level(cli.log_level);
 
     match &cli.command {
-        Some(Commands::Run {
-            transport,
-            ip,
-            port,
-            serial,
-            target,
-            roof,
-            mountain,
-            topology,
-            duration,
-            cycles,
-            interval,
-            lna_target,
-        }) => {
+        Some(Commands::Run { test, observers }) => {
             println!("Starting MSNR Tool CLI...");
             use std::io::Write;
             let _ = std::io::stdout().flush();
 
-            let mut config = Config::default();
-
-            // Set Test Parameters
-            config.phase_duration_ms = duration * 1000;
-            config.cycles = *cycles;
-            config.interval_ms = interval * 1000;
-
-            let lna_target_value = lna_target.to_lowercase();
-            config.lna_control_target = match lna_target_value.as_str() {
-                "none" => LnaControlTarget::Disabled,
-                "mountain" => LnaControlTarget::Mountain,
-                _ => LnaControlTarget::Roof,
-            };
-
-            // Set Node IDs
-            config.target_node_id = target.clone();
-            config.roof_node_id = roof.clone();
-            config.mountain_node_id = mountain.clone();
-
-            // Set Topology
-            config.topology = match topology.to_lowercase().as_str() {
-                "direct" => msnr_core::config::Topology::Direct,
-                _ => msnr_core::config::Topology::Relay,
-            };
-
-            let transport_impl: Box<dyn Transport> = match transport.as_str() {
-                "serial" => {
-                    config.transport_mode = TransportMode::Serial;
-                    if let Some(s) = serial {
-                        config.serial_port = Some(s.clone());
-                        Box::new(SerialTransport::new(s.clone()))
-                    } else {
-                        eprintln!("Error: --serial is required for serial transport");
+            let config = test.to_config();
+            let transport_impl = transport_from_config(&config)?;
+            let mut engine = Engine::new(config, transport_impl);
+            for spec in observers {
+                match parse_observer(spec) {
+                    Ok((name, observer)) => engine.add_observer(name, observer),
+                    Err(e) => {
+                        eprintln!("Error: {e}");
                         return Ok(());
                     }
                 }
-                _ => {
-                    config.transport_mode = TransportMode::Ip;
-                    config.ip = Some(ip.clone());
-                    config.port = Some(*port);
-                    Box::new(IpTransport::new(ip.clone(), *port))
-                }
-            };
-
-            let mut engine = Engine::new(config, transport_impl);
+            }
+            stop_on_ctrl_c(engine.stop_handle());
 
             engine
                 .run(|progress| {
@@ -153,6 +386,163 @@ async fn main() -> Result<()> {
 
             println!("\nTest completed!");
         }
+        Some(Commands::Coverage {
+            inputs,
+            geohash,
+            cell_size,
+            output,
+        }) => {
+            let grid = match geohash {
+                Some(precision) if (1..=12).contains(precision) => {
+                    CoverageGrid::Geohash(*precision)
+                }
+                Some(precision) => {
+                    anyhow::bail!("geohash precision must be 1..=12, got {}", precision)
+                }
+                None if *cell_size > 0.0 => CoverageGrid::Metres(*cell_size),
+                None => anyhow::bail!("cell size must be positive, got {}", cell_size),
+            };
+
+            let mut points = Vec::new();
+            for input in inputs {
+                let samples = coverage::load_geojson_samples(input)?;
+                println!("{}: {} samples", input.display(), samples.len());
+                points.extend(samples);
+            }
+
+            let cells = coverage::aggregate(&points, grid);
+            let geojson_path = output.with_extension("geojson");
+            let csv_path = output.with_extension("csv");
+            coverage::write_cells_geojson(&geojson_path, &cells)?;
+            coverage::write_cells_csv(&csv_path, &cells)?;
+            println!(
+                "{} samples in {} cells -> {}, {}",
+                points.len(),
+                cells.len(),
+                geojson_path.display(),
+                csv_path.display()
+            );
+        }
+        Some(Commands::Doctor { test }) => {
+            let config = test.to_config();
+            let transport = transport_from_config(&config)?;
+            let report = Engine::new(config, transport).preflight().await?;
+            if !report.passed() {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Resume {
+            checkpoint,
+            observers,
+        }) => {
+            let checkpoint = Checkpoint::load(checkpoint)?;
+            println!(
+                "Resuming {} after cycle {} phase {}...",
+                checkpoint.config.output_path,
+                checkpoint.cycle + 1,
+                checkpoint.phase_num
+            );
+            let transport = transport_from_config(&checkpoint.config)?;
+            let mut engine = Engine::new(checkpoint.config.clone(), transport);
+            for spec in observers {
+                let (name, observer) = parse_observer(spec)?;
+                engine.add_observer(name, observer);
+            }
+            stop_on_ctrl_c(engine.stop_handle());
+
+            engine
+                .resume(checkpoint, |progress| {
+                    println!(
+                        "[{}] {:.1}% | {}",
+                        progress_bar(progress.total_progress),
+                        progress.total_progress * 100.0,
+                        progress.status_message
+                    );
+                })
+                .await?;
+
+            println!("\nTest completed!");
+        }
+        Some(Commands::Daemon {
+            config,
+            start_at,
+            every_hours,
+            window,
+            state,
+            observers,
+        }) => {
+            let config = Config::from_json_file(config)?;
+            let schedule = Schedule {
+                start_at: start_at
+                    .as_deref()
+                    .map(scheduler::parse_start_at)
+                    .transpose()?,
+                repeat_every_hours: *every_hours,
+                window: *window,
+            };
+            for spec in observers {
+                parse_observer(spec)?;
+            }
+
+            let mut scheduler = Scheduler::new(config, schedule, state)?;
+            if let Some(next) = scheduler.state().next_run {
+                println!("Next run at {}", next.to_rfc3339());
+            }
+            stop_on_ctrl_c(scheduler.stop_handle());
+            scheduler
+                .run(
+                    |config| {
+                        let transport = transport_from_config(&config)?;
+                        let mut engine = Engine::new(config, transport);
+                        for spec in observers {
+                            let (name, observer) = parse_observer(spec)?;
+                            engine.add_observer(name, observer);
+                        }
+                        Ok(engine)
+                    },
+                    |progress| {
+                        println!(
+                            "[{}] {:.1}% | {}",
+                            progress_bar(progress.total_progress),
+                            progress.total_progress * 100.0,
+                            progress.status_message
+                        );
+                    },
+                )
+                .await?;
+        }
+        Some(Commands::Ports { probe, baud }) => {
+            let mut ports = discover_serial_ports();
+            if *probe {
+                ports = probe_serial_ports(ports, *baud).await;
+            }
+
+            if ports.is_empty() {
+                println!("No serial ports found.");
+            }
+            for port in ports {
+                let usb_id = match (port.vid, port.pid) {
+                    (Some(vid), Some(pid)) => format!("{:04x}:{:04x}", vid, pid),
+                    _ => "----:----".to_string(),
+                };
+                println!(
+                    "{} {} {} | {} | S/N {} | {}{}",
+                    if port.is_likely_meshtastic() {
+                        "*"
+                    } else {
+                        " "
+                    },
+                    port.port_name,
+                    usb_id,
+                    port.manufacturer.as_deref().unwrap_or("-"),
+                    port.serial_number.as_deref().unwrap_or("-"),
+                    port.chip.as_deref().unwrap_or("unknown chip"),
+                    port.node_num
+                        .map(|num| format!(" | node !{:08x}", num))
+                        .unwrap_or_default()
+                );
+            }
+        }
         None => {
             println!("No command specified. Use --help for usage.");
         }
@@ -161,6 +551,55 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+fn parse_observer(spec: &str) -> Result<(String, Box<dyn Transport>)> {
+    let (name, target) = spec
+        .split_once('=')
+        .ok_or_else(|| anyhow::anyhow!("invalid --observer '{spec}', expected NAME=TRANSPORT"))?;
+
+    let transport: Box<dyn Transport> = if let Some(port) = target.strip_prefix("serial:") {
+        Box::new(SerialTransport::new(port.to_string()))
+    } else if target.starts_with("http://") || target.starts_with("https://") {
+        Box::new(HttpTransport::new(target.to_string())?)
+    } else {
+        let addr = target.strip_prefix("tcp://").unwrap_or(target);
+        let (host, port) = match addr.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>()?),
+            None => (addr, 4403),
+        };
+        Box::new(IpTransport::new(host.to_string(), port))
+    };
+
+    Ok((name.to_string(), transport))
+}
+
+/// Builds the local-node transport described by a loaded config.
+fn transport_from_config(config: &Config) -> Result<Box<dyn Transport>> {
+    Ok(match config.transport_mode {
+        TransportMode::Serial => {
+            let port_name = resolve_serial_port(
+                config.serial_port.as_deref(),
+                config.serial_number.as_deref(),
+            )?;
+            Box::new(SerialTransport::with_settings(
+                port_name,
+                config.serial_baud_rate,
+                config.serial_flow_control,
+            ))
+        }
+        TransportMode::Ip => Box::new(IpTransport::new(
+            config.ip.clone().unwrap_or("127.0.0.1".to_string()),
+            config.port.unwrap_or(4403),
+        )),
+        TransportMode::Http => {
+            let url = config
+                .http_url
+                .clone()
+                .ok_or_else(|| anyhow::anyhow!("HTTP URL not specified"))?;
+            Box::new(HttpTransport::new(url)?)
+        }
+    })
+}
+
 fn progress_bar(progress: f32) -> String {
     let width = 20;
     let filled = (progress * width as f32) as usize;
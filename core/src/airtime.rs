@@ -0,0 +1,225 @@
+use meshtastic::protobufs::config::LoRaConfig;
+use meshtastic::protobufs::config::lo_ra_config::ModemPreset;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Meshtastic firmware uses a 16 symbol preamble on every preset.
+const PREAMBLE_SYMBOLS: f64 = 16.0;
+/// Unencrypted Meshtastic radio header prepended to every packet.
+const MESH_HEADER_BYTES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoraParams {
+    pub spreading_factor: u32,
+    pub bandwidth_hz: f64,
+    /// Coding rate denominator, 5..=8 for 4/5..4/8.
+    pub coding_rate: u32,
+}
+
+impl LoraParams {
+    /// Resolves the effective modem parameters, expanding `use_preset` the same way firmware does.
+    pub fn from_lora_config(lora: &LoRaConfig) -> Option<Self> {
+        if !lora.use_preset {
+            if lora.spread_factor == 0 || lora.bandwidth == 0 {
+                return None;
+            }
+            return Some(Self {
+                spreading_factor: lora.spread_factor,
+                bandwidth_hz: Self::custom_bandwidth_hz(lora.bandwidth),
+                coding_rate: lora.coding_rate.clamp(5, 8),
+            });
+        }
+
+        let (spreading_factor, bandwidth_khz, coding_rate) =
+            match ModemPreset::try_from(lora.modem_preset).ok()? {
+                ModemPreset::ShortTurbo => (7, 500.0, 5),
+                ModemPreset::ShortFast => (7, 250.0, 5),
+                ModemPreset::ShortSlow => (8, 250.0, 5),
+                ModemPreset::MediumFast => (9, 250.0, 5),
+                ModemPreset::MediumSlow => (10, 250.0, 5),
+                ModemPreset::LongFast => (11, 250.0, 5),
+                ModemPreset::LongModerate => (11, 125.0, 8),
+                ModemPreset::LongSlow => (12, 125.0, 8),
+                #[allow(deprecated)]
+                ModemPreset::VeryLongSlow => (12, 62.5, 8),
+                #[allow(unreachable_patterns)]
+                _ => return None,
+            };
+
+        Some(Self {
+            spreading_factor,
+            bandwidth_hz: bandwidth_khz * 1000.0,
+            coding_rate,
+        })
+    }
+
+    /// Firmware accepts the nominal values 31/62/125/250/500 and maps them to real bandwidths.
+    fn custom_bandwidth_hz(bandwidth_khz: u32) -> f64 {
+        match bandwidth_khz {
+            31 => 31_250.0,
+            62 => 62_500.0,
+            khz => khz as f64 * 1000.0,
+        }
+    }
+
+    /// Time on air for a packet carrying `payload_bytes` after the mesh header (Semtech AN1200.13).
+    pub fn packet_airtime(&self, payload_bytes: usize) -> Duration {
+        let sf = self.spreading_factor as f64;
+        let symbol_secs = 2f64.powf(sf) / self.bandwidth_hz;
+        let low_data_rate_optimize = if symbol_secs > 0.016 { 1.0 } else { 0.0 };
+        let total_bytes = (payload_bytes + MESH_HEADER_BYTES) as f64;
+
+        // Explicit header, CRC on
+        let numerator = 8.0 * total_bytes - 4.0 * sf + 28.0 + 16.0;
+        let denominator = 4.0 * (sf - 2.0 * low_data_rate_optimize);
+        let payload_symbols =
+            8.0 + ((numerator / denominator).ceil() * self.coding_rate as f64).max(0.0);
+
+        let preamble_secs = (PREAMBLE_SYMBOLS + 4.25) * symbol_secs;
+        Duration::from_secs_f64(preamble_secs + payload_symbols * symbol_secs)
+    }
+}
+
+/// Rolling record of our own transmissions, used to enforce a duty-cycle limit.
+#[derive(Debug)]
+pub struct AirtimeTracker {
+    window: Duration,
+    transmissions: VecDeque<(Instant, Duration)>,
+}
+
+impl AirtimeTracker {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            transmissions: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, now: Instant, airtime: Duration) {
+        self.prune(now);
+        self.transmissions.push_back((now, airtime));
+    }
+
+    pub fn used(&mut self, now: Instant) -> Duration {
+        self.prune(now);
+        self.transmissions.iter().map(|(_, airtime)| *airtime).sum()
+    }
+
+    /// Percentage of the window that would be used after transmitting `airtime` now.
+    pub fn utilization_with(&mut self, now: Instant, airtime: Duration) -> f32 {
+        let used = self.used(now) + airtime;
+        (used.as_secs_f64() / self.window.as_secs_f64() * 100.0) as f32
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some((sent_at, _)) = self.transmissions.front() {
+            if now.duration_since(*sent_at) > self.window {
+                self.transmissions.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(modem_preset: ModemPreset) -> LoRaConfig {
+        LoRaConfig {
+            use_preset: true,
+            modem_preset: modem_preset as i32,
+            ..Default::default()
+        }
+    }
+
+    fn assert_airtime_ms(airtime: Duration, expected_ms: f64) {
+        let actual_ms = airtime.as_secs_f64() * 1000.0;
+        assert!(
+            (actual_ms - expected_ms).abs() < 0.001,
+            "airtime {actual_ms} ms, expected {expected_ms} ms"
+        );
+    }
+
+    #[test]
+    fn presets_expand_like_firmware() {
+        assert_eq!(
+            LoraParams::from_lora_config(&preset(ModemPreset::LongFast)),
+            Some(LoraParams {
+                spreading_factor: 11,
+                bandwidth_hz: 250_000.0,
+                coding_rate: 5,
+            })
+        );
+        assert_eq!(
+            LoraParams::from_lora_config(&preset(ModemPreset::LongSlow)),
+            Some(LoraParams {
+                spreading_factor: 12,
+                bandwidth_hz: 125_000.0,
+                coding_rate: 8,
+            })
+        );
+    }
+
+    #[test]
+    fn custom_settings_map_nominal_bandwidths() {
+        let lora = LoRaConfig {
+            use_preset: false,
+            spread_factor: 10,
+            bandwidth: 62,
+            coding_rate: 9,
+            ..Default::default()
+        };
+        assert_eq!(
+            LoraParams::from_lora_config(&lora),
+            Some(LoraParams {
+                spreading_factor: 10,
+                bandwidth_hz: 62_500.0,
+                coding_rate: 8,
+            })
+        );
+
+        let unset = LoRaConfig {
+            use_preset: false,
+            ..Default::default()
+        };
+        assert_eq!(LoraParams::from_lora_config(&unset), None);
+    }
+
+    #[test]
+    fn packet_airtime_matches_semtech_formula() {
+        let short_turbo = LoraParams::from_lora_config(&preset(ModemPreset::ShortTurbo)).unwrap();
+        assert_airtime_ms(short_turbo.packet_airtime(12), 18.752);
+
+        let long_fast = LoraParams::from_lora_config(&preset(ModemPreset::LongFast)).unwrap();
+        assert_airtime_ms(long_fast.packet_airtime(12), 477.184);
+    }
+
+    #[test]
+    fn packet_airtime_uses_low_data_rate_optimize_for_long_symbols() {
+        // 32.768 ms symbols; without the optimization this would be 2236.416 ms
+        let long_slow = LoraParams::from_lora_config(&preset(ModemPreset::LongSlow)).unwrap();
+        assert_airtime_ms(long_slow.packet_airtime(12), 2498.56);
+    }
+
+    #[test]
+    fn tracker_forgets_transmissions_outside_the_window() {
+        let mut tracker = AirtimeTracker::new(Duration::from_secs(60));
+        let start = Instant::now();
+        tracker.record(start, Duration::from_secs(1));
+        tracker.record(start + Duration::from_secs(30), Duration::from_secs(2));
+
+        assert_eq!(
+            tracker.used(start + Duration::from_secs(30)),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            tracker.used(start + Duration::from_secs(61)),
+            Duration::from_secs(2)
+        );
+        let utilization =
+            tracker.utilization_with(start + Duration::from_secs(61), Duration::from_secs(1));
+        assert!((utilization - 5.0).abs() < 1e-4);
+    }
+}
@@ -1,9 +1,39 @@
+use crate::airtime::LoraParams;
+use crate::geo::GeoColorMetric;
+use crate::node_id::{self, NodeId};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+
+const DEFAULT_LNA_MAX_ATTEMPTS: u32 = 10;
+/// Floors for the airtime-derived defaults, enough for a fast preset over one hop.
+const MIN_LNA_TIMEOUT_MS: u64 = 30_000;
+const MIN_SETTLE_MS: u64 = 5_000;
+const DEFAULT_OWNER_INFO_TIMEOUT_MS: u64 = 3_000;
+/// Changing a module config reboots the remote node; time given for it to come back.
+const DEFAULT_RANGE_TEST_SETTLE_MS: u64 = 30_000;
+/// Longest settle pause accepted; anything above is almost certainly a unit mistake.
+const MAX_SETTLE_MS: u64 = 600_000;
+/// Phases are capped at this length in dry-run mode.
+const DRY_RUN_PHASE_MS: u64 = 30_000;
+/// Approximate encoded size of a PKI-encrypted admin request or LoRa config response.
+const ADMIN_PACKET_BYTES: usize = 96;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TransportMode {
     Ip,
     Serial,
+    Http,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum SerialFlowControl {
+    #[default]
+    None,
+    Software,
+    Hardware,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -39,6 +69,78 @@ pub enum OutputFormat {
     Json,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum DutyCyclePolicy {
+    /// Lengthen the traceroute interval until it fits the limit.
+    #[default]
+    Stretch,
+    /// Abort the run instead of changing the requested schedule.
+    Refuse,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum MeasurementMode {
+    /// Actively traceroute the target and read per-hop SNR from the replies.
+    #[default]
+    Traceroute,
+    /// Send nothing; record the local radio's reception of existing traffic.
+    Passive,
+    /// Enable the remote node's Range Test module and measure its sequenced packets.
+    RangeTest,
+}
+
+/// One intermediate hop of an expected relay path; `*` accepts any single node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum PathHop {
+    Node(NodeId),
+    Any,
+}
+
+impl PathHop {
+    pub fn matches(self, node: NodeId) -> bool {
+        match self {
+            PathHop::Node(expected) => expected == node,
+            PathHop::Any => true,
+        }
+    }
+}
+
+impl std::str::FromStr for PathHop {
+    type Err = node_id::NodeIdParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if input.trim() == "*" {
+            Ok(PathHop::Any)
+        } else {
+            input.parse().map(PathHop::Node)
+        }
+    }
+}
+
+impl TryFrom<String> for PathHop {
+    type Error = node_id::NodeIdParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<PathHop> for String {
+    fn from(hop: PathHop) -> Self {
+        hop.to_string()
+    }
+}
+
+impl fmt::Display for PathHop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathHop::Node(id) => write!(f, "{}", id),
+            PathHop::Any => write!(f, "*"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum LnaControlTarget {
     Disabled,
@@ -46,6 +148,39 @@ pub enum LnaControlTarget {
     Mountain,
 }
 
+/// Retry count and timeouts in effect for a run, after defaults are filled in.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    pub lna_max_attempts: u32,
+    pub lna_wait_timeout_ms: u64,
+    pub lna_ack_timeout_ms: u64,
+    pub settle_ms: u64,
+    pub owner_info_timeout_ms: u64,
+    pub range_test_settle_ms: u64,
+}
+
+impl Timings {
+    pub fn lna_wait_timeout(&self) -> Duration {
+        Duration::from_millis(self.lna_wait_timeout_ms)
+    }
+
+    pub fn lna_ack_timeout(&self) -> Duration {
+        Duration::from_millis(self.lna_ack_timeout_ms)
+    }
+
+    pub fn settle(&self) -> Duration {
+        Duration::from_millis(self.settle_ms)
+    }
+
+    pub fn owner_info_timeout(&self) -> Duration {
+        Duration::from_millis(self.owner_info_timeout_ms)
+    }
+
+    pub fn range_test_settle(&self) -> Duration {
+        Duration::from_millis(self.range_test_settle_ms)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     // Connection
@@ -53,22 +188,99 @@ pub struct Config {
     pub ip: Option<String>,
     pub port: Option<u16>,
     pub serial_port: Option<String>,
+    /// USB serial number of the local node; takes precedence over `serial_port`
+    /// so `/dev/ttyUSB*` renumbering doesn't break unattended runs.
+    #[serde(default)]
+    pub serial_number: Option<String>,
+    #[serde(default = "default_serial_baud_rate")]
+    pub serial_baud_rate: u32,
+    #[serde(default)]
+    pub serial_flow_control: SerialFlowControl,
+    #[serde(default)]
+    pub http_url: Option<String>,
 
     // Topology & Test Mode
     pub topology: Topology,
     pub test_mode: TestMode,
+    #[serde(default)]
+    pub measurement_mode: MeasurementMode,
 
     // Test Parameters
     pub interval_ms: u64,
     pub phase_duration_ms: u64,
     pub cycles: u32,
     pub scan_duration_ms: Option<u64>,
+    /// Adaptive stopping: keep cycling (at least `cycles` times) until the 95% CI
+    /// half-width of the ON−OFF SNR delta is at or below this many dB.
+    #[serde(default)]
+    pub target_ci_half_width_db: Option<f32>,
+    /// Upper bound on total run time when adaptive stopping is enabled.
+    #[serde(default)]
+    pub max_duration_ms: Option<u64>,
+    /// Read and log remote configs but never change them, with phases cut short,
+    /// to validate a plan on real hardware. Output goes to `<stem>.dryrun.*`.
+    #[serde(default)]
+    pub dry_run: bool,
+
+    // Airtime budget
+    /// Maximum share of airtime (in %) our own transmissions may use over a rolling hour.
+    #[serde(default)]
+    pub duty_cycle_limit_percent: Option<f32>,
+    #[serde(default)]
+    pub duty_cycle_policy: DutyCyclePolicy,
+
+    // Telemetry
+    /// A cycle is flagged when a node's mean channel utilization differs by more than
+    /// this many percentage points between its LNA OFF and ON phases.
+    #[serde(default = "default_channel_util_confound_percent")]
+    pub channel_util_confound_percent: f32,
+
+    // Timing (unset values are derived from the local modem preset)
+    /// SetConfig/verify attempts before a run is aborted.
+    #[serde(default)]
+    pub lna_max_attempts: Option<u32>,
+    /// How long to wait for a remote config response.
+    #[serde(default)]
+    pub lna_wait_timeout_ms: Option<u64>,
+    /// How long to listen for an answer after sending SetConfig.
+    #[serde(default)]
+    pub lna_ack_timeout_ms: Option<u64>,
+    /// Pause after toggling the LNA before measuring.
+    #[serde(default)]
+    pub settle_ms: Option<u64>,
+    /// How long to wait for the local node's owner info.
+    #[serde(default)]
+    pub owner_info_timeout_ms: Option<u64>,
+    /// Pause after changing the range test module config, which reboots the node.
+    #[serde(default)]
+    pub range_test_settle_ms: Option<u64>,
+
+    // Geotagging
+    /// Serial port of an NMEA GPS used when the local node has no recent position.
+    #[serde(default)]
+    pub gps_nmea_port: Option<String>,
+    #[serde(default = "default_gps_nmea_baud_rate")]
+    pub gps_nmea_baud_rate: u32,
+    #[serde(default)]
+    pub geo_color_metric: GeoColorMetric,
 
     // Node IDs
-    pub local_node_id: Option<String>,
-    pub roof_node_id: Option<String>,
-    pub mountain_node_id: Option<String>,
-    pub target_node_id: Option<String>,
+    #[serde(default, deserialize_with = "node_id::deserialize_optional")]
+    pub local_node_id: Option<NodeId>,
+    #[serde(default, deserialize_with = "node_id::deserialize_optional")]
+    pub roof_node_id: Option<NodeId>,
+    #[serde(default, deserialize_with = "node_id::deserialize_optional")]
+    pub mountain_node_id: Option<NodeId>,
+    #[serde(default, deserialize_with = "node_id::deserialize_optional")]
+    pub target_node_id: Option<NodeId>,
+
+    // Relay paths (intermediate hops only, in travel order)
+    /// Expected forward hops from local to mountain; defaults to `[roof]`.
+    #[serde(default)]
+    pub expected_route: Option<Vec<PathHop>>,
+    /// Expected return hops from mountain to local; defaults to the reversed forward path.
+    #[serde(default)]
+    pub expected_route_back: Option<Vec<PathHop>>,
 
     // LNA Control
     pub lna_control_target: LnaControlTarget,
@@ -78,6 +290,18 @@ pub struct Config {
     pub output_format: OutputFormat,
 }
 
+fn default_serial_baud_rate() -> u32 {
+    115200
+}
+
+fn default_channel_util_confound_percent() -> f32 {
+    5.0
+}
+
+fn default_gps_nmea_baud_rate() -> u32 {
+    9600
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -85,19 +309,333 @@ impl Default for Config {
             ip: Some("192.168.1.100".to_string()),
             port: Some(4403),
             serial_port: None,
+            serial_number: None,
+            serial_baud_rate: default_serial_baud_rate(),
+            serial_flow_control: SerialFlowControl::None,
+            http_url: None,
             topology: Topology::Relay,
             test_mode: TestMode::Relay(RelayTestMode::RoofOnly),
             interval_ms: 30000,
             phase_duration_ms: 450000, // Default 7.5 minutes per phase (15 minutes per cycle)
             cycles: 2,
             scan_duration_ms: None,
+            target_ci_half_width_db: None,
+            max_duration_ms: None,
+            dry_run: false,
+            duty_cycle_limit_percent: None,
+            duty_cycle_policy: DutyCyclePolicy::Stretch,
+            measurement_mode: MeasurementMode::Traceroute,
+            channel_util_confound_percent: default_channel_util_confound_percent(),
+            lna_max_attempts: None,
+            lna_wait_timeout_ms: None,
+            lna_ack_timeout_ms: None,
+            settle_ms: None,
+            owner_info_timeout_ms: None,
+            range_test_settle_ms: None,
+            gps_nmea_port: None,
+            gps_nmea_baud_rate: default_gps_nmea_baud_rate(),
+            geo_color_metric: GeoColorMetric::Snr,
             local_node_id: None,
             roof_node_id: None,
             mountain_node_id: None,
             target_node_id: None,
+            expected_route: None,
+            expected_route_back: None,
             lna_control_target: LnaControlTarget::Roof,
             output_path: "results.csv".to_string(),
             output_format: OutputFormat::Csv,
         }
     }
 }
+
+impl Config {
+    /// Reads a config saved as JSON, with the same field names the desktop app uses.
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Rejects configurations that would address the wrong node before anything is transmitted.
+    pub fn validate(&self) -> Result<()> {
+        let nodes = [
+            ("local", self.local_node_id),
+            ("roof", self.roof_node_id),
+            ("mountain", self.mountain_node_id),
+            ("target", self.target_node_id),
+        ];
+        for (role, id) in nodes {
+            if let Some(id) = id {
+                if id.is_broadcast() || id.is_local() {
+                    anyhow::bail!("{} node ID {} is not a unicast node", role, id);
+                }
+            }
+        }
+
+        if let Some(limit) = self.duty_cycle_limit_percent {
+            if !(limit > 0.0 && limit <= 100.0) {
+                anyhow::bail!("duty cycle limit must be within (0, 100] %, got {}", limit);
+            }
+        }
+
+        if self.channel_util_confound_percent.is_nan() || self.channel_util_confound_percent < 0.0 {
+            anyhow::bail!(
+                "channel utilization confound threshold must be non-negative, got {}",
+                self.channel_util_confound_percent
+            );
+        }
+
+        if self.max_duration_ms.is_some() && self.target_ci_half_width_db.is_none() {
+            anyhow::bail!("max_duration_ms only applies to adaptive stopping; set a target CI");
+        }
+        if let Some(target) = self.target_ci_half_width_db {
+            if !(target > 0.0 && target.is_finite()) {
+                anyhow::bail!(
+                    "target CI half-width must be a positive number of dB, got {}",
+                    target
+                );
+            }
+            let cycle_ms = self.effective_phase_duration_ms() * 2;
+            match self.max_duration_ms {
+                None => anyhow::bail!("adaptive stopping requires max_duration_ms"),
+                Some(max) if max < cycle_ms => anyhow::bail!(
+                    "max duration ({} ms) is shorter than one ON/OFF cycle ({} ms)",
+                    max,
+                    cycle_ms
+                ),
+                Some(_) => {}
+            }
+        }
+
+        if self.lna_max_attempts == Some(0) {
+            anyhow::bail!("LNA attempts must be at least 1");
+        }
+        let timeouts = [
+            ("LNA wait timeout", self.lna_wait_timeout_ms),
+            ("LNA ack timeout", self.lna_ack_timeout_ms),
+            ("owner info timeout", self.owner_info_timeout_ms),
+        ];
+        for (name, timeout) in timeouts {
+            if timeout == Some(0) {
+                anyhow::bail!("{} must be greater than 0 ms", name);
+            }
+        }
+        let settles = [
+            ("settle time", self.settle_ms),
+            ("range test settle time", self.range_test_settle_ms),
+        ];
+        for (name, settle) in settles {
+            if let Some(settle) = settle {
+                if settle == 0 || settle > MAX_SETTLE_MS {
+                    anyhow::bail!(
+                        "{} must be within 1..={} ms, got {}",
+                        name,
+                        MAX_SETTLE_MS,
+                        settle
+                    );
+                }
+            }
+        }
+
+        match self.topology {
+            Topology::Relay => {
+                if self.roof_node_id.is_none() && self.expected_route.is_none() {
+                    anyhow::bail!("Relay topology requires a roof node ID or an expected route");
+                }
+                let paths = [
+                    ("route", self.expected_route.as_deref()),
+                    ("return route", self.expected_route_back.as_deref()),
+                ];
+                for (name, path) in paths {
+                    let Some(path) = path else { continue };
+                    if path.is_empty() {
+                        anyhow::bail!("expected {} must contain at least one hop", name);
+                    }
+                    for hop in path {
+                        if let PathHop::Node(id) = hop {
+                            if id.is_broadcast() || id.is_local() {
+                                anyhow::bail!("expected {} hop {} is not a unicast node", name, id);
+                            }
+                        }
+                    }
+                }
+                if self.mountain_node_id.is_none() {
+                    anyhow::bail!("Relay topology requires a mountain node ID");
+                }
+                // An expected route can stand in for the roof ID, but LNA control cannot
+                if self.lna_control_target == LnaControlTarget::Roof && self.roof_node_id.is_none()
+                {
+                    anyhow::bail!("LNA control on the roof node requires a roof node ID");
+                }
+            }
+            Topology::Direct => {
+                if self.target_node_id.is_none() {
+                    anyhow::bail!("Direct topology requires a target node ID");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Phase length actually run, capped in dry-run mode.
+    pub fn effective_phase_duration_ms(&self) -> u64 {
+        if self.dry_run {
+            self.phase_duration_ms.min(DRY_RUN_PHASE_MS)
+        } else {
+            self.phase_duration_ms
+        }
+    }
+
+    /// Fills in unset timing values from the airtime of an admin exchange with the
+    /// LNA-controlled node; `lora` is the local modem, `None` while still unknown.
+    pub fn timings(&self, lora: Option<LoraParams>) -> Timings {
+        let round_trip = lora
+            .map(|params| params.packet_airtime(ADMIN_PACKET_BYTES) * 2 * self.lna_control_hops())
+            .unwrap_or_default();
+        let timeout_ms = MIN_LNA_TIMEOUT_MS + (round_trip * 3).as_millis() as u64;
+
+        Timings {
+            lna_max_attempts: self.lna_max_attempts.unwrap_or(DEFAULT_LNA_MAX_ATTEMPTS),
+            lna_wait_timeout_ms: self.lna_wait_timeout_ms.unwrap_or(timeout_ms),
+            lna_ack_timeout_ms: self.lna_ack_timeout_ms.unwrap_or(timeout_ms),
+            settle_ms: self
+                .settle_ms
+                .unwrap_or(MIN_SETTLE_MS + round_trip.as_millis() as u64),
+            owner_info_timeout_ms: self
+                .owner_info_timeout_ms
+                .unwrap_or(DEFAULT_OWNER_INFO_TIMEOUT_MS),
+            range_test_settle_ms: self
+                .range_test_settle_ms
+                .unwrap_or(DEFAULT_RANGE_TEST_SETTLE_MS),
+        }
+    }
+
+    /// Radio hops between the local node and the node whose LNA is toggled.
+    fn lna_control_hops(&self) -> u32 {
+        let path = self.forward_path();
+        let hops = match (&self.topology, &self.lna_control_target) {
+            (Topology::Relay, LnaControlTarget::Roof) => self
+                .roof_node_id
+                .and_then(|roof| path.iter().position(|hop| *hop == PathHop::Node(roof)))
+                .map_or(1, |position| position + 1),
+            (Topology::Relay, LnaControlTarget::Mountain) => path.len() + 1,
+            _ => 1,
+        };
+        hops as u32
+    }
+
+    /// Intermediate hops the forward route must take in Relay topology.
+    pub fn forward_path(&self) -> Vec<PathHop> {
+        match &self.expected_route {
+            Some(path) => path.clone(),
+            None => self.roof_node_id.map(PathHop::Node).into_iter().collect(),
+        }
+    }
+
+    /// Intermediate hops the return route must take in Relay topology.
+    pub fn return_path(&self) -> Vec<PathHop> {
+        match &self.expected_route_back {
+            Some(path) => path.clone(),
+            None => self.forward_path().into_iter().rev().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOF: NodeId = NodeId::new(0x0000_aaaa);
+    const RELAY: NodeId = NodeId::new(0x0000_bbbb);
+
+    fn adaptive(max_duration_ms: u64, dry_run: bool) -> Config {
+        Config {
+            roof_node_id: Some(ROOF),
+            mountain_node_id: Some(RELAY),
+            target_ci_half_width_db: Some(0.5),
+            max_duration_ms: Some(max_duration_ms),
+            dry_run,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn max_duration_is_checked_against_the_dry_run_phase_cap() {
+        assert!(adaptive(60_000, false).validate().is_err());
+        assert!(adaptive(60_000, true).validate().is_ok());
+        assert!(adaptive(59_999, true).validate().is_err());
+    }
+
+    #[test]
+    fn settle_times_are_range_checked() {
+        let valid = adaptive(3_600_000, false);
+        assert!(valid.validate().is_ok());
+        for settle in [0, MAX_SETTLE_MS + 1] {
+            let config = Config {
+                settle_ms: Some(settle),
+                ..valid.clone()
+            };
+            assert!(config.validate().is_err(), "settle {settle} ms");
+            let config = Config {
+                range_test_settle_ms: Some(settle),
+                ..valid.clone()
+            };
+            assert!(config.validate().is_err(), "range test settle {settle} ms");
+        }
+    }
+
+    #[test]
+    fn path_hop_parses_wildcards_and_node_ids() {
+        assert_eq!("*".parse(), Ok(PathHop::Any));
+        assert_eq!(" * ".parse(), Ok(PathHop::Any));
+        assert_eq!("!0000aaaa".parse(), Ok(PathHop::Node(ROOF)));
+        assert_eq!("43690".parse(), Ok(PathHop::Node(ROOF)));
+        assert!("**".parse::<PathHop>().is_err());
+        assert!("".parse::<PathHop>().is_err());
+    }
+
+    #[test]
+    fn path_hop_round_trips_through_serde() {
+        let path = vec![PathHop::Node(ROOF), PathHop::Any];
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, r#"["!0000aaaa","*"]"#);
+        assert_eq!(serde_json::from_str::<Vec<PathHop>>(&json).unwrap(), path);
+    }
+
+    #[test]
+    fn wildcard_matches_any_node() {
+        assert!(PathHop::Any.matches(RELAY));
+        assert!(PathHop::Node(ROOF).matches(ROOF));
+        assert!(!PathHop::Node(ROOF).matches(RELAY));
+    }
+
+    #[test]
+    fn default_paths_go_through_the_roof_node() {
+        let config = Config {
+            roof_node_id: Some(ROOF),
+            ..Config::default()
+        };
+        assert_eq!(config.forward_path(), vec![PathHop::Node(ROOF)]);
+        assert_eq!(config.return_path(), vec![PathHop::Node(ROOF)]);
+    }
+
+    #[test]
+    fn return_path_defaults_to_the_reversed_forward_path() {
+        let mut config = Config {
+            roof_node_id: Some(ROOF),
+            expected_route: Some(vec![
+                PathHop::Node(ROOF),
+                PathHop::Any,
+                PathHop::Node(RELAY),
+            ]),
+            ..Config::default()
+        };
+        assert_eq!(
+            config.return_path(),
+            vec![PathHop::Node(RELAY), PathHop::Any, PathHop::Node(ROOF)]
+        );
+
+        config.expected_route_back = Some(vec![PathHop::Node(RELAY)]);
+        assert_eq!(config.return_path(), vec![PathHop::Node(RELAY)]);
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+const METRES_PER_DEGREE_LAT: f64 = 111_320.0;
+const GEOHASH_ALPHABET: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// How samples are binned into coverage cells.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageGrid {
+    /// Geohash cells of the given precision (1..=12 characters).
+    Geohash(usize),
+    /// Square cells of roughly this edge length in metres.
+    Metres(f64),
+}
+
+/// A geotagged sample read back from an exported `.geojson` layer.
+#[derive(Debug, Clone)]
+pub struct CoveragePoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub lna_on: bool,
+    /// Traceroute samples count towards the delivery ratio; passive ones do not.
+    pub is_traceroute: bool,
+    pub delivered: bool,
+    pub snr: Option<f32>,
+    pub rssi: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellBounds {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageCell {
+    pub cell: String,
+    #[serde(skip)]
+    pub bounds: CellBounds,
+    pub center_lat: f64,
+    pub center_lon: f64,
+    pub samples: usize,
+    pub traceroutes: usize,
+    pub delivered: usize,
+    pub delivery_ratio: Option<f32>,
+    pub median_snr: Option<f32>,
+    pub median_rssi: Option<f32>,
+    pub lna_off_median_snr: Option<f32>,
+    pub lna_on_median_snr: Option<f32>,
+    pub delta_snr: Option<f32>,
+}
+
+/// Reads the point features written by the engine's GeoJSON export.
+pub fn load_geojson_samples(path: &Path) -> Result<Vec<CoveragePoint>> {
+    let text = std::fs::read_to_string(path)?;
+    let document: serde_json::Value = serde_json::from_str(&text)?;
+    let features = document["features"]
+        .as_array()
+        .ok_or_else(|| anyhow!("{} is not a GeoJSON FeatureCollection", path.display()))?;
+
+    let mut points = Vec::with_capacity(features.len());
+    for feature in features {
+        let coordinates = &feature["geometry"]["coordinates"];
+        let (Some(longitude), Some(latitude)) = (coordinates[0].as_f64(), coordinates[1].as_f64())
+        else {
+            continue;
+        };
+        let properties = &feature["properties"];
+        points.push(CoveragePoint {
+            latitude,
+            longitude,
+            lna_on: properties["lna_on"].as_bool().unwrap_or(false),
+            is_traceroute: properties["kind"].as_str() == Some("traceroute"),
+            delivered: properties["delivered"].as_bool().unwrap_or(true),
+            snr: properties["snr"].as_f64().map(|value| value as f32),
+            rssi: properties["rssi"].as_f64().map(|value| value as f32),
+        });
+    }
+    Ok(points)
+}
+
+/// Bins points into cells; inputs from several drive tests can simply be concatenated.
+pub fn aggregate(points: &[CoveragePoint], grid: CoverageGrid) -> Vec<CoverageCell> {
+    let mut cells: BTreeMap<String, (CellBounds, Vec<&CoveragePoint>)> = BTreeMap::new();
+    for point in points {
+        let (id, bounds) = cell_for(point.latitude, point.longitude, grid);
+        cells
+            .entry(id)
+            .or_insert_with(|| (bounds, Vec::new()))
+            .1
+            .push(point);
+    }
+
+    cells
+        .into_iter()
+        .map(|(cell, (bounds, members))| {
+            let traceroutes = members.iter().filter(|point| point.is_traceroute).count();
+            let delivered = members
+                .iter()
+                .filter(|point| point.is_traceroute && point.delivered)
+                .count();
+            let snr_where = |filter: &dyn Fn(&CoveragePoint) -> bool| {
+                median(
+                    members
+                        .iter()
+                        .filter(|point| filter(point))
+                        .filter_map(|point| point.snr)
+                        .collect(),
+                )
+            };
+            let lna_off_median_snr = snr_where(&|point: &CoveragePoint| !point.lna_on);
+            let lna_on_median_snr = snr_where(&|point: &CoveragePoint| point.lna_on);
+
+            CoverageCell {
+                cell,
+                bounds,
+                center_lat: (bounds.min_lat + bounds.max_lat) / 2.0,
+                center_lon: (bounds.min_lon + bounds.max_lon) / 2.0,
+                samples: members.len(),
+                traceroutes,
+                delivered,
+                delivery_ratio: (traceroutes > 0).then(|| delivered as f32 / traceroutes as f32),
+                median_snr: snr_where(&|_: &CoveragePoint| true),
+                median_rssi: median(members.iter().filter_map(|point| point.rssi).collect()),
+                lna_off_median_snr,
+                lna_on_median_snr,
+                delta_snr: match (lna_on_median_snr, lna_off_median_snr) {
+                    (Some(on), Some(off)) => Some(on - off),
+                    _ => None,
+                },
+            }
+        })
+        .collect()
+}
+
+pub fn write_cells_geojson(path: &Path, cells: &[CoverageCell]) -> Result<()> {
+    let features: Vec<serde_json::Value> = cells
+        .iter()
+        .map(|cell| {
+            let b = cell.bounds;
+            let ring = vec![
+                [b.min_lon, b.min_lat],
+                [b.max_lon, b.min_lat],
+                [b.max_lon, b.max_lat],
+                [b.min_lon, b.max_lat],
+                [b.min_lon, b.min_lat],
+            ];
+            let mut properties = serde_json::to_value(cell).unwrap_or_default();
+            properties["type"] = serde_json::Value::String("coverage-cell".into());
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": { "type": "Polygon", "coordinates": [ring] },
+                "properties": properties,
+            })
+        })
+        .collect();
+
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    std::fs::write(path, serde_json::to_string_pretty(&collection)?)?;
+    Ok(())
+}
+
+pub fn write_cells_csv(path: &Path, cells: &[CoverageCell]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for cell in cells {
+        writer.serialize(cell)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn cell_for(latitude: f64, longitude: f64, grid: CoverageGrid) -> (String, CellBounds) {
+    match grid {
+        CoverageGrid::Geohash(precision) => geohash_cell(latitude, longitude, precision),
+        CoverageGrid::Metres(size) => {
+            // Rows are fixed in latitude; each row uses its own centre for the longitude scale
+            // so the same point always lands in the same cell across runs.
+            let lat_step = size / METRES_PER_DEGREE_LAT;
+            let row = (latitude / lat_step).floor();
+            let min_lat = row * lat_step;
+            let center_lat = min_lat + lat_step / 2.0;
+            let lon_step = size / (METRES_PER_DEGREE_LAT * center_lat.to_radians().cos().max(1e-6));
+            let col = (longitude / lon_step).floor();
+            let min_lon = col * lon_step;
+            (
+                format!("m{}:{}:{}", size, row as i64, col as i64),
+                CellBounds {
+                    min_lat,
+                    min_lon,
+                    max_lat: min_lat + lat_step,
+                    max_lon: min_lon + lon_step,
+                },
+            )
+        }
+    }
+}
+
+fn geohash_cell(latitude: f64, longitude: f64, precision: usize) -> (String, CellBounds) {
+    let mut lat_range = (-90.0, 90.0);
+    let mut lon_range = (-180.0, 180.0);
+    let mut hash = String::with_capacity(precision);
+    let mut even_bit = true;
+    let mut bits = 0u8;
+    let mut index = 0usize;
+
+    while hash.len() < precision {
+        let (range, value) = if even_bit {
+            (&mut lon_range, longitude)
+        } else {
+            (&mut lat_range, latitude)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        index <<= 1;
+        if value >= mid {
+            index |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even_bit = !even_bit;
+        bits += 1;
+        if bits == 5 {
+            hash.push(GEOHASH_ALPHABET[index] as char);
+            bits = 0;
+            index = 0;
+        }
+    }
+
+    (
+        hash,
+        CellBounds {
+            min_lat: lat_range.0,
+            min_lon: lon_range.0,
+            max_lat: lat_range.1,
+            max_lon: lon_range.1,
+        },
+    )
+}
+
+fn median(mut values: Vec<f32>) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    // Averages the two middle values for even counts, picks the middle one otherwise
+    let lower = values[(values.len() - 1) / 2];
+    let upper = values[values.len() / 2];
+    Some((lower + upper) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(latitude: f64, longitude: f64, lna_on: bool) -> CoveragePoint {
+        CoveragePoint {
+            latitude,
+            longitude,
+            lna_on,
+            is_traceroute: true,
+            delivered: true,
+            snr: None,
+            rssi: None,
+        }
+    }
+
+    fn traceroute(lna_on: bool, snr: Option<f32>) -> CoveragePoint {
+        CoveragePoint {
+            delivered: snr.is_some(),
+            snr,
+            ..point(57.64911, 10.40744, lna_on)
+        }
+    }
+
+    #[test]
+    fn geohash_matches_reference_hashes() {
+        let (hash, _) = geohash_cell(57.64911, 10.40744, 11);
+        assert_eq!(hash, "u4pruydqqvj");
+        let (hash, _) = geohash_cell(57.64911, 10.40744, 7);
+        assert_eq!(hash, "u4pruyd");
+
+        let (hash, bounds) = geohash_cell(42.6, -5.6, 5);
+        assert_eq!(hash, "ezs42");
+        assert_eq!(
+            bounds,
+            CellBounds {
+                min_lat: 42.5830078125,
+                min_lon: -5.625,
+                max_lat: 42.626953125,
+                max_lon: -5.5810546875,
+            }
+        );
+    }
+
+    #[test]
+    fn metre_cells_contain_their_points() {
+        let (id, bounds) = cell_for(57.64911, 10.40744, CoverageGrid::Metres(100.0));
+        assert!(bounds.min_lat <= 57.64911 && 57.64911 < bounds.max_lat);
+        assert!(bounds.min_lon <= 10.40744 && 10.40744 < bounds.max_lon);
+        assert!(((bounds.max_lat - bounds.min_lat) * METRES_PER_DEGREE_LAT - 100.0).abs() < 1e-6);
+        assert_eq!(
+            cell_for(57.64911, 10.40744, CoverageGrid::Metres(100.0)).0,
+            id
+        );
+    }
+
+    #[test]
+    fn aggregate_computes_delivery_ratio_and_lna_delta() {
+        let mut passive = point(57.64911, 10.40744, true);
+        passive.is_traceroute = false;
+        passive.snr = Some(4.0);
+        passive.rssi = Some(-100.0);
+        let points = vec![
+            traceroute(false, Some(-5.0)),
+            traceroute(false, Some(-3.0)),
+            traceroute(false, None),
+            traceroute(true, Some(1.0)),
+            traceroute(true, Some(2.0)),
+            passive,
+            point(42.6, -5.6, false),
+        ];
+
+        let cells = aggregate(&points, CoverageGrid::Geohash(7));
+        assert_eq!(cells.len(), 2);
+        assert!(cells[0].cell.starts_with("ezs42"));
+        assert_eq!(cells[0].samples, 1);
+
+        let cell = &cells[1];
+        assert_eq!(cell.cell, "u4pruyd");
+        assert_eq!(cell.samples, 6);
+        assert_eq!(cell.traceroutes, 5);
+        assert_eq!(cell.delivered, 4);
+        assert_eq!(cell.delivery_ratio, Some(0.8));
+        assert_eq!(cell.median_snr, Some(1.0));
+        assert_eq!(cell.median_rssi, Some(-100.0));
+        assert_eq!(cell.lna_off_median_snr, Some(-4.0));
+        assert_eq!(cell.lna_on_median_snr, Some(2.0));
+        assert_eq!(cell.delta_snr, Some(6.0));
+    }
+
+    #[test]
+    fn median_averages_the_middle_pair() {
+        assert_eq!(median(vec![]), None);
+        assert_eq!(median(vec![3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(vec![4.0, 1.0, 3.0, 2.0]), Some(2.5));
+    }
+}
@@ -1,16 +1,56 @@
-use crate::config::{Config, LnaControlTarget};
-use crate::transport::Transport;
-use crate::{msnr_log, msnr_log_err};
+use crate::airtime::{AirtimeTracker, LoraParams};
+use crate::config::{Config, DutyCyclePolicy, LnaControlTarget, MeasurementMode, PathHop, Timings};
+use crate::events::{EngineEvent, ProgressTracker, RejectReason};
+use crate::geo::{GeoFix, GeoSample, NmeaGps};
+use crate::logging::{self, LogCallback, LogContext};
+use crate::node_id::NodeId;
+use crate::transport::{TracerouteRequest, Transport};
+use crate::{msnr_debug, msnr_log, msnr_log_err, msnr_warn};
 use anyhow::Result;
-use meshtastic::protobufs::{AdminMessage, Config as MeshConfig, PortNum, admin_message, config};
+use meshtastic::protobufs::{
+    AdminMessage, Config as MeshConfig, Data, ModuleConfig, PortNum, RouteDiscovery, admin_message,
+    config, from_radio, mesh_packet, module_config,
+};
 use prost::Message;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, Instant}; // For encoding/decoding
+use tokio::sync::{mpsc, watch};
 
-const LNA_MAX_ATTEMPTS: u32 = 10;
-const LNA_WAIT_TIMEOUT_SECS: u64 = 30;
-const LNA_ACK_TIMEOUT_SECS: u64 = 30;
+pub mod doctor;
+mod reboot;
+
+/// Observer name used for samples collected through the primary transport.
+pub(crate) const PRIMARY_OBSERVER: &str = "local";
+
+/// Encoded `Data` size of an empty traceroute request and a typical single-relay reply.
+const TRACEROUTE_REQUEST_BYTES: usize = 12;
+const TRACEROUTE_REPLY_BYTES: usize = 28;
+/// Encoded `Data` size of a range test `seq N` packet.
+const RANGE_TEST_PACKET_BYTES: usize = 16;
+const DUTY_CYCLE_WINDOW_SECS: u64 = 3600;
+/// Node positions older than this give way to the NMEA GPS, if one is configured.
+const NODE_FIX_MAX_AGE_SECS: u64 = 60;
+/// Two-sided 95% normal quantile, the limit of `t_quantile_95` for many cycles.
+const Z_95: f64 = 1.96;
+/// Two-sided 95% Student t quantiles for 1..=30 degrees of freedom.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+/// Pause between setting the LNA and reading the setting back.
+const LNA_VERIFY_DELAY_SECS: u64 = 2;
+/// Telemetry can reveal a reboot up to a device-metrics interval (30 min by default) after it
+/// happened; older losses are kept no longer than this in case an outage explains them.
+const REBOOT_LOOKBACK_SECS: u64 = 3600;
+/// Expired request ids remembered so their stragglers still count as late replies.
+const EXPIRED_REQUEST_IDS_KEPT: usize = 256;
+/// Longest wait for the connect-time config dump, which takes a while with a large node DB.
+const CONFIG_DUMP_TIMEOUT_SECS: u64 = 30;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProgressState {
@@ -32,6 +72,158 @@ pub struct AverageStats {
     pub lna_on_samples: u32,
     pub lna_on_roof_to_mtn: Option<f32>,
     pub lna_on_mtn_to_roof: Option<f32>,
+    /// Estimated channel airtime spent on traceroutes, summed over all phases of each kind.
+    #[serde(default)]
+    pub lna_off_airtime_secs: f32,
+    #[serde(default)]
+    pub lna_on_airtime_secs: f32,
+    /// Traceroute round-trip latency; retries caused by a deaf receiver show up here first.
+    #[serde(default)]
+    pub lna_off_rtt_median_ms: Option<f32>,
+    #[serde(default)]
+    pub lna_off_rtt_p90_ms: Option<f32>,
+    #[serde(default)]
+    pub lna_on_rtt_median_ms: Option<f32>,
+    #[serde(default)]
+    pub lna_on_rtt_p90_ms: Option<f32>,
+    /// Average SNR of every link seen in a validated route, keyed by node pair.
+    #[serde(default)]
+    pub links: Vec<LinkStats>,
+    /// Passive mode: local reception of each configured node's own traffic.
+    #[serde(default)]
+    pub heard: Vec<HeardNodeStats>,
+    /// Mean channel utilization reported by each node's telemetry.
+    #[serde(default)]
+    pub channel_util: Vec<ChannelUtilStats>,
+    /// 1-based cycles whose OFF/ON channel utilization differed beyond the configured threshold.
+    #[serde(default)]
+    pub confounded_cycles: Vec<u32>,
+    /// Range test mode: one-way results from the remote node's sequenced packets.
+    #[serde(default)]
+    pub lna_off_range_test: Option<RangeTestSummary>,
+    #[serde(default)]
+    pub lna_on_range_test: Option<RangeTestSummary>,
+    /// 95% confidence half-width of the ON−OFF delta used by adaptive stopping.
+    #[serde(default)]
+    pub delta_ci95_half_width_db: Option<f32>,
+    /// Reboots of a reconfigured node that interrupted a phase, and the time paused out.
+    #[serde(default)]
+    pub lna_off_reboots: u32,
+    #[serde(default)]
+    pub lna_on_reboots: u32,
+    #[serde(default)]
+    pub lna_off_reboot_excluded_secs: f32,
+    #[serde(default)]
+    pub lna_on_reboot_excluded_secs: f32,
+    /// Traceroutes each observer radio sent to the primary node, for both ends of its link.
+    #[serde(default)]
+    pub observers: Vec<ObserverStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RangeTestSummary {
+    /// Packets the sender should have sent in the measured phase time.
+    pub expected: u32,
+    pub received: u32,
+    pub loss_ratio: Option<f32>,
+    pub rx_snr: Option<f32>,
+    pub rx_rssi: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelUtilStats {
+    pub node: NodeId,
+    pub lna_off_percent: Option<f32>,
+    pub lna_on_percent: Option<f32>,
+}
+
+/// LNA OFF/ON reception averages for packets heard from one node in passive mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeardNodeStats {
+    pub node: NodeId,
+    pub lna_off_samples: u32,
+    pub lna_off_rx_snr: Option<f32>,
+    pub lna_off_rx_rssi: Option<f32>,
+    pub lna_on_samples: u32,
+    pub lna_on_rx_snr: Option<f32>,
+    pub lna_on_rx_rssi: Option<f32>,
+}
+
+impl HeardNodeStats {
+    pub fn delta_rx_snr(&self) -> Option<f32> {
+        match (self.lna_on_rx_snr, self.lna_off_rx_snr) {
+            (Some(on), Some(off)) => Some(on - off),
+            _ => None,
+        }
+    }
+}
+
+/// LNA OFF/ON averages of an observer's traceroutes to the primary node: the SNR the primary
+/// heard the request with, and the SNR the observer heard the reply with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObserverStats {
+    pub name: String,
+    pub node: Option<NodeId>,
+    pub lna_off_samples: u32,
+    pub lna_off_snr_at_local: Option<f32>,
+    pub lna_off_snr_at_observer: Option<f32>,
+    pub lna_off_rtt_median_ms: Option<f32>,
+    pub lna_on_samples: u32,
+    pub lna_on_snr_at_local: Option<f32>,
+    pub lna_on_snr_at_observer: Option<f32>,
+    pub lna_on_rtt_median_ms: Option<f32>,
+}
+
+impl ObserverStats {
+    pub fn delta_snr_at_local(&self) -> Option<f32> {
+        match (self.lna_on_snr_at_local, self.lna_off_snr_at_local) {
+            (Some(on), Some(off)) => Some(on - off),
+            _ => None,
+        }
+    }
+
+    pub fn delta_snr_at_observer(&self) -> Option<f32> {
+        match (self.lna_on_snr_at_observer, self.lna_off_snr_at_observer) {
+            (Some(on), Some(off)) => Some(on - off),
+            _ => None,
+        }
+    }
+}
+
+/// LNA OFF/ON SNR averages for a single directed radio link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkStats {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub lna_off_samples: u32,
+    pub lna_off_snr: Option<f32>,
+    pub lna_on_samples: u32,
+    pub lna_on_snr: Option<f32>,
+    /// SNR the receiving node reported for this link in its NeighborInfo broadcasts.
+    #[serde(default)]
+    pub lna_off_neighbor_samples: u32,
+    #[serde(default)]
+    pub lna_off_neighbor_snr: Option<f32>,
+    #[serde(default)]
+    pub lna_on_neighbor_samples: u32,
+    #[serde(default)]
+    pub lna_on_neighbor_snr: Option<f32>,
+}
+
+impl LinkStats {
+    pub fn delta_snr(&self) -> Option<f32> {
+        match (self.lna_on_snr, self.lna_off_snr) {
+            (Some(on), Some(off)) => Some(on - off),
+            _ => None,
+        }
+    }
+
+    pub fn delta_neighbor_snr(&self) -> Option<f32> {
+        match (self.lna_on_neighbor_snr, self.lna_off_neighbor_snr) {
+            (Some(on), Some(off)) => Some(on - off),
+            _ => None,
+        }
+    }
 }
 
 impl AverageStats {
@@ -48,36 +240,196 @@ impl AverageStats {
             _ => None,
         }
     }
+
+    pub fn delta_rtt_median_ms(&self) -> Option<f32> {
+        match (self.lna_on_rtt_median_ms, self.lna_off_rtt_median_ms) {
+            (Some(on), Some(off)) => Some(on - off),
+            _ => None,
+        }
+    }
+
+    pub fn delta_rtt_p90_ms(&self) -> Option<f32> {
+        match (self.lna_on_rtt_p90_ms, self.lna_off_rtt_p90_ms) {
+            (Some(on), Some(off)) => Some(on - off),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, Default)]
+/// Running mean and variance (Welford), which stay accurate over long runs of similar values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct ChannelStats {
     samples: u32,
-    sum: f32,
+    mean: f64,
+    /// Sum of squared deviations from the running mean.
+    m2: f64,
 }
 
 impl ChannelStats {
     fn add_sample(&mut self, value: f32) {
-        self.sum += value;
+        let value = value as f64;
         self.samples += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Unbiased sample variance; needs at least two samples.
+    fn variance(&self) -> Option<f64> {
+        if self.samples < 2 {
+            return None;
+        }
+        Some(self.m2 / (self.samples - 1) as f64)
     }
 
     fn average(&self) -> Option<f32> {
         if self.samples == 0 {
             None
         } else {
-            Some(self.sum / self.samples as f32)
+            Some(self.mean as f32)
+        }
+    }
+
+    /// Sum of all samples, for means over the samples added between two snapshots.
+    fn total(&self) -> f64 {
+        self.mean * self.samples as f64
+    }
+}
+
+/// Two-sided 95% Student t quantile for `df` degrees of freedom.
+fn t_quantile_95(df: u32) -> f64 {
+    match df {
+        0 => f64::INFINITY,
+        1..=30 => T_95[df as usize - 1],
+        // Cornish-Fisher expansion; within 0.001 of the exact value beyond the table
+        _ => {
+            let df = df as f64;
+            Z_95 + (Z_95.powi(3) + Z_95) / (4.0 * df)
+                + (5.0 * Z_95.powi(5) + 16.0 * Z_95.powi(3) + 3.0 * Z_95) / (96.0 * df * df)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct LatencyStats {
+    samples_ms: Vec<f32>,
+}
+
+impl LatencyStats {
+    fn add_sample(&mut self, rtt: Duration) {
+        self.samples_ms.push(rtt.as_secs_f32() * 1000.0);
+    }
+
+    /// Nearest-rank percentile, `pct` in 0..=100.
+    fn percentile(&self, pct: f32) -> Option<f32> {
+        if self.samples_ms.is_empty() {
+            return None;
         }
+        let mut sorted = self.samples_ms.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let rank = ((pct / 100.0) * sorted.len() as f32).ceil() as usize;
+        sorted.get(rank.saturating_sub(1)).copied()
+    }
+
+    fn median(&self) -> Option<f32> {
+        self.percentile(50.0)
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct PhaseStats {
     roof_to_mtn: ChannelStats,
     mtn_to_roof: ChannelStats,
+    airtime: Duration,
+    traceroutes_sent: u32,
+    replies_matched: u32,
+    rtt: LatencyStats,
+    #[serde(with = "entry_list")]
+    links: BTreeMap<(NodeId, NodeId), ChannelStats>,
+    #[serde(with = "entry_list")]
+    neighbor_links: BTreeMap<(NodeId, NodeId), ChannelStats>,
+    heard: BTreeMap<NodeId, ReceptionStats>,
+    channel_util: BTreeMap<NodeId, ChannelStats>,
+    range_test: RangeTestStats,
+    #[serde(default)]
+    reboots: u32,
+    #[serde(default)]
+    reboot_excluded: Duration,
+    /// Accepted observer traceroutes, keyed by observer name.
+    #[serde(default)]
+    observers: BTreeMap<String, ObserverPhaseStats>,
+}
+
+/// One phase of an observer's traceroutes to the primary node.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ObserverPhaseStats {
+    snr_at_local: ChannelStats,
+    snr_at_observer: ChannelStats,
+    rtt: LatencyStats,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RangeTestStats {
+    expected: u32,
+    received: u32,
+    rx_snr: ChannelStats,
+    rx_rssi: ChannelStats,
+}
+
+impl RangeTestStats {
+    fn summary(&self) -> Option<RangeTestSummary> {
+        if self.expected == 0 && self.received == 0 {
+            return None;
+        }
+        Some(RangeTestSummary {
+            expected: self.expected,
+            received: self.received,
+            // A sender slightly ahead of the clock can deliver one packet more than expected
+            loss_ratio: (self.expected > 0)
+                .then(|| (1.0 - self.received as f32 / self.expected as f32).max(0.0)),
+            rx_snr: self.rx_snr.average(),
+            rx_rssi: self.rx_rssi.average(),
+        })
+    }
+}
+
+/// Range test packets counted since the window opened, at phase start or after a reboot.
+#[derive(Debug, Clone, Copy)]
+struct RangeTestWindow {
+    opened: Instant,
+    /// First sequence number seen; a lower one means the sender restarted its counter.
+    first_seq: Option<u32>,
+    received: u32,
+}
+
+impl RangeTestWindow {
+    fn new() -> Self {
+        Self {
+            opened: Instant::now(),
+            first_seq: None,
+            received: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ReceptionStats {
+    rx_snr: ChannelStats,
+    rx_rssi: ChannelStats,
 }
 
 impl PhaseStats {
+    /// `hops` lists every node along the path, both ends included; `snr[i]` is the SNR
+    /// measured by `hops[i + 1]` when receiving from `hops[i]`.
+    fn add_link_samples(&mut self, hops: &[NodeId], snr: &[f32]) {
+        for (pair, value) in hops.windows(2).zip(snr) {
+            self.links
+                .entry((pair[0], pair[1]))
+                .or_default()
+                .add_sample(*value);
+        }
+    }
+
     fn add_sample(&mut self, roof_to_mtn: Option<f32>, mtn_to_roof: Option<f32>) {
         if let Some(val) = roof_to_mtn {
             self.roof_to_mtn.add_sample(val);
@@ -100,159 +452,692 @@ impl PhaseStats {
     }
 }
 
+/// An additional radio connection that listens and traceroutes back to the primary node.
+struct ObserverRadio {
+    name: String,
+    transport: Box<dyn Transport>,
+    node_id: Option<NodeId>,
+}
+
+type ObserverReceiver = mpsc::UnboundedReceiver<(usize, meshtastic::protobufs::FromRadio)>;
+
+/// A traceroute we sent and have not seen a reply for yet, keyed by packet id.
+#[derive(Debug, Clone)]
+struct PendingTraceroute {
+    observer: Option<usize>,
+    target: NodeId,
+    phase_index: u32,
+    is_lna_on: bool,
+    sent_at: Instant,
+    sent_wallclock: chrono::DateTime<chrono::Local>,
+    /// Where we were when sending, so unanswered requests still land on the coverage map.
+    fix: Option<GeoFix>,
+}
+
+/// Timing of a traceroute reply that matched one of our requests.
 #[derive(Debug)]
-enum RouteValidationOutcome {
-    RoofOnly,
+struct TracerouteTiming {
+    sent_at: chrono::DateTime<chrono::Local>,
+    rtt: Duration,
+}
+
+/// Serializes maps with tuple keys as `[key, value]` pairs, since JSON object keys must be strings.
+mod entry_list {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<K, V, S>(map: &BTreeMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map)
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<BTreeMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(Vec::<(K, V)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+/// LNA setting last read back from the controlled node.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RemoteLnaState {
+    pub node: NodeId,
+    pub rx_boosted_gain: bool,
+}
+
+/// Asks a running engine to stop. The run still puts back the range test config and
+/// LNA setting it changed, then returns an error; the checkpoint is kept for `resume`.
+#[derive(Debug, Clone)]
+pub struct StopHandle(Arc<watch::Sender<bool>>);
+
+impl Default for StopHandle {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
+
+impl StopHandle {
+    pub fn stop(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Completes once `stop` has been called.
+    pub async fn stopped(&self) {
+        let mut rx = self.0.subscribe();
+        // The sender lives in `self`, so the channel cannot close while we wait
+        let _ = rx.wait_for(|stopped| *stopped).await;
+    }
+}
+
+/// Progress of a run, rewritten next to the output after every sample so an
+/// interrupted run can be continued with `Engine::resume`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub config: Config,
+    /// Cycle (0-based) and phase (1 = LNA OFF, 2 = LNA ON) running when it was written.
+    pub cycle: u32,
+    pub phase_num: u8,
+    pub total_cycles: u32,
+    /// Time spent cycling so far, so adaptive stopping keeps its `max_duration_ms` budget.
+    pub elapsed_ms: u64,
+    pub remote_lna: Option<RemoteLnaState>,
+    /// LNA setting found before the run first changed it, put back at the end.
+    #[serde(default)]
+    pub lna_original: Option<RemoteLnaState>,
+    /// Range test config to put back at the end, prost-encoded.
+    range_test_original: Option<(NodeId, Vec<u8>)>,
+    stats_lna_off: PhaseStats,
+    stats_lna_on: PhaseStats,
+    #[serde(with = "entry_list")]
+    cycle_channel_util: BTreeMap<(u32, bool), BTreeMap<NodeId, ChannelStats>>,
+    confounded_cycles: Vec<u32>,
+    geo_samples: Vec<GeoSample>,
+    #[serde(default)]
+    cycle_deltas: ChannelStats,
+    #[serde(default)]
+    lna_off_phase_mean: Option<(u32, f64)>,
+}
+
+impl Checkpoint {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+/// Where `run_cycles` picks up after a checkpoint.
+struct ResumePoint {
+    cycle: u32,
+    phase_num: u8,
+    total_cycles: u32,
+    elapsed: Duration,
 }
 
 pub struct Engine {
     config: Config,
     transport: Box<dyn Transport>,
-    session_keys: HashMap<String, Vec<u8>>,
+    observers: Vec<ObserverRadio>,
+    local_node: Option<NodeId>,
+    local_lora: Option<config::LoRaConfig>,
+    airtime: AirtimeTracker,
+    pending_traceroutes: HashMap<u32, PendingTraceroute>,
+    /// Recently given-up traceroutes, in case a reboot detected later explains them.
+    lost_traceroutes: Vec<PendingTraceroute>,
+    late_replies: u32,
+    /// Most recently expired request ids, oldest first.
+    expired_request_ids: VecDeque<u32>,
+    unmatched_replies: u32,
+    session_keys: HashMap<NodeId, Vec<u8>>,
     stats_lna_on: PhaseStats,
     stats_lna_off: PhaseStats,
+    /// Channel utilization per node for each (cycle, is_lna_on) phase.
+    cycle_channel_util: BTreeMap<(u32, bool), BTreeMap<NodeId, ChannelStats>>,
+    confounded_cycles: Vec<u32>,
+    /// ON−OFF difference of the primary metric's phase means, one sample per cycle.
+    cycle_deltas: ChannelStats,
+    /// Cycle and primary metric mean of the last LNA OFF phase, awaiting its ON phase.
+    lna_off_phase_mean: Option<(u32, f64)>,
+    node_fix: Option<GeoFix>,
+    nmea_gps: Option<NmeaGps>,
+    geo_samples: Vec<GeoSample>,
+    range_window: Option<RangeTestWindow>,
+    remote_lna: Option<RemoteLnaState>,
+    /// LNA setting read before the run first changed it.
+    lna_original: Option<RemoteLnaState>,
+    /// Range test sender interval, after the duty cycle check.
+    range_test_sender_secs: u32,
+    /// Range test config saved before the run enabled the sender.
+    range_test_original: Option<(NodeId, module_config::RangeTestConfig)>,
+    /// When the first cycle started, shifted back by the time already spent before a resume.
+    cycles_started: Instant,
+    /// Set whenever phase statistics change; cleared by the next checkpoint.
+    checkpoint_due: bool,
+    /// Resolved once the local modem preset is known.
+    timings: Timings,
+    /// Last uptime reported by each node we watch for reboots.
+    node_uptime: HashMap<NodeId, u32>,
+    /// Node and time of the last config change we sent.
+    config_sent: Option<(NodeId, Instant)>,
+    reboot: Option<reboot::RebootWindow>,
+    events: Vec<mpsc::UnboundedSender<EngineEvent>>,
+    /// Tags every log line and session record of this engine's runs.
+    session_id: String,
+    /// Where this engine's log records go; the global callback when `None`.
+    log_sink: Option<LogCallback>,
+    stop: StopHandle,
 }
 
 impl Engine {
     pub fn new(config: Config, transport: Box<dyn Transport>) -> Self {
+        let local_node = config.local_node_id;
+        let timings = config.timings(None);
         Self {
             config,
             transport,
+            observers: Vec::new(),
+            local_node,
+            local_lora: None,
+            airtime: AirtimeTracker::new(Duration::from_secs(DUTY_CYCLE_WINDOW_SECS)),
+            pending_traceroutes: HashMap::new(),
+            lost_traceroutes: Vec::new(),
+            late_replies: 0,
+            expired_request_ids: VecDeque::new(),
+            unmatched_replies: 0,
             session_keys: HashMap::new(),
             stats_lna_on: PhaseStats::default(),
             stats_lna_off: PhaseStats::default(),
+            cycle_channel_util: BTreeMap::new(),
+            cycle_deltas: ChannelStats::default(),
+            lna_off_phase_mean: None,
+            confounded_cycles: Vec::new(),
+            node_fix: None,
+            nmea_gps: None,
+            geo_samples: Vec::new(),
+            range_window: None,
+            remote_lna: None,
+            lna_original: None,
+            range_test_original: None,
+            range_test_sender_secs: 0,
+            cycles_started: Instant::now(),
+            checkpoint_due: false,
+            timings,
+            node_uptime: HashMap::new(),
+            config_sent: None,
+            reboot: None,
+            events: Vec::new(),
+            session_id: format!(
+                "{}-{:04x}",
+                chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                rand::random::<u16>()
+            ),
+            log_sink: None,
+            stop: StopHandle::default(),
         }
     }
 
+    /// Like `new`, but routes the engine's log records to `log_sink` instead of the
+    /// process-wide callback, so concurrent engines keep their logs apart.
+    pub fn with_log_sink(
+        config: Config,
+        transport: Box<dyn Transport>,
+        log_sink: LogCallback,
+    ) -> Self {
+        let mut engine = Self::new(config, transport);
+        engine.log_sink = Some(log_sink);
+        engine
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Lets another task (a Stop button, Ctrl-C) end the run cleanly.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Adds a second radio (e.g. the roof node's own USB/TCP link). Admin and LNA
+    /// control stay on the primary transport; observers log what they hear from the
+    /// other radios and issue traceroutes back to the primary node.
+    pub fn add_observer(&mut self, name: impl Into<String>, transport: Box<dyn Transport>) {
+        self.observers.push(ObserverRadio {
+            name: name.into(),
+            transport,
+            node_id: None,
+        });
+    }
+
+    /// Delivers every `EngineEvent` of subsequent runs; the channel closes when the engine drops.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<EngineEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.events.push(tx);
+        rx
+    }
+
     pub async fn run<F>(&mut self, on_progress: F) -> Result<()>
     where
         F: Fn(ProgressState) + Send + Sync + 'static,
     {
-        let mut rx = self.transport.connect().await?;
+        self.run_reporting(None, on_progress).await
+    }
 
-        // Inject User Identity (Client-Side Signing)
-        // Private Key provided by user
-        let priv_key_b64 = "EP7uGaSlaoJHVp5wYVzv5O6fQQNx+q8yb9OshyMANmU=";
-        use base64::Engine;
-        if let Ok(priv_bytes) = base64::prelude::BASE64_STANDARD.decode(priv_key_b64) {
-            msnr_log!("Injecting User Identity (Client-Side Signing)...");
-            self.transport.set_identity(priv_bytes).await;
-        } else {
-            msnr_log!("Error decoding private key!");
+    /// Continues an interrupted run: re-reads the remote LNA setting, then starts at the
+    /// phase after the checkpointed one and appends to the same output files.
+    ///
+    /// The engine should be built from `checkpoint.config`.
+    pub async fn resume<F>(&mut self, checkpoint: Checkpoint, on_progress: F) -> Result<()>
+    where
+        F: Fn(ProgressState) + Send + Sync + 'static,
+    {
+        if checkpoint.config.output_path != self.config.output_path {
+            anyhow::bail!(
+                "checkpoint was written for {}, not {}",
+                checkpoint.config.output_path,
+                self.config.output_path
+            );
         }
+        self.run_reporting(Some(checkpoint), on_progress).await
+    }
 
-        let total_cycles = self.config.cycles;
+    /// Runs while folding this engine's events into `ProgressState` for `on_progress`.
+    async fn run_reporting<F>(
+        &mut self,
+        checkpoint: Option<Checkpoint>,
+        on_progress: F,
+    ) -> Result<()>
+    where
+        F: Fn(ProgressState) + Send + Sync + 'static,
+    {
+        let mut events = self.subscribe();
+        let progress_tx = self.events.last().cloned();
+        let context = self.session_log_context();
+        let run = async {
+            let result = context.scope(self.run_from(checkpoint)).await;
+            // Close the progress channel so the forwarder below finishes
+            if let Some(progress_tx) = &progress_tx {
+                self.events.retain(|tx| !tx.same_channel(progress_tx));
+            }
+            result
+        };
+        let forward = async {
+            let mut tracker = ProgressTracker::default();
+            while let Some(event) = events.recv().await {
+                if let Some(progress) = tracker.apply(&event) {
+                    on_progress(progress);
+                }
+            }
+        };
+        let (result, ()) = tokio::join!(run, forward);
+        result
+    }
 
-        for cycle in 0..total_cycles {
-            // --- Phase 1: LNA OFF ---
-            self.report_phase_start(&on_progress, cycle, total_cycles, "LNA OFF", 1);
+    async fn run_from(&mut self, checkpoint: Option<Checkpoint>) -> Result<()> {
+        self.config.validate()?;
+        let mut rx = self.transport.connect().await?;
+        // Disconnect everything however the run ends, so no connection or forwarder outlives it
+        let result = match self.connect_observers().await {
+            Ok(mut observer_rx) => {
+                self.run_connected(&mut rx, &mut observer_rx, checkpoint)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.disconnect_all().await;
+        result
+    }
 
-            // Toggle LNA OFF
-            // Toggle LNA OFF
-            if let Err(e) = self.set_lna_mode(&mut rx, false).await {
-                msnr_log_err!("Error setting LNA OFF: {}", e);
-                return Err(e); // Abort test
+    async fn run_connected(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        observer_rx: &mut ObserverReceiver,
+        checkpoint: Option<Checkpoint>,
+    ) -> Result<()> {
+        if let Some(port) = self.config.gps_nmea_port.clone() {
+            match NmeaGps::open(&port, self.config.gps_nmea_baud_rate) {
+                Ok(gps) => {
+                    msnr_log!("Reading NMEA GPS on {}", port);
+                    self.nmea_gps = Some(gps);
+                }
+                Err(e) => msnr_log_err!("Could not open NMEA GPS on {}: {}", port, e),
             }
-            // Wait for settling
-            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
 
-            // Run Traceroute Loop
-            // Run Traceroute Loop
-            self.run_traceroute_phase(
-                &mut rx,
-                &on_progress,
-                cycle,
-                "LNA OFF",
-                1,
-                total_cycles,
-                false,
-            )
-            .await?;
+        self.inject_identity().await;
+        if self.config.dry_run {
+            msnr_log!(
+                "[DRY RUN] Remote configs are read but never changed; phases last {} s; writing {} and no checkpoint.",
+                self.phase_duration_ms() / 1000,
+                self.output_path().display()
+            );
+        }
 
-            // --- Phase 2: LNA ON ---
-            self.report_phase_start(&on_progress, cycle, total_cycles, "LNA ON", 2);
+        self.await_local_lora(rx).await;
+        self.resolve_timings();
+        self.write_session_metadata(checkpoint.is_some());
 
-            // Toggle LNA ON
-            // Toggle LNA ON
-            if let Err(e) = self.set_lna_mode(&mut rx, true).await {
-                msnr_log_err!("Error setting LNA ON: {}", e);
-                return Err(e); // Abort test
-            }
-            // Wait for settling
-            tokio::time::sleep(Duration::from_secs(5)).await;
+        let resume_point = match checkpoint {
+            Some(checkpoint) => Some(self.restore_checkpoint(rx, checkpoint).await?),
+            None => None,
+        };
 
-            // Run Traceroute Loop
-            // Run Traceroute Loop
-            self.run_traceroute_phase(
-                &mut rx,
-                &on_progress,
-                cycle,
-                "LNA ON",
-                2,
-                total_cycles,
-                true,
-            )
-            .await?;
+        if self.config.measurement_mode == MeasurementMode::RangeTest {
+            self.range_test_sender_secs = self.plan_range_test_sender()?;
+        }
+        // Range test changes the remote node's module config; always put it back.
+        if self.config.measurement_mode == MeasurementMode::RangeTest && self.config.dry_run {
+            msnr_log!(
+                "[DRY RUN] Would enable the range test sender on {} every {} s; not sent.",
+                Self::format_node_id(self.measured_node().map(NodeId::as_u32)),
+                self.range_test_sender_secs
+            );
+        } else if self.config.measurement_mode == MeasurementMode::RangeTest {
+            let original = self.enable_range_test(rx).await?;
+            // A resumed run restores the config saved before the first start, not its own
+            if self.range_test_original.is_none() {
+                self.range_test_original = Some(original);
+            }
         }
+        let result = self.run_cycles(rx, observer_rx, resume_point).await;
+        self.restore_remote_config(rx).await;
+        result?;
 
+        self.expire_pending_traceroutes(u32::MAX);
         self.log_average_summary();
+        self.export_geo_layers();
+        self.remove_checkpoint();
 
-        // Send final completion progress
-        on_progress(ProgressState {
-            total_progress: 1.0,
-            current_round_progress: 1.0,
-            status_message: "Test Completed".to_string(),
-            eta_seconds: 0,
-            snr_towards: None,
-            snr_back: None,
-            phase: "Done".to_string(),
-            average_stats: Some(self.current_average_stats()),
+        self.emit(EngineEvent::Finished {
+            stats: self.current_average_stats(),
         });
+        Ok(())
+    }
 
+    async fn disconnect_all(&mut self) {
         if let Err(e) = self.transport.disconnect().await {
-            msnr_log!("Warning: Failed to disconnect cleanly: {e}");
+            msnr_warn!("Warning: Failed to disconnect cleanly: {e}");
+        }
+        for observer in &mut self.observers {
+            if let Err(e) = observer.transport.disconnect().await {
+                msnr_warn!(
+                    "Warning: Failed to disconnect observer {} cleanly: {e}",
+                    observer.name
+                );
+            }
+        }
+    }
+
+    /// Puts back the range test config and LNA setting found before the run, however the
+    /// cycles ended.
+    async fn restore_remote_config(&mut self, rx: &mut meshtastic::packet::PacketReceiver) {
+        if let Some((node, original)) = self.range_test_original.take() {
+            if let Err(e) = self.restore_range_test(rx, node, original).await {
+                let message = format!("Failed to restore range test config on {}: {}", node, e);
+                msnr_log_err!("{}", message);
+                self.emit(EngineEvent::Warning { message });
+            }
+        }
+
+        let Some(original) = self.lna_original.take() else {
+            return;
+        };
+        let current = self
+            .remote_lna
+            .filter(|current| current.node == original.node)
+            .map(|current| current.rx_boosted_gain);
+        if current == Some(original.rx_boosted_gain) {
+            return;
+        }
+        msnr_log!(
+            "Resetting LNA on {} to {} as found before the run",
+            original.node,
+            original.rx_boosted_gain
+        );
+        if let Err(e) = self.set_lna_mode(rx, original.rx_boosted_gain).await {
+            let message = format!("Failed to reset LNA on {}: {}", original.node, e);
+            msnr_log_err!("{}", message);
+            self.emit(EngineEvent::Warning { message });
+        }
+    }
+
+    /// Fails once a stop was requested, so `run_cycles` unwinds to the config restore.
+    fn check_stopped(&self) -> Result<()> {
+        if self.stop.is_stopped() && self.config.dry_run {
+            anyhow::bail!("Dry run stopped on request");
+        }
+        if self.stop.is_stopped() {
+            anyhow::bail!(
+                "Run stopped on request; continue it with the checkpoint {}",
+                self.checkpoint_path().display()
+            );
         }
         Ok(())
     }
 
-    async fn set_lna_mode(
+    /// Sleeps for `duration`, returning early if a stop is requested.
+    async fn pause(&self, duration: Duration) {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.stop.stopped() => {}
+        }
+    }
+
+    async fn run_cycles(
         &mut self,
         rx: &mut meshtastic::packet::PacketReceiver,
-        enable: bool,
+        observer_rx: &mut ObserverReceiver,
+        resume_point: Option<ResumePoint>,
     ) -> Result<()> {
-        let target_node = match self.resolve_lna_control_node() {
-            Some(node) if !node.is_empty() => node,
-            _ => {
-                msnr_log!("LNA control disabled or missing target node, skipping toggle.");
-                return Ok(());
-            }
+        let adaptive = self.config.target_ci_half_width_db.is_some();
+        let max_cycles = self.max_cycles();
+        let elapsed = resume_point
+            .as_ref()
+            .map_or(Duration::ZERO, |point| point.elapsed);
+        let started = Instant::now()
+            .checked_sub(elapsed)
+            .unwrap_or_else(Instant::now);
+        self.cycles_started = started;
+        // With adaptive stopping this is an estimate, refined after every cycle for the ETA
+        let mut total_cycles = match &resume_point {
+            Some(point) => point.total_cycles,
+            None if adaptive => self.config.cycles.clamp(1, max_cycles),
+            None => self.config.cycles,
         };
 
-        let target_id = if target_node.starts_with('!') {
-            u32::from_str_radix(&target_node[1..], 16).unwrap_or(0)
-        } else {
-            target_node.parse::<u32>().unwrap_or(0)
+        // Resume with the phase after the checkpointed one
+        let (mut cycle, mut first_phase) = match &resume_point {
+            Some(point) if point.phase_num == 1 => (point.cycle, 2),
+            Some(point) => (point.cycle + 1, 1),
+            None => (0, 1),
         };
+        if adaptive && resume_point.is_some() && first_phase == 1 {
+            match self.plan_adaptive_cycles(cycle, max_cycles, started) {
+                Some(estimate) => total_cycles = estimate,
+                None => return Ok(()),
+            }
+        }
 
-        msnr_log!("Fetching Local Node Info...");
-        let owner_req = AdminMessage {
-            payload_variant: Some(admin_message::PayloadVariant::GetOwnerRequest(true)),
-            ..Default::default()
-        };
-        self.send_admin_with_session("0", &owner_req).await?;
+        while cycle < total_cycles {
+            self.check_stopped()?;
+            if first_phase == 1 {
+                // --- Phase 1: LNA OFF ---
+                self.report_phase_start(cycle, total_cycles, "LNA OFF", 1);
 
-        let info_timeout = Duration::from_secs(3);
-        let info_start = Instant::now();
-        loop {
-            if info_start.elapsed() > info_timeout {
-                msnr_log!("Warning: Could not fetch local node info.");
-                break;
+                // Toggle LNA OFF
+                if let Err(e) = self.set_lna_mode(rx, false).await {
+                    msnr_log_err!("Error setting LNA OFF: {}", e);
+                    return Err(e); // Abort test
+                }
+                // Wait for settling
+                self.pause(self.timings.settle()).await;
+                self.check_stopped()?;
+
+                // Run Traceroute Loop
+                self.run_traceroute_phase(
+                    rx,
+                    observer_rx,
+                    cycle,
+                    "LNA OFF",
+                    1,
+                    total_cycles,
+                    false,
+                )
+                .await?;
+                self.check_stopped()?;
+            }
+            first_phase = 1;
+
+            // --- Phase 2: LNA ON ---
+            self.report_phase_start(cycle, total_cycles, "LNA ON", 2);
+
+            // Toggle LNA ON
+            // Toggle LNA ON
+            if let Err(e) = self.set_lna_mode(rx, true).await {
+                msnr_log_err!("Error setting LNA ON: {}", e);
+                return Err(e); // Abort test
+            }
+            // Wait for settling
+            self.pause(self.timings.settle()).await;
+            self.check_stopped()?;
+
+            // Run Traceroute Loop
+            // Run Traceroute Loop
+            self.run_traceroute_phase(rx, observer_rx, cycle, "LNA ON", 2, total_cycles, true)
+                .await?;
+            self.check_stopped()?;
+
+            cycle += 1;
+            if adaptive {
+                match self.plan_adaptive_cycles(cycle, max_cycles, started) {
+                    Some(estimate) => total_cycles = estimate,
+                    None => break,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Connects every observer radio and merges their packet streams, tagged by index.
+    async fn connect_observers(&mut self) -> Result<ObserverReceiver> {
+        let (tx, merged_rx) = mpsc::unbounded_channel();
+        for (index, observer) in self.observers.iter_mut().enumerate() {
+            msnr_log!("Connecting observer radio '{}'...", observer.name);
+            let mut rx = observer.transport.connect().await?;
+            let tx = tx.clone();
+            tokio::spawn(logging::in_current_context(async move {
+                while let Some(packet) = rx.recv().await {
+                    if tx.send((index, packet)).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+        Ok(merged_rx)
+    }
+
+    /// Consumes the connect-time config dump until the local LoRa config shows up, so
+    /// timings can be derived from the modem preset. Gives up once the dump is complete
+    /// without it.
+    async fn await_local_lora(&mut self, rx: &mut meshtastic::packet::PacketReceiver) {
+        use meshtastic::protobufs::from_radio::PayloadVariant;
+
+        let deadline = Instant::now() + Duration::from_secs(CONFIG_DUMP_TIMEOUT_SECS);
+        while self.local_lora.is_none() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match tokio::time::timeout(remaining, rx.recv()).await {
+                Ok(Some(packet)) => {
+                    self.observe_primary_packet(&packet);
+                    if matches!(
+                        packet.payload_variant,
+                        Some(PayloadVariant::ConfigCompleteId(_))
+                    ) {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Fills in unset timings from the local modem preset and logs the values in effect.
+    fn resolve_timings(&mut self) {
+        self.timings = self.config.timings(self.local_lora_params());
+        msnr_log!(
+            "Timings: {} LNA attempt(s), config wait {} ms, SetConfig ack {} ms, settle {} ms, owner info {} ms, range test settle {} ms{}",
+            self.timings.lna_max_attempts,
+            self.timings.lna_wait_timeout_ms,
+            self.timings.lna_ack_timeout_ms,
+            self.timings.settle_ms,
+            self.timings.owner_info_timeout_ms,
+            self.timings.range_test_settle_ms,
+            if self.local_lora.is_none() {
+                " (local preset unknown, using minimums)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    /// Inject User Identity (Client-Side Signing)
+    async fn inject_identity(&mut self) {
+        // Private Key provided by user
+        let priv_key_b64 = "EP7uGaSlaoJHVp5wYVzv5O6fQQNx+q8yb9OshyMANmU=";
+        use base64::Engine;
+        if let Ok(priv_bytes) = base64::prelude::BASE64_STANDARD.decode(priv_key_b64) {
+            msnr_log!("Injecting User Identity (Client-Side Signing)...");
+            self.transport.set_identity(priv_bytes).await;
+        } else {
+            msnr_log_err!("Error decoding private key!");
+        }
+    }
+
+    async fn set_lna_mode(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        enable: bool,
+    ) -> Result<()> {
+        let target_node = match self.resolve_lna_control_node() {
+            Some(node) => node,
+            None => {
+                msnr_log!("LNA control disabled or missing target node, skipping toggle.");
+                return Ok(());
+            }
+        };
+        let target_id = target_node.as_u32();
+
+        msnr_log!("Fetching Local Node Info...");
+        let owner_req = AdminMessage {
+            payload_variant: Some(admin_message::PayloadVariant::GetOwnerRequest(true)),
+            ..Default::default()
+        };
+        self.send_admin_with_session(NodeId::LOCAL, &owner_req)
+            .await?;
+
+        let info_timeout = self.timings.owner_info_timeout();
+        let info_start = Instant::now();
+        loop {
+            if info_start.elapsed() > info_timeout {
+                msnr_warn!("Warning: Could not fetch local node info.");
+                break;
             }
             let sleep = tokio::time::sleep(Duration::from_millis(100));
             tokio::select! {
                 packet = rx.recv() => {
                     if let Some(p) = packet {
-                        self.remember_session_key_from_packet(&p);
+                        self.observe_primary_packet(&p);
                         if let Some(meshtastic::protobufs::from_radio::PayloadVariant::Packet(mesh_pkt)) = p.payload_variant {
                             if let Some(meshtastic::protobufs::mesh_packet::PayloadVariant::Decoded(meshtastic::protobufs::Data { portnum, payload, .. })) = mesh_pkt.payload_variant {
                                 if portnum == PortNum::AdminApp as i32 {
@@ -273,16 +1158,9 @@ impl Engine {
             }
         }
 
-        if !self.has_session_key(&target_node) {
-            let session_req = AdminMessage {
-                payload_variant: Some(admin_message::PayloadVariant::GetConfigRequest(
-                    admin_message::ConfigType::SessionkeyConfig as i32,
-                )),
-                ..Default::default()
-            };
-            self.send_admin_with_session(&target_node, &session_req)
-                .await?;
-        }
+        // Finish waiting out a reboot noticed since the last toggle
+        self.await_reboot_recovery(rx).await?;
+        self.ensure_session_key(target_node).await?;
 
         let get_req = AdminMessage {
             payload_variant: Some(admin_message::PayloadVariant::GetConfigRequest(
@@ -292,13 +1170,29 @@ impl Engine {
         };
 
         let mut lora = self
-            .fetch_lora_config_with_retry(rx, &target_node, target_id, &get_req, LNA_MAX_ATTEMPTS)
+            .fetch_lora_config_with_retry(rx, target_node, &get_req, self.timings.lna_max_attempts)
             .await?;
 
         msnr_log!(
             "Received LoRa Config. Current RX Gain: {:?}",
             lora.sx126x_rx_boosted_gain
         );
+        if self.lna_original.is_none() && !self.config.dry_run {
+            self.lna_original = Some(RemoteLnaState {
+                node: target_node,
+                rx_boosted_gain: lora.sx126x_rx_boosted_gain,
+            });
+        }
+
+        if self.config.dry_run {
+            msnr_log!(
+                "[DRY RUN] Would set RX Boosted Gain on {} from {} to {}; SetConfig not sent.",
+                target_node,
+                lora.sx126x_rx_boosted_gain,
+                enable
+            );
+            return Ok(());
+        }
 
         msnr_log!("Setting LNA (RX Boosted Gain) to {}...", enable);
         lora.sx126x_rx_boosted_gain = enable;
@@ -312,14 +1206,25 @@ impl Engine {
 
         let mut success = false;
 
-        for attempt in 1..=10 {
-            msnr_log!("Attempt {}/10: Setting LNA...", attempt);
-            self.send_admin_with_session(&target_node, &set_req).await?;
+        let attempts = self.timings.lna_max_attempts;
+        for attempt in 1..=attempts {
+            msnr_log!("Attempt {}/{}: Setting LNA...", attempt, attempts);
+            self.emit(EngineEvent::LnaSetRequested {
+                node: target_node,
+                enable,
+                attempt,
+                max_attempts: attempts,
+            });
+            self.send_admin_with_session(target_node, &set_req).await?;
+            self.note_config_sent(target_node);
             msnr_log!("Set Config Request sent (PKI Encrypted). Waiting for ACK/Response...");
 
             let ack_start = Instant::now();
-            let ack_timeout = Duration::from_secs(LNA_ACK_TIMEOUT_SECS);
+            let ack_timeout = self.timings.lna_ack_timeout();
             loop {
+                if self.reboot.is_some() {
+                    break;
+                }
                 if ack_start.elapsed() > ack_timeout {
                     msnr_log!(
                         "Wait for SetACK timed out (This is normal if node is silent on success)."
@@ -331,14 +1236,14 @@ impl Engine {
                     result = rx.recv() => {
                         match result {
                             Some(packet) => {
-                                self.remember_session_key_from_packet(&packet);
+                                self.observe_primary_packet(&packet);
                                 if let Some(meshtastic::protobufs::from_radio::PayloadVariant::Packet(mesh_packet)) = packet.payload_variant {
                                     if mesh_packet.from == target_id {
                                         if let Some(meshtastic::protobufs::mesh_packet::PayloadVariant::Decoded(meshtastic::protobufs::Data { portnum, payload, .. })) = mesh_packet.payload_variant {
-                                            msnr_log!("Received packet from target on port {}: {:02X?}", portnum, payload);
+                                            msnr_debug!("Received packet from target on port {}: {:02X?}", portnum, payload);
                                             if portnum == PortNum::AdminApp as i32 {
                                                 if let Ok(admin_msg) = AdminMessage::decode(payload.as_slice()) {
-                                                    msnr_log!("AdminMessage Response: {:?}", admin_msg.payload_variant);
+                                                    msnr_debug!("AdminMessage Response: {:?}", admin_msg.payload_variant);
                                                 }
                                             }
                                         }
@@ -352,12 +1257,13 @@ impl Engine {
                 }
             }
 
+            // The new LoRa config may have restarted the node; verify once it is back
+            self.await_reboot_recovery(rx).await?;
+
             msnr_log!("Verifying...");
-            tokio::time::sleep(Duration::from_secs(2)).await;
+            tokio::time::sleep(Duration::from_secs(LNA_VERIFY_DELAY_SECS)).await;
 
-            let verify_result = self
-                .fetch_lora_config_once(rx, &target_node, target_id, &get_req)
-                .await;
+            let verify_result = self.fetch_lora_config_once(rx, target_node, &get_req).await;
 
             let mut verified = false;
             match verify_result {
@@ -369,7 +1275,7 @@ impl Engine {
                         );
                         verified = true;
                     } else {
-                        msnr_log!(
+                        msnr_warn!(
                             "❌ LNA Verification FAILED! (Expected: {}, Got: {})",
                             enable,
                             config_lora.sx126x_rx_boosted_gain
@@ -377,7 +1283,7 @@ impl Engine {
                     }
                 }
                 Err(e) => {
-                    msnr_log!(
+                    msnr_warn!(
                         "WARNING: Verification Read Timed Out! (Attempt {}) | {}",
                         attempt,
                         e
@@ -386,18 +1292,26 @@ impl Engine {
             }
 
             if verified {
+                self.emit(EngineEvent::LnaVerified {
+                    node: target_node,
+                    enable,
+                });
+                self.remote_lna = Some(RemoteLnaState {
+                    node: target_node,
+                    rx_boosted_gain: enable,
+                });
                 success = true;
                 break;
             } else {
-                msnr_log!("⚠️ Attempt {} failed. Retrying...", attempt);
+                msnr_warn!("⚠️ Attempt {} failed. Retrying...", attempt);
                 tokio::time::sleep(Duration::from_secs(1)).await;
             }
         }
 
         if !success {
             let err_msg = format!(
-                "CRITICAL ERROR: Failed to toggle LNA to {} after 10 attempts! Aborting test.",
-                enable
+                "CRITICAL ERROR: Failed to toggle LNA to {} after {} attempts! Aborting test.",
+                enable, attempts
             );
             msnr_log!("{}", err_msg);
             return Err(anyhow::anyhow!(err_msg));
@@ -406,68 +1320,95 @@ impl Engine {
         Ok(())
     }
 
-    fn report_phase_start<F>(
-        &self,
-        on_progress: &F,
-        cycle: u32,
-        total_cycles: u32,
-        phase_name: &str,
-        phase_num: u8,
-    ) where
-        F: Fn(ProgressState) + Send + Sync + 'static,
-    {
+    fn emit(&self, event: EngineEvent) {
+        for tx in &self.events {
+            // A dropped receiver just stops listening; the run carries on
+            let _ = tx.send(event.clone());
+        }
+    }
+
+    fn report_phase_start(&self, cycle: u32, total_cycles: u32, phase_name: &str, phase_num: u8) {
         // Global ETA Calculation
-        let total_duration_secs =
-            (total_cycles as u64) * 2 * (self.config.phase_duration_ms as u64 / 1000);
+        let total_duration_secs = (total_cycles as u64) * 2 * (self.phase_duration_ms() / 1000);
         let passed_phases = (cycle * 2) + (phase_num as u32 - 1);
-        let passed_seconds = passed_phases as u64 * (self.config.phase_duration_ms as u64 / 1000);
+        let passed_seconds = passed_phases as u64 * (self.phase_duration_ms() / 1000);
         let remaining = total_duration_secs.saturating_sub(passed_seconds);
 
-        on_progress(ProgressState {
-            total_progress: (passed_phases as f32) / ((total_cycles * 2) as f32),
-            current_round_progress: 0.0,
-            status_message: format!(
-                "Cycle {}/{}: Starting Phase {} ({})",
-                cycle + 1,
-                total_cycles,
-                phase_num,
-                phase_name
-            ),
-            eta_seconds: remaining,
-            snr_towards: None,
-            snr_back: None,
+        self.emit(EngineEvent::PhaseStarted {
+            cycle,
+            total_cycles,
             phase: phase_name.to_string(),
-            average_stats: None,
+            phase_num,
+            eta_seconds: remaining,
         });
     }
 
-    async fn run_traceroute_phase<F>(
+    async fn run_traceroute_phase(
         &mut self,
         rx: &mut meshtastic::packet::PacketReceiver,
-        on_progress: &F,
+        observer_rx: &mut ObserverReceiver,
         cycle: u32,
         phase_name: &str,
         phase_num: u8,
         total_cycles: u32,
         is_lna_on: bool,
-    ) -> Result<()>
-    where
-        F: Fn(ProgressState) + Send + Sync + 'static,
-    {
+    ) -> Result<()> {
         let start_time = Instant::now();
-        let phase_duration = Duration::from_millis(self.config.phase_duration_ms as u64);
-        let total_steps = self.config.phase_duration_ms / 1000;
+        let phase_duration = Duration::from_millis(self.phase_duration_ms());
+        let total_steps = self.phase_duration_ms() / 1000;
         let mut interval = tokio::time::interval(Duration::from_secs(1));
 
         // Consume the first tick
         interval.tick().await;
 
         msnr_log!("Engine started. Config: {:?}", self.config);
-        use std::io::Write;
         let _ = std::io::stdout().flush();
 
+        let phase_index = cycle * 2 + (phase_num as u32 - 1);
+        // Keep the previous phase's requests so their stragglers are recognised as late
+        self.expire_pending_traceroutes(phase_index);
+
+        let passive = self.config.measurement_mode == MeasurementMode::Passive;
+        let range_test = self.config.measurement_mode == MeasurementMode::RangeTest;
+        let sends_traceroutes = !passive && !range_test;
+        let mut traceroute_interval_secs = if sends_traceroutes {
+            self.plan_traceroute_interval()?
+        } else {
+            0
+        };
+        let mut interval_planned_with_lora = !sends_traceroutes || self.local_lora.is_some();
+        self.range_window = range_test.then(RangeTestWindow::new);
+        let primary_before = self.primary_totals(is_lna_on);
+        let mut phase_airtime = Duration::ZERO;
+        // Time lost to reboots is paused out, so the phase still measures for its full duration
+        let mut excluded = Duration::ZERO;
+        let stop = self.stop.clone();
+
         loop {
-            let elapsed = start_time.elapsed();
+            if stop.is_stopped() {
+                msnr_log!("Stop requested; ending {} early.", phase_name);
+                break;
+            }
+            if self.reboot.is_some() {
+                let downtime = self.await_reboot_recovery(rx).await?;
+                let paused = downtime.min(start_time.elapsed().saturating_sub(excluded));
+                excluded += paused;
+                let stats = self.phase_stats_mut(is_lna_on);
+                stats.reboots += 1;
+                stats.reboot_excluded += paused;
+                // Observer reports queued during the outage belong to the excluded window
+                while observer_rx.try_recv().is_ok() {}
+                // The sender restarts its sequence numbers, so keep what was counted so far
+                if self.close_range_window(is_lna_on, paused).is_some() {
+                    self.range_window = Some(RangeTestWindow::new());
+                }
+            }
+
+            if self.checkpoint_due {
+                self.save_checkpoint(cycle, phase_num, total_cycles);
+            }
+
+            let elapsed = start_time.elapsed().saturating_sub(excluded);
             if elapsed >= phase_duration {
                 break;
             }
@@ -483,165 +1424,224 @@ impl Engine {
                     let total_phases = total_cycles * 2;
                     let current_global_phase_idx = (cycle * 2) + (phase_num as u32 - 1);
                     let future_phases = total_phases - current_global_phase_idx - 1;
-                    let future_seconds = future_phases as u64 * (self.config.phase_duration_ms as u64 / 1000);
+                    let future_seconds = future_phases as u64 * (self.phase_duration_ms() / 1000);
                     let global_remaining = remaining_in_phase + future_seconds;
 
                     let global_progress = (current_global_phase_idx as f32 + progress) / total_phases as f32;
 
-                    on_progress(ProgressState {
-                        total_progress: global_progress.min(0.99),
-                        current_round_progress: progress,
-                        status_message: format!("Cycle {}: {} - Step {}/{}", cycle + 1, phase_name, elapsed_secs, total_steps),
-                        eta_seconds: global_remaining,
-                        snr_towards: None,
-                        snr_back: None,
+                    self.emit(EngineEvent::PhaseProgress {
+                        cycle,
                         phase: phase_name.to_string(),
-                        average_stats: None,
+                        step: elapsed_secs,
+                        total_steps,
+                        phase_progress: progress,
+                        total_progress: global_progress,
+                        eta_seconds: global_remaining,
                     });
 
-                    // Send traceroute based on configured interval
-                    let interval_secs = self.config.interval_ms / 1000;
+                    // Re-plan once the local LoRa config has arrived
+                    if !interval_planned_with_lora && self.local_lora.is_some() {
+                        traceroute_interval_secs = self.plan_traceroute_interval()?;
+                        interval_planned_with_lora = true;
+                    }
 
-                    if interval_secs > 0 && elapsed_secs % (interval_secs as u64) == 0 {
+                    // Send traceroute based on the (possibly stretched) interval
+                    if traceroute_interval_secs > 0 && elapsed_secs % traceroute_interval_secs == 0 {
                          // Determine target based on topology
                          let target = match self.config.topology {
-                             crate::config::Topology::Relay => self.config.mountain_node_id.clone(),
-                             crate::config::Topology::Direct => self.config.target_node_id.clone(),
-                         }.unwrap_or_default();
+                             crate::config::Topology::Relay => self.config.mountain_node_id,
+                             crate::config::Topology::Direct => self.config.target_node_id,
+                         };
+
+                         if let Some(target) = target {
+                             if self.reserve_traceroute_airtime() {
+                                 match self.transport.run_traceroute(target).await {
+                                     Ok(request) => {
+                                         self.emit(EngineEvent::TracerouteSent {
+                                             observer: PRIMARY_OBSERVER.to_string(),
+                                             target: request.target,
+                                             request_id: request.packet_id,
+                                         });
+                                         self.track_traceroute(None, request, phase_index, is_lna_on);
+                                         phase_airtime += self.traceroute_channel_airtime().unwrap_or_default();
+                                     }
+                                     Err(e) => msnr_log_err!("Error sending traceroute: {}", e),
+                                 }
+                             }
+                         }
 
-                         if !target.is_empty() {
-                             if let Err(e) = self.transport.run_traceroute(&target).await {
-                                 msnr_log!("Error sending traceroute: {}", e);
+                         // Observers trace back towards the primary node
+                         if let Some(local) = self.local_node {
+                             for index in 0..self.observers.len() {
+                                 match self.observers[index].transport.run_traceroute(local).await {
+                                     Ok(request) => {
+                                         self.emit(EngineEvent::TracerouteSent {
+                                             observer: self.observers[index].name.clone(),
+                                             target: request.target,
+                                             request_id: request.packet_id,
+                                         });
+                                         self.track_traceroute(Some(index), request, phase_index, is_lna_on);
+                                     }
+                                     Err(e) => msnr_log_err!("Error sending traceroute from observer '{}': {}", self.observers[index].name, e),
+                                 }
                              }
                          }
                     }
                 }
+                _ = stop.stopped() => {}
+                Some((index, packet)) = observer_rx.recv(), if !self.observers.is_empty() => {
+                    self.handle_observer_packet(index, packet, cycle, phase_name, phase_index, is_lna_on);
+                }
                 result = rx.recv() => {
                     match result {
                         Some(packet) => {
-                            self.remember_session_key_from_packet(&packet);
-                            use meshtastic::protobufs::{PortNum, Data, RouteDiscovery};
-                            use meshtastic::protobufs::from_radio::PayloadVariant;
-                            use prost::Message;
+                            self.observe_primary_packet(&packet);
+                            self.record_link_observation(None, &packet, cycle, phase_name);
+                            if passive {
+                                self.record_passive_packet(&packet, cycle, phase_name, is_lna_on);
+                            }
+                            if range_test {
+                                self.record_range_test_packet(&packet, cycle, phase_name, is_lna_on);
+                            }
+                            self.record_neighbor_info(&packet, cycle, phase_name, is_lna_on);
+                            self.record_telemetry(&packet, cycle, phase_name, is_lna_on);
 
-                            if let Some(PayloadVariant::Packet(mesh_packet)) = packet.payload_variant {
-                                if let Some(meshtastic::protobufs::mesh_packet::PayloadVariant::Decoded(Data { portnum, payload, .. })) = mesh_packet.payload_variant {
+                            if let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = packet.payload_variant {
+                                if let Some(mesh_packet::PayloadVariant::Decoded(Data { portnum, payload, request_id, .. })) = mesh_packet.payload_variant {
                                      if portnum == PortNum::TracerouteApp as i32 {
                                          match RouteDiscovery::decode(&payload[..]) {
                                             Ok(route_discovery) => {
-                                                msnr_log!("TRACEROUTE RESPONSE RECEIVED! ({})", phase_name);
+                                                let timing = match self.match_traceroute_reply(None, request_id, phase_index, is_lna_on) {
+                                                    Some(timing) => timing,
+                                                    None => continue,
+                                                };
+                                                msnr_log!(
+                                                    "TRACEROUTE RESPONSE RECEIVED! ({}, RTT {} ms, {} hop(s) out / {} back)",
+                                                    phase_name,
+                                                    timing.rtt.as_millis(),
+                                                    route_discovery.route.len(),
+                                                    route_discovery.route_back.len()
+                                                );
 
                                                 let snr_towards: Vec<f32> = route_discovery.snr_towards.iter().map(|&x| x as f32 / 4.0).collect();
                                                 let snr_back: Vec<f32> = route_discovery.snr_back.iter().map(|&x| x as f32 / 4.0).collect();
 
                                                  let hit_floor = snr_towards.iter().chain(snr_back.iter()).any(|value| (*value + 32.0).abs() < f32::EPSILON);
                                                  if hit_floor {
-                                                     msnr_log!("Skipping traceroute sample (SNR hit -32 dB floor).");
+                                                     msnr_warn!("Skipping traceroute sample (SNR hit -32 dB floor).");
+                                                     self.emit(EngineEvent::SampleRejected { request_id, reason: RejectReason::SnrFloor });
                                                      continue;
                                                  }
 
-                                                let roof_to_mtn_sample = snr_towards.get(1).copied();
-                                                let mtn_to_roof_sample = snr_back.get(0).copied();
+                                                // Last forward link reaches the mountain, first return link leaves it
+                                                let roof_to_mtn_sample = snr_towards.get(route_discovery.route.len()).copied();
+                                                let mtn_to_roof_sample = snr_back.first().copied();
 
                                                 if matches!(self.config.topology, crate::config::Topology::Relay) {
-                                                    let roof_id = match Self::parse_configured_node_u32(&self.config.roof_node_id) {
-                                                        Some(id) => id,
-                                                        None => {
-                                                            msnr_log!("❌ VALIDATION FAIL: Roof node ID is not configured, discarding sample.");
-                                                            continue;
-                                                        }
-                                                    };
-
-                                                    match Self::validate_relay_route(&route_discovery.route, roof_id) {
-                                                        Ok(RouteValidationOutcome::RoofOnly) => {
-                                                            msnr_log!(
-                                                                "✅ VALIDATION PASS: Forward route hops: {}",
-                                                                Self::format_route(&route_discovery.route)
-                                                            );
-                                                        }
-                                                        Err(reason) => {
-                                                            msnr_log!(
-                                                                "❌ VALIDATION FAIL (Forward): {} | Route {}",
-                                                                reason,
-                                                                Self::format_route(&route_discovery.route)
-                                                            );
-                                                            continue;
+                                                    let checks = [
+                                                        ("Forward", "Route", &route_discovery.route, self.config.forward_path()),
+                                                        ("Return", "RouteBack", &route_discovery.route_back, self.config.return_path()),
+                                                    ];
+                                                    let mut valid = true;
+                                                    for (direction, label, route, expected) in checks {
+                                                        match Self::validate_path(route, &expected) {
+                                                            Ok(()) => {
+                                                                msnr_log!(
+                                                                    "✅ VALIDATION PASS: {} route hops: {}",
+                                                                    direction,
+                                                                    Self::format_route(route)
+                                                                );
+                                                            }
+                                                            Err(reason) => {
+                                                                msnr_warn!(
+                                                                    "❌ VALIDATION FAIL ({}): {} | {} {}",
+                                                                    direction,
+                                                                    reason,
+                                                                    label,
+                                                                    Self::format_route(route)
+                                                                );
+                                                                self.emit(EngineEvent::SampleRejected {
+                                                                    request_id,
+                                                                    reason: RejectReason::RouteMismatch {
+                                                                        direction: direction.to_string(),
+                                                                        detail: reason.to_string(),
+                                                                    },
+                                                                });
+                                                                valid = false;
+                                                                break;
+                                                            }
                                                         }
                                                     }
-
-                                                    match Self::validate_relay_return_route(&route_discovery.route_back, roof_id) {
-                                                        Ok(RouteValidationOutcome::RoofOnly) => {
-                                                            msnr_log!(
-                                                                "✅ VALIDATION PASS: Return route hops: {}",
-                                                                Self::format_route(&route_discovery.route_back)
-                                                            );
-                                                        }
-                                                        Err(reason) => {
-                                                            msnr_log!(
-                                                                "❌ VALIDATION FAIL (Return): {} | RouteBack {}",
-                                                                reason,
-                                                                Self::format_route(&route_discovery.route_back)
-                                                            );
-                                                            continue;
-                                                        }
+                                                    if !valid {
+                                                        continue;
                                                     }
                                                 }
 
-                                                if is_lna_on {
-                                                    self.stats_lna_on.add_sample(roof_to_mtn_sample, mtn_to_roof_sample);
-                                                } else {
-                                                    self.stats_lna_off.add_sample(roof_to_mtn_sample, mtn_to_roof_sample);
-                                                }
+                                                // RTT only counts for samples that made it through validation
+                                                let stats = self.phase_stats_mut(is_lna_on);
+                                                stats.add_sample(roof_to_mtn_sample, mtn_to_roof_sample);
+                                                stats.rtt.add_sample(timing.rtt);
+                                                let origin = self.local_node.unwrap_or(NodeId::LOCAL);
+                                                self.record_route_links(is_lna_on, origin, NodeId::new(mesh_packet.from), &route_discovery, &snr_towards, &snr_back);
 
-                                                let averages_snapshot = self.current_average_stats();
-
-                                                // Emit live SNR data along with averages
-                                                 on_progress(ProgressState {
-                                                     total_progress: (cycle as f32) / (total_cycles as f32), // Approximate
-                                                     current_round_progress: 0.0, // Don't disrupt progress bar
-                                                     status_message: format!("Received Result ({})", phase_name),
-                                                     eta_seconds: 0, // Should use tracked value but 0 is fine for ephemeral
-                                                     snr_towards: Some(snr_towards.clone()),
-                                                     snr_back: Some(snr_back.clone()),
-                                                     phase: phase_name.to_string(),
-                                                     average_stats: Some(averages_snapshot),
-                                                 });
-
-                                                // Logic Validation for Relay Topology
-                                                if matches!(self.config.topology, crate::config::Topology::Relay) {
-                                                    let record = TracerouteRecord {
-                                                       timestamp: chrono::Local::now().to_rfc3339(),
-                                                       cycle,
-                                                        phase: phase_name.to_string(),
-                                                        route: format!("{:?}", route_discovery.route),
-                                                        route_back: format!("{:?}", route_discovery.route_back),
-                                                        snr_towards_1_room_roof: snr_towards.get(0).copied(),
-                                                        snr_towards_2_roof_mtn: snr_towards.get(1).copied(),
-                                                        snr_back_1_mtn_roof: snr_back.get(0).copied(),
-                                                        snr_back_2_roof_room: snr_back.get(1).copied(),
-                                                    };
-
-                                                    if snr_towards.len() >= 2 && snr_back.len() >= 2 {
-                                                        msnr_log!("--- SNR DATA (Roof <-> Mtn) ---");
-                                                        msnr_log!("Roof -> Mtn : {:.2} dB", snr_towards[1]);
-                                                        msnr_log!("Mtn  -> Roof: {:.2} dB", snr_back[0]);
-                                                        msnr_log!("-----------------------------");
-                                                    }
+                                                self.emit(EngineEvent::SampleAccepted {
+                                                    observer: PRIMARY_OBSERVER.to_string(),
+                                                    phase: phase_name.to_string(),
+                                                    request_id,
+                                                    rtt_ms: timing.rtt.as_millis() as u64,
+                                                    snr_towards: snr_towards.clone(),
+                                                    snr_back: snr_back.clone(),
+                                                });
+                                                self.emit(EngineEvent::StatsUpdated { stats: self.current_average_stats() });
 
-                                                    if let Err(e) = self.append_csv_record(&record) {
-                                                        msnr_log!("Error writing CSV: {}", e);
+                                                let relay = matches!(self.config.topology, crate::config::Topology::Relay);
+                                                // Direct scans are often driven around, so every reply keeps a geotagged row
+                                                let record = TracerouteRecord {
+                                                    timestamp: chrono::Local::now().to_rfc3339(),
+                                                    cycle,
+                                                    phase: phase_name.to_string(),
+                                                    observer: PRIMARY_OBSERVER.to_string(),
+                                                    request_id,
+                                                    sent_at: timing.sent_at.to_rfc3339(),
+                                                    rtt_ms: timing.rtt.as_millis() as u64,
+                                                    hops_towards: route_discovery.route.len(),
+                                                    hops_back: route_discovery.route_back.len(),
+                                                    route: format!("{:?}", route_discovery.route),
+                                                    route_back: format!("{:?}", route_discovery.route_back),
+                                                    snr_towards: snr_towards.clone(),
+                                                    snr_back: snr_back.clone(),
+                                                    link_snr: if relay {
+                                                        self.relay_link_snr(&snr_towards, &snr_back)
                                                     } else {
-                                                        msnr_log!("Data saved to CSV.");
+                                                        Vec::new()
+                                                    },
+                                                    geo: self.geo_columns(),
+                                                };
+
+                                                if relay {
+                                                    msnr_log!("--- SNR DATA (per link) ---");
+                                                    for (column, value) in self.relay_link_columns().iter().zip(record.link_snr.iter()) {
+                                                        if let Some(value) = value {
+                                                            msnr_log!("{}: {:.2} dB", column, value);
+                                                        }
                                                     }
+                                                    msnr_log!("---------------------------");
                                                 } else {
                                                     msnr_log!("SNR Towards: {:?}", snr_towards);
                                                     msnr_log!("SNR Back: {:?}", snr_back);
                                                 }
 
-                                                 use std::io::Write;
+                                                if let Err(e) = self.append_csv_record(&record) {
+                                                    msnr_log_err!("Error writing CSV: {}", e);
+                                                } else if relay {
+                                                    msnr_log!("Data saved to CSV.");
+                                                }
+                                                // RSSI of the reply's last hop into the local radio; 0 means not measured
+                                                let rssi = (mesh_packet.rx_rssi != 0).then_some(mesh_packet.rx_rssi);
+                                                self.record_geo_sample(cycle, phase_name, is_lna_on, "traceroute", NodeId::new(mesh_packet.from), snr_back.last().copied(), rssi);
                                                  let _ = std::io::stdout().flush();
                                              }
-                                             Err(e) => msnr_log!("Failed to decode RouteDiscovery: {}", e),
+                                             Err(e) => msnr_warn!("Failed to decode RouteDiscovery: {}", e),
                                          }
                                      }
                                 }
@@ -655,14 +1655,167 @@ impl Engine {
                 }
             }
         }
+
+        msnr_log!(
+            "Cycle {} {}: estimated channel airtime for traceroutes {:.1} s ({:.2}% of phase)",
+            cycle + 1,
+            phase_name,
+            phase_airtime.as_secs_f32(),
+            phase_airtime.as_secs_f32() / phase_duration.as_secs_f32().max(1.0) * 100.0
+        );
+        if let Some((received, expected)) = self.close_range_window(is_lna_on, Duration::ZERO) {
+            msnr_log!(
+                "Cycle {} {}: range test received {}/{} packets",
+                cycle + 1,
+                phase_name,
+                received,
+                expected
+            );
+        }
+        self.record_phase_mean(cycle, is_lna_on, primary_before);
+        if is_lna_on {
+            self.stats_lna_on.airtime += phase_airtime;
+            self.check_channel_util_confound(cycle);
+        } else {
+            self.stats_lna_off.airtime += phase_airtime;
+        }
+        self.save_checkpoint(cycle, phase_num, total_cycles);
         Ok(())
     }
 
+    /// Configured phase length, capped in dry-run mode.
+    fn phase_duration_ms(&self) -> u64 {
+        self.config.effective_phase_duration_ms()
+    }
+
+    fn local_lora_params(&self) -> Option<LoraParams> {
+        self.local_lora
+            .as_ref()
+            .and_then(LoraParams::from_lora_config)
+    }
+
+    /// Airtime of the traceroute request our own radio transmits.
+    fn traceroute_tx_airtime(&self) -> Option<Duration> {
+        self.local_lora_params()
+            .map(|params| params.packet_airtime(TRACEROUTE_REQUEST_BYTES))
+    }
+
+    /// Airtime the whole exchange occupies on the channel, counting every relay hop.
+    fn traceroute_channel_airtime(&self) -> Option<Duration> {
+        let params = self.local_lora_params()?;
+        let transmissions_each_way = match self.config.topology {
+            crate::config::Topology::Relay => 2,
+            crate::config::Topology::Direct => 1,
+        };
+        Some(
+            (params.packet_airtime(TRACEROUTE_REQUEST_BYTES)
+                + params.packet_airtime(TRACEROUTE_REPLY_BYTES))
+                * transmissions_each_way,
+        )
+    }
+
+    /// Returns the traceroute interval in seconds, stretched or refused if it breaks the duty cycle.
+    fn plan_traceroute_interval(&self) -> Result<u64> {
+        self.plan_duty_cycle(
+            "Traceroute",
+            self.config.interval_ms / 1000,
+            self.traceroute_tx_airtime(),
+        )
+    }
+
+    /// Returns the range test sender interval in seconds, checked against the duty cycle like
+    /// traceroutes. The remote sender is assumed to use the local modem preset.
+    fn plan_range_test_sender(&self) -> Result<u32> {
+        let airtime = self
+            .local_lora_params()
+            .map(|params| params.packet_airtime(RANGE_TEST_PACKET_BYTES));
+        let interval_secs = (self.config.interval_ms / 1000).max(1);
+        Ok(self.plan_duty_cycle("Range test", interval_secs, airtime)? as u32)
+    }
+
+    /// Stretches or refuses `interval_secs` if one `airtime` transmission per interval
+    /// breaks the duty cycle limit.
+    fn plan_duty_cycle(
+        &self,
+        what: &str,
+        interval_secs: u64,
+        airtime: Option<Duration>,
+    ) -> Result<u64> {
+        let Some(limit) = self.config.duty_cycle_limit_percent else {
+            return Ok(interval_secs);
+        };
+        let Some(airtime) = airtime else {
+            msnr_log!("Local LoRa config unknown yet; duty cycle limit not applied to schedule.");
+            return Ok(interval_secs);
+        };
+        if interval_secs == 0 {
+            return Ok(0);
+        }
+
+        let duty = airtime.as_secs_f64() / interval_secs as f64 * 100.0;
+        if duty <= limit as f64 {
+            msnr_log!(
+                "{} airtime {:.0} ms every {} s = {:.2}% duty cycle (limit {:.2}%)",
+                what,
+                airtime.as_secs_f64() * 1000.0,
+                interval_secs,
+                duty,
+                limit
+            );
+            return Ok(interval_secs);
+        }
+
+        let min_interval = (airtime.as_secs_f64() * 100.0 / limit as f64).ceil() as u64;
+        match self.config.duty_cycle_policy {
+            DutyCyclePolicy::Refuse => Err(anyhow::anyhow!(
+                "{} interval {} s needs {:.2}% duty cycle, above the {:.2}% limit (minimum interval {} s)",
+                what,
+                interval_secs,
+                duty,
+                limit,
+                min_interval
+            )),
+            DutyCyclePolicy::Stretch => {
+                msnr_warn!(
+                    "⚠️ {} interval {} s would use {:.2}% duty cycle; stretching to {} s to stay under {:.2}%",
+                    what,
+                    interval_secs,
+                    duty,
+                    min_interval,
+                    limit
+                );
+                Ok(min_interval)
+            }
+        }
+    }
+
+    /// Checks the rolling budget and books the airtime if the traceroute may be sent.
+    fn reserve_traceroute_airtime(&mut self) -> bool {
+        let (Some(limit), Some(airtime)) = (
+            self.config.duty_cycle_limit_percent,
+            self.traceroute_tx_airtime(),
+        ) else {
+            return true;
+        };
+
+        let now = Instant::now();
+        let utilization = self.airtime.utilization_with(now, airtime);
+        if utilization > limit {
+            msnr_warn!(
+                "Skipping traceroute: rolling duty cycle would reach {:.2}% (limit {:.2}%)",
+                utilization,
+                limit
+            );
+            return false;
+        }
+        self.airtime.record(now, airtime);
+        true
+    }
+
     async fn fetch_lora_config_with_retry(
         &mut self,
         rx: &mut meshtastic::packet::PacketReceiver,
-        target_node: &str,
-        target_id: u32,
+        target_node: NodeId,
         get_req: &AdminMessage,
         attempts: u32,
     ) -> Result<config::LoRaConfig> {
@@ -673,14 +1826,11 @@ impl Engine {
                 attempt,
                 attempts
             );
-            match self
-                .fetch_lora_config_once(rx, target_node, target_id, get_req)
-                .await
-            {
+            match self.fetch_lora_config_once(rx, target_node, get_req).await {
                 Ok(lora) => return Ok(lora),
                 Err(e) => {
                     if attempt == attempts {
-                        msnr_log!(
+                        msnr_warn!(
                             "WARNING: Get Config failed after {} attempts for {}. {}",
                             attempts,
                             target_node,
@@ -709,17 +1859,13 @@ impl Engine {
     async fn fetch_lora_config_once(
         &mut self,
         rx: &mut meshtastic::packet::PacketReceiver,
-        target_node: &str,
-        target_id: u32,
+        target_node: NodeId,
         get_req: &AdminMessage,
     ) -> Result<config::LoRaConfig> {
         self.send_admin_with_session(target_node, get_req).await?;
-        self.wait_for_lora_config_response(
-            rx,
-            target_id,
-            Duration::from_secs(LNA_WAIT_TIMEOUT_SECS),
-        )
-        .await
+        let timeout = self.timings.lna_wait_timeout();
+        self.wait_for_lora_config_response(rx, target_node.as_u32(), timeout)
+            .await
     }
 
     async fn wait_for_lora_config_response(
@@ -744,7 +1890,7 @@ impl Engine {
                 result = rx.recv() => {
                     match result {
                         Some(packet) => {
-                            self.remember_session_key_from_packet(&packet);
+                            self.observe_primary_packet(&packet);
                             if let Some(PayloadVariant::Packet(mesh_packet)) = packet.payload_variant {
                                 if mesh_packet.from == target_id {
                                      if let Some(mesh_packet::PayloadVariant::Decoded(Data { portnum, payload, .. })) = mesh_packet.payload_variant {
@@ -773,32 +1919,220 @@ impl Engine {
         }
     }
 
-    fn resolve_lna_control_node(&self) -> Option<String> {
+    /// Adds the open range test window to the phase totals and returns its (received, expected).
+    ///
+    /// Expected packets follow from the window's measured time, less `paused`, and the
+    /// sender interval, so packets lost at either end of the window still count.
+    fn close_range_window(&mut self, is_lna_on: bool, paused: Duration) -> Option<(u32, u32)> {
+        let window = self.range_window.take()?;
+        let measured = window.opened.elapsed().saturating_sub(paused);
+        let expected = (measured.as_secs() / self.range_test_sender_secs.max(1) as u64) as u32;
+        let stats = &mut self.phase_stats_mut(is_lna_on).range_test;
+        stats.expected += expected;
+        stats.received += window.received;
+        Some((window.received, expected))
+    }
+
+    fn resolve_lna_control_node(&self) -> Option<NodeId> {
         match self.config.topology {
             crate::config::Topology::Relay => match self.config.lna_control_target {
                 LnaControlTarget::Disabled => None,
-                LnaControlTarget::Roof => self.config.roof_node_id.clone(),
-                LnaControlTarget::Mountain => self.config.mountain_node_id.clone(),
+                LnaControlTarget::Roof => self.config.roof_node_id,
+                LnaControlTarget::Mountain => self.config.mountain_node_id,
             },
             crate::config::Topology::Direct => match self.config.lna_control_target {
                 LnaControlTarget::Disabled => None,
-                _ => self.config.target_node_id.clone(),
+                _ => self.config.target_node_id,
             },
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug)]
 struct TracerouteRecord {
     timestamp: String,
     cycle: u32,
     phase: String,
+    observer: String,
+    request_id: u32,
+    sent_at: String,
+    rtt_ms: u64,
+    hops_towards: usize,
+    hops_back: usize,
     route: String,
     route_back: String,
-    snr_towards_1_room_roof: Option<f32>,
-    snr_towards_2_roof_mtn: Option<f32>,
-    snr_back_1_mtn_roof: Option<f32>,
-    snr_back_2_roof_room: Option<f32>,
+    snr_towards: Vec<f32>,
+    snr_back: Vec<f32>,
+    /// One value per configured relay link, in the order of `Engine::relay_link_columns`.
+    link_snr: Vec<Option<f32>>,
+    geo: GeoColumns,
+}
+
+/// Position columns attached to every output row; empty when no fix is available.
+#[derive(Debug, Default, Clone, Copy)]
+struct GeoColumns {
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude_m: Option<f32>,
+    speed_mps: Option<f32>,
+}
+
+impl TracerouteRecord {
+    const BASE_COLUMNS: [&'static str; 17] = [
+        "timestamp",
+        "cycle",
+        "phase",
+        "observer",
+        "request_id",
+        "sent_at",
+        "rtt_ms",
+        "hops_towards",
+        "hops_back",
+        "route",
+        "route_back",
+        "snr_towards",
+        "snr_back",
+        "latitude",
+        "longitude",
+        "altitude_m",
+        "speed_mps",
+    ];
+
+    fn to_row(&self, link_columns: usize) -> Vec<String> {
+        let mut row = vec![
+            self.timestamp.clone(),
+            self.cycle.to_string(),
+            self.phase.clone(),
+            self.observer.clone(),
+            self.request_id.to_string(),
+            self.sent_at.clone(),
+            self.rtt_ms.to_string(),
+            self.hops_towards.to_string(),
+            self.hops_back.to_string(),
+            self.route.clone(),
+            self.route_back.clone(),
+            format!("{:?}", self.snr_towards),
+            format!("{:?}", self.snr_back),
+            display_opt(self.geo.latitude),
+            display_opt(self.geo.longitude),
+            display_opt(self.geo.altitude_m),
+            display_opt(self.geo.speed_mps),
+        ];
+        row.extend((0..link_columns).map(|index| {
+            self.link_snr
+                .get(index)
+                .copied()
+                .flatten()
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        }));
+        row
+    }
+}
+
+fn display_opt<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+#[derive(Debug, Serialize)]
+struct PassiveRecord {
+    timestamp: String,
+    cycle: u32,
+    phase: String,
+    from: String,
+    portnum: Option<i32>,
+    hop_start: u32,
+    hop_limit: u32,
+    hops_away: u32,
+    rx_snr: f32,
+    rx_rssi: i32,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude_m: Option<f32>,
+    speed_mps: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct RangeTestRecord {
+    timestamp: String,
+    cycle: u32,
+    phase: String,
+    from: String,
+    seq: u32,
+    hops_away: u32,
+    rx_snr: f32,
+    rx_rssi: i32,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude_m: Option<f32>,
+    speed_mps: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct NeighborInfoRecord {
+    timestamp: String,
+    cycle: u32,
+    phase: String,
+    reporter: String,
+    neighbor: String,
+    snr: f32,
+    last_rx_time: u32,
+    broadcast_interval_secs: u32,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude_m: Option<f32>,
+    speed_mps: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct TelemetryRecord {
+    timestamp: String,
+    cycle: u32,
+    phase: String,
+    node: String,
+    kind: &'static str,
+    battery_level: Option<u32>,
+    voltage: Option<f32>,
+    channel_utilization: Option<f32>,
+    air_util_tx: Option<f32>,
+    uptime_seconds: Option<u32>,
+    num_packets_rx: Option<u32>,
+    num_packets_rx_bad: Option<u32>,
+    num_rx_dupe: Option<u32>,
+    num_online_nodes: Option<u32>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude_m: Option<f32>,
+    speed_mps: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+struct LinkObservationRecord {
+    timestamp: String,
+    cycle: u32,
+    phase: String,
+    observer: String,
+    from: String,
+    portnum: Option<i32>,
+    hops_away: u32,
+    rx_snr: f32,
+    rx_rssi: i32,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude_m: Option<f32>,
+    speed_mps: Option<f32>,
+}
+
+/// One line of `<stem>.session.jsonl`, written whenever a run starts or resumes.
+#[derive(Debug, Serialize)]
+struct SessionMetadata<'a> {
+    session: &'a str,
+    started_at: String,
+    resumed: bool,
+    local_node: Option<NodeId>,
+    local_lora: Option<String>,
+    timings: Timings,
+    config: &'a Config,
 }
 
 impl Engine {
@@ -824,120 +2158,1406 @@ impl Engine {
             display_opt(stats.lna_on_mtn_to_roof),
             display_opt(stats.delta_mtn_to_roof())
         );
-        msnr_log!("========================================================");
-
-        fn display_opt(val: Option<f32>) -> String {
-            val.map(|v| format!("{:.2}", v))
-                .unwrap_or_else(|| "--".into())
-        }
-    }
-
-    fn append_csv_record(&self, record: &TracerouteRecord) -> Result<()> {
-        let file_exists = std::path::Path::new(&self.config.output_path).exists();
+        msnr_log!(
+            "Round-trip (median) | OFF: {} ms | ON: {} ms | Δ: {} ms",
+            display_opt(stats.lna_off_rtt_median_ms),
+            display_opt(stats.lna_on_rtt_median_ms),
+            display_opt(stats.delta_rtt_median_ms())
+        );
+        msnr_log!(
+            "Round-trip (p90)    | OFF: {} ms | ON: {} ms | Δ: {} ms",
+            display_opt(stats.lna_off_rtt_p90_ms),
+            display_opt(stats.lna_on_rtt_p90_ms),
+            display_opt(stats.delta_rtt_p90_ms())
+        );
+        msnr_log!(
+            "Traceroutes answered - LNA OFF: {}/{} | LNA ON: {}/{} | late: {} | unmatched: {}",
+            self.stats_lna_off.replies_matched,
+            self.stats_lna_off.traceroutes_sent,
+            self.stats_lna_on.replies_matched,
+            self.stats_lna_on.traceroutes_sent,
+            self.late_replies,
+            self.unmatched_replies
+        );
+        for observer in &stats.observers {
+            msnr_log!(
+                "Observer {} ({}) | OFF: {} samples | ON: {} samples",
+                observer.name,
+                Self::format_node_id(observer.node.map(NodeId::as_u32)),
+                observer.lna_off_samples,
+                observer.lna_on_samples
+            );
+            msnr_log!(
+                "  SNR at {} (avg) | OFF: {} dB | ON: {} dB | Δ: {} dB",
+                PRIMARY_OBSERVER,
+                display_opt(observer.lna_off_snr_at_local),
+                display_opt(observer.lna_on_snr_at_local),
+                display_opt(observer.delta_snr_at_local())
+            );
+            msnr_log!(
+                "  SNR at observer (avg) | OFF: {} dB | ON: {} dB | Δ: {} dB",
+                display_opt(observer.lna_off_snr_at_observer),
+                display_opt(observer.lna_on_snr_at_observer),
+                display_opt(observer.delta_snr_at_observer())
+            );
+        }
+        for link in &stats.links {
+            msnr_log!(
+                "Link {} -> {} (avg) | OFF: {} dB ({}) | ON: {} dB ({}) | Δ: {} dB",
+                link.from,
+                link.to,
+                display_opt(link.lna_off_snr),
+                link.lna_off_samples,
+                display_opt(link.lna_on_snr),
+                link.lna_on_samples,
+                display_opt(link.delta_snr())
+            );
+            if link.lna_off_neighbor_samples + link.lna_on_neighbor_samples > 0 {
+                msnr_log!(
+                    "  NeighborInfo       | OFF: {} dB ({}) | ON: {} dB ({}) | Δ: {} dB",
+                    display_opt(link.lna_off_neighbor_snr),
+                    link.lna_off_neighbor_samples,
+                    display_opt(link.lna_on_neighbor_snr),
+                    link.lna_on_neighbor_samples,
+                    display_opt(link.delta_neighbor_snr())
+                );
+            }
+        }
+        let range_tests = [
+            ("OFF", &stats.lna_off_range_test),
+            ("ON", &stats.lna_on_range_test),
+        ];
+        for (label, summary) in range_tests {
+            if let Some(summary) = summary {
+                msnr_log!(
+                    "Range test LNA {} | received {}/{} | loss {} % | SNR {} dB | RSSI {} dBm",
+                    label,
+                    summary.received,
+                    summary.expected,
+                    display_opt(summary.loss_ratio.map(|loss| loss * 100.0)),
+                    display_opt(summary.rx_snr),
+                    display_opt(summary.rx_rssi)
+                );
+            }
+        }
+        for node in &stats.channel_util {
+            msnr_log!(
+                "Channel util {} (avg) | OFF: {} % | ON: {} %",
+                node.node,
+                display_opt(node.lna_off_percent),
+                display_opt(node.lna_on_percent)
+            );
+        }
+        if !stats.confounded_cycles.is_empty() {
+            msnr_warn!(
+                "⚠️ Cycles with confounding channel utilization: {:?}",
+                stats.confounded_cycles
+            );
+        }
+        for node in &stats.heard {
+            msnr_log!(
+                "Heard {} (avg) | OFF: {} dB / {} dBm ({}) | ON: {} dB / {} dBm ({}) | Δ SNR: {} dB",
+                node.node,
+                display_opt(node.lna_off_rx_snr),
+                display_opt(node.lna_off_rx_rssi),
+                node.lna_off_samples,
+                display_opt(node.lna_on_rx_snr),
+                display_opt(node.lna_on_rx_rssi),
+                node.lna_on_samples,
+                display_opt(node.delta_rx_snr())
+            );
+        }
+        msnr_log!("========================================================");
+
+        fn display_opt(val: Option<f32>) -> String {
+            val.map(|v| format!("{:.2}", v))
+                .unwrap_or_else(|| "--".into())
+        }
+    }
+
+    fn append_csv_record(&self, record: &TracerouteRecord) -> Result<()> {
+        let output_path = self.output_path();
+        let path = output_path.as_path();
+        let link_columns = self.relay_link_columns();
+        let file_exists = path.exists();
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(file);
+
+        if !file_exists {
+            let header = TracerouteRecord::BASE_COLUMNS
+                .iter()
+                .map(|column| column.to_string())
+                .chain(link_columns.iter().cloned());
+            writer.write_record(header)?;
+        }
+        writer.write_record(record.to_row(link_columns.len()))?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Per-link SNR column names for the configured relay path, forward links first.
+    ///
+    /// Ends are labelled `local` and the mountain ID; wildcard hops become `hop<n>`.
+    fn relay_link_columns(&self) -> Vec<String> {
+        if !matches!(self.config.topology, crate::config::Topology::Relay) {
+            return Vec::new();
+        }
+
+        let mountain = self
+            .config
+            .mountain_node_id
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "mountain".to_string());
+        let label = |position: usize, hop: &PathHop| match hop {
+            PathHop::Node(id) => id.to_string(),
+            PathHop::Any => format!("hop{}", position + 1),
+        };
+
+        let mut forward = vec![PRIMARY_OBSERVER.to_string()];
+        forward.extend(
+            self.config
+                .forward_path()
+                .iter()
+                .enumerate()
+                .map(|(position, hop)| label(position, hop)),
+        );
+        forward.push(mountain.clone());
+
+        let mut back = vec![mountain];
+        back.extend(
+            self.config
+                .return_path()
+                .iter()
+                .enumerate()
+                .map(|(position, hop)| label(position, hop)),
+        );
+        back.push(PRIMARY_OBSERVER.to_string());
+
+        forward
+            .windows(2)
+            .chain(back.windows(2))
+            .map(|pair| format!("snr_{}_to_{}", pair[0], pair[1]))
+            .collect()
+    }
+
+    /// Per-link SNR in `relay_link_columns` order. Each direction is padded or cut to its own
+    /// column count, so a short forward route never shifts return links into forward columns.
+    fn relay_link_snr(&self, snr_towards: &[f32], snr_back: &[f32]) -> Vec<Option<f32>> {
+        let forward_links = self.config.forward_path().len() + 1;
+        let return_links = self.config.return_path().len() + 1;
+        let fill = |values: &[f32], links: usize| {
+            (0..links)
+                .map(|link| values.get(link).copied())
+                .collect::<Vec<_>>()
+        };
+        let mut link_snr = fill(snr_towards, forward_links);
+        link_snr.extend(fill(snr_back, return_links));
+        link_snr
+    }
+
+    /// Main output file; dry runs write `<stem>.dryrun.<ext>` so real results stay untouched.
+    fn output_path(&self) -> PathBuf {
+        let path = PathBuf::from(&self.config.output_path);
+        if !self.config.dry_run {
+            return path;
+        }
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("results");
+        let file_name = match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => format!("{}.dryrun.{}", stem, ext),
+            None => format!("{}.dryrun", stem),
+        };
+        path.with_file_name(file_name)
+    }
+
+    /// Path next to the main output, e.g. `results.csv` -> `results.<suffix>`.
+    fn sibling_output_path(&self, suffix: &str) -> PathBuf {
+        let path = self.output_path();
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("results");
+        path.with_file_name(format!("{}.{}", stem, suffix))
+    }
+
+    /// Tags records with this engine's session and routes them to its sink, if any.
+    fn log_context(&self) -> LogContext {
+        let context = LogContext::new(self.session_id.as_str());
+        match &self.log_sink {
+            Some(sink) => context.with_sink(sink.clone()),
+            None => context,
+        }
+    }
+
+    /// Log context for a run, also writing every level to `<stem>.log` next to the output.
+    fn session_log_context(&self) -> LogContext {
+        let context = self.log_context();
+        let path = self.sibling_output_path("log");
+        match std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+        {
+            Ok(file) => context.with_file(file),
+            Err(e) => {
+                msnr_log_err!("Error opening session log {}: {}", path.display(), e);
+                context
+            }
+        }
+    }
+
+    fn write_session_metadata(&self, resumed: bool) {
+        let metadata = SessionMetadata {
+            session: &self.session_id,
+            started_at: chrono::Local::now().to_rfc3339(),
+            resumed,
+            local_node: self.local_node,
+            local_lora: self.local_lora_params().map(|params| {
+                format!(
+                    "SF{} / {:.1} kHz / CR 4/{}",
+                    params.spreading_factor,
+                    params.bandwidth_hz / 1000.0,
+                    params.coding_rate
+                )
+            }),
+            timings: self.timings,
+            config: &self.config,
+        };
+
+        let path = self.sibling_output_path("session.jsonl");
+        let result = serde_json::to_string(&metadata)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| {
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)?;
+                writeln!(file, "{}", line)?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            msnr_log_err!("Error writing session metadata {}: {}", path.display(), e);
+        }
+    }
+
+    /// Checkpoint file next to the main output, e.g. `results.checkpoint.json`.
+    fn checkpoint_path(&self) -> PathBuf {
+        self.sibling_output_path("checkpoint.json")
+    }
+
+    /// Rewrites the checkpoint; written through a temporary file so a crash mid-write
+    /// leaves the previous one intact.
+    fn save_checkpoint(&mut self, cycle: u32, phase_num: u8, total_cycles: u32) {
+        self.checkpoint_due = false;
+        // A dry run changed nothing worth continuing
+        if self.config.dry_run {
+            return;
+        }
+        let checkpoint = Checkpoint {
+            config: self.config.clone(),
+            cycle,
+            phase_num,
+            total_cycles,
+            elapsed_ms: self.cycles_started.elapsed().as_millis() as u64,
+            remote_lna: self.remote_lna,
+            lna_original: self.lna_original,
+            range_test_original: self
+                .range_test_original
+                .as_ref()
+                .map(|(node, original)| (*node, original.encode_to_vec())),
+            stats_lna_off: self.stats_lna_off.clone(),
+            stats_lna_on: self.stats_lna_on.clone(),
+            cycle_channel_util: self.cycle_channel_util.clone(),
+            cycle_deltas: self.cycle_deltas.clone(),
+            lna_off_phase_mean: self.lna_off_phase_mean,
+            confounded_cycles: self.confounded_cycles.clone(),
+            geo_samples: self.geo_samples.clone(),
+        };
+
+        let path = self.checkpoint_path();
+        let tmp_path = path.with_extension("tmp");
+        let result = serde_json::to_string(&checkpoint)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                std::fs::write(&tmp_path, json)?;
+                std::fs::rename(&tmp_path, &path)?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            msnr_log_err!("Error writing checkpoint {}: {}", path.display(), e);
+        }
+    }
+
+    /// A finished run leaves nothing to resume.
+    fn remove_checkpoint(&self) {
+        let path = self.checkpoint_path();
+        if path.exists() {
+            if let Err(e) = std::fs::remove_file(&path) {
+                msnr_log_err!("Could not remove checkpoint {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    /// Loads the accumulated state and re-reads the controlled node's LNA setting,
+    /// which may have changed (or reset on reboot) while we were down.
+    async fn restore_checkpoint(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        checkpoint: Checkpoint,
+    ) -> Result<ResumePoint> {
+        msnr_log!(
+            "Resuming after cycle {} phase {} ({} LNA OFF / {} LNA ON samples so far)",
+            checkpoint.cycle + 1,
+            checkpoint.phase_num,
+            checkpoint.stats_lna_off.count_roof_to_mtn(),
+            checkpoint.stats_lna_on.count_roof_to_mtn()
+        );
+        self.stats_lna_off = checkpoint.stats_lna_off;
+        self.stats_lna_on = checkpoint.stats_lna_on;
+        self.cycle_channel_util = checkpoint.cycle_channel_util;
+        self.cycle_deltas = checkpoint.cycle_deltas;
+        self.lna_off_phase_mean = checkpoint.lna_off_phase_mean;
+        self.confounded_cycles = checkpoint.confounded_cycles;
+        self.geo_samples = checkpoint.geo_samples;
+        self.range_test_original = match checkpoint.range_test_original {
+            Some((node, encoded)) => Some((
+                node,
+                module_config::RangeTestConfig::decode(encoded.as_slice())?,
+            )),
+            None => None,
+        };
+        self.remote_lna = checkpoint.remote_lna;
+        self.lna_original = checkpoint.lna_original;
+
+        if let Some(node) = self.resolve_lna_control_node() {
+            self.ensure_session_key(node).await?;
+            let get_req = AdminMessage {
+                payload_variant: Some(admin_message::PayloadVariant::GetConfigRequest(
+                    admin_message::ConfigType::LoraConfig as i32,
+                )),
+                ..Default::default()
+            };
+            let current = self
+                .fetch_lora_config_with_retry(rx, node, &get_req, self.timings.lna_max_attempts)
+                .await?
+                .sx126x_rx_boosted_gain;
+            match checkpoint.remote_lna {
+                Some(known) if known.node == node && known.rx_boosted_gain == current => {
+                    msnr_log!("✅ LNA on {} still {} as checkpointed", node, current);
+                }
+                Some(known) => msnr_log_err!(
+                    "⚠️ LNA on {} reads {} but the checkpoint recorded {} on {}; the next phase sets it again",
+                    node,
+                    current,
+                    known.rx_boosted_gain,
+                    known.node
+                ),
+                None => msnr_log!("LNA on {} currently {}", node, current),
+            }
+            self.remote_lna = Some(RemoteLnaState {
+                node,
+                rx_boosted_gain: current,
+            });
+        }
+
+        Ok(ResumePoint {
+            cycle: checkpoint.cycle,
+            phase_num: checkpoint.phase_num,
+            total_cycles: checkpoint.total_cycles,
+            elapsed: Duration::from_millis(checkpoint.elapsed_ms),
+        })
+    }
+
+    fn append_csv_row<T: Serialize>(path: &Path, record: &T) -> Result<()> {
+        let file_exists = path.exists();
         let file = std::fs::OpenOptions::new()
             .create(true)
             .append(true)
-            .open(&self.config.output_path)?;
+            .open(path)?;
 
         let mut writer = csv::WriterBuilder::new()
             .has_headers(!file_exists)
             .from_writer(file);
 
-        writer.serialize(record)?;
-        writer.flush()?;
-        Ok(())
+        writer.serialize(record)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn current_average_stats(&self) -> AverageStats {
+        AverageStats {
+            lna_off_samples: self.stats_lna_off.count_roof_to_mtn(),
+            lna_off_roof_to_mtn: self.stats_lna_off.average_roof_to_mtn(),
+            lna_off_mtn_to_roof: self.stats_lna_off.average_mtn_to_roof(),
+            lna_on_samples: self.stats_lna_on.count_roof_to_mtn(),
+            lna_on_roof_to_mtn: self.stats_lna_on.average_roof_to_mtn(),
+            lna_on_mtn_to_roof: self.stats_lna_on.average_mtn_to_roof(),
+            lna_off_airtime_secs: self.stats_lna_off.airtime.as_secs_f32(),
+            lna_on_airtime_secs: self.stats_lna_on.airtime.as_secs_f32(),
+            lna_off_rtt_median_ms: self.stats_lna_off.rtt.median(),
+            lna_off_rtt_p90_ms: self.stats_lna_off.rtt.percentile(90.0),
+            lna_on_rtt_median_ms: self.stats_lna_on.rtt.median(),
+            lna_on_rtt_p90_ms: self.stats_lna_on.rtt.percentile(90.0),
+            links: self.link_stats(),
+            heard: self.heard_node_stats(),
+            channel_util: self.channel_util_stats(),
+            confounded_cycles: self.confounded_cycles.clone(),
+            lna_off_range_test: self.stats_lna_off.range_test.summary(),
+            lna_on_range_test: self.stats_lna_on.range_test.summary(),
+            delta_ci95_half_width_db: self
+                .delta_ci_half_width()
+                .map(|(half_width, _)| half_width as f32),
+            lna_off_reboots: self.stats_lna_off.reboots,
+            lna_on_reboots: self.stats_lna_on.reboots,
+            lna_off_reboot_excluded_secs: self.stats_lna_off.reboot_excluded.as_secs_f32(),
+            lna_on_reboot_excluded_secs: self.stats_lna_on.reboot_excluded.as_secs_f32(),
+            observers: self.observer_stats(),
+        }
+    }
+
+    /// The OFF/ON sample sets the stopping rule looks at for the current measurement mode.
+    fn primary_delta_stats(&self) -> (Option<&ChannelStats>, Option<&ChannelStats>) {
+        match self.config.measurement_mode {
+            MeasurementMode::Traceroute => (
+                Some(&self.stats_lna_off.roof_to_mtn),
+                Some(&self.stats_lna_on.roof_to_mtn),
+            ),
+            MeasurementMode::RangeTest => (
+                Some(&self.stats_lna_off.range_test.rx_snr),
+                Some(&self.stats_lna_on.range_test.rx_snr),
+            ),
+            MeasurementMode::Passive => {
+                let Some(node) = self.measured_node() else {
+                    return (None, None);
+                };
+                (
+                    self.stats_lna_off
+                        .heard
+                        .get(&node)
+                        .map(|heard| &heard.rx_snr),
+                    self.stats_lna_on
+                        .heard
+                        .get(&node)
+                        .map(|heard| &heard.rx_snr),
+                )
+            }
+        }
+    }
+
+    /// Sample count and sum of the primary metric in one phase kind, to diff across a phase.
+    fn primary_totals(&self, is_lna_on: bool) -> (u32, f64) {
+        let (off, on) = self.primary_delta_stats();
+        let stats = if is_lna_on { on } else { off };
+        stats.map_or((0, 0.0), |stats| (stats.samples, stats.total()))
+    }
+
+    /// Pairs each cycle's LNA ON phase mean with its LNA OFF one for the stopping rule.
+    /// `before` is `primary_totals` at the start of the phase.
+    fn record_phase_mean(&mut self, cycle: u32, is_lna_on: bool, before: (u32, f64)) {
+        let (samples, total) = self.primary_totals(is_lna_on);
+        let mean = (samples > before.0).then(|| (total - before.1) / (samples - before.0) as f64);
+        if !is_lna_on {
+            self.lna_off_phase_mean = mean.map(|mean| (cycle, mean));
+            return;
+        }
+        if let (Some(on), Some((off_cycle, off))) = (mean, self.lna_off_phase_mean.take()) {
+            if off_cycle == cycle {
+                self.cycle_deltas.add_sample((on - off) as f32);
+            }
+        }
+    }
+
+    /// 95% CI half-width of the mean ON−OFF delta over cycles, from each cycle's paired
+    /// phase means with a t quantile on n−1 degrees of freedom, plus the number of pairs.
+    fn delta_ci_half_width(&self) -> Option<(f64, u32)> {
+        let pairs = self.cycle_deltas.samples;
+        let standard_error = (self.cycle_deltas.variance()? / pairs as f64).sqrt();
+        Some((t_quantile_95(pairs - 1) * standard_error, pairs))
+    }
+
+    /// Largest number of cycles that fits in `max_duration_ms`, or `cycles` when not adaptive.
+    fn max_cycles(&self) -> u32 {
+        match (
+            self.config.target_ci_half_width_db,
+            self.config.max_duration_ms,
+        ) {
+            (Some(_), Some(max_ms)) => {
+                let cycle_ms =
+                    (self.estimated_cycle_duration(0, Duration::ZERO).as_millis() as u64).max(1);
+                ((max_ms / cycle_ms) as u32).max(1)
+            }
+            _ => self.config.cycles,
+        }
+    }
+
+    /// Expected length of one ON/OFF cycle: both phases plus the LNA toggle and settle time
+    /// before each. Once cycles have run, their measured average is used if longer, which
+    /// also covers slow toggles, reboot pauses and the stretched duty cycle budget.
+    fn estimated_cycle_duration(&self, completed: u32, elapsed: Duration) -> Duration {
+        let toggle = if self.resolve_lna_control_node().is_some() {
+            self.timings.owner_info_timeout()
+                + self.timings.lna_ack_timeout()
+                + Duration::from_secs(LNA_VERIFY_DELAY_SECS)
+        } else {
+            Duration::ZERO
+        };
+        let planned =
+            (Duration::from_millis(self.phase_duration_ms()) + self.timings.settle() + toggle) * 2;
+        if completed == 0 {
+            planned
+        } else {
+            planned.max(elapsed / completed)
+        }
+    }
+
+    /// Decides after each completed cycle whether adaptive stopping is satisfied.
+    /// Returns `None` to stop, otherwise the estimated total number of cycles.
+    fn plan_adaptive_cycles(
+        &self,
+        completed: u32,
+        max_cycles: u32,
+        started: Instant,
+    ) -> Option<u32> {
+        let target = self.config.target_ci_half_width_db? as f64;
+        // Stop before a cycle that would not finish within the budget
+        let elapsed = started.elapsed();
+        let next_cycle = self.estimated_cycle_duration(completed, elapsed);
+        if completed >= max_cycles
+            || self
+                .config
+                .max_duration_ms
+                .is_some_and(|max_ms| elapsed + next_cycle > Duration::from_millis(max_ms))
+        {
+            msnr_log!(
+                "Adaptive stop: maximum duration reached after {} cycle(s).",
+                completed
+            );
+            return None;
+        }
+
+        let min_cycles = self.config.cycles.max(1);
+        let Some((half_width, pairs)) = self.delta_ci_half_width() else {
+            msnr_log!("Adaptive stop: not enough paired cycles for a confidence interval yet.");
+            return Some((completed + 1).max(min_cycles).min(max_cycles));
+        };
+        msnr_log!(
+            "Adaptive stop: ±{:.2} dB after {} cycle(s) (target ±{:.2} dB)",
+            half_width,
+            completed,
+            target
+        );
+        if half_width <= target && completed >= min_cycles {
+            msnr_log!("Adaptive stop: confidence target reached.");
+            return None;
+        }
+
+        // Half-width shrinks with 1/sqrt(n): extrapolate the pairs still needed from the
+        // share of cycles that produced one so far.
+        let needed = pairs as f64 * (half_width / target).powi(2);
+        let per_cycle = pairs as f64 / completed.max(1) as f64;
+        let more_cycles = ((needed - pairs as f64).max(0.0) / per_cycle).ceil() as u32;
+        Some(
+            (completed + more_cycles.max(1))
+                .max(min_cycles)
+                .min(max_cycles),
+        )
+    }
+
+    /// The far end of the measured link: the range test sender, and the node whose
+    /// passive and adaptive-stopping samples are tracked.
+    fn measured_node(&self) -> Option<NodeId> {
+        match self.config.topology {
+            crate::config::Topology::Relay => self.config.mountain_node_id,
+            crate::config::Topology::Direct => self.config.target_node_id,
+        }
+    }
+
+    /// Turns on the remote Range Test sender and returns its previous config for restoring.
+    async fn enable_range_test(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+    ) -> Result<(NodeId, module_config::RangeTestConfig)> {
+        let node = self
+            .measured_node()
+            .ok_or_else(|| anyhow::anyhow!("Range test mode requires a remote node ID"))?;
+
+        self.ensure_session_key(node).await?;
+
+        let original = self.fetch_range_test_config(rx, node).await?;
+        msnr_log!(
+            "Range test on {}: enabled={}, sender={} s (saving to restore later)",
+            node,
+            original.enabled,
+            original.sender
+        );
+
+        let sender_secs = self.range_test_sender_secs;
+        let enabled = module_config::RangeTestConfig {
+            enabled: true,
+            sender: sender_secs,
+            save: false,
+            ..original.clone()
+        };
+        self.send_range_test_config(node, enabled).await?;
+        msnr_log!(
+            "Enabled range test sender on {} every {} s; waiting {} ms for it to reboot...",
+            node,
+            sender_secs,
+            self.timings.range_test_settle_ms
+        );
+        tokio::time::sleep(self.timings.range_test_settle()).await;
+
+        match self.fetch_range_test_config(rx, node).await {
+            Ok(current) if current.enabled && current.sender == sender_secs => {
+                msnr_log!("✅ Range test sender VERIFIED on {}", node);
+            }
+            Ok(current) => msnr_log_err!(
+                "⚠️ Range test config on {} reads back enabled={}, sender={} s",
+                node,
+                current.enabled,
+                current.sender
+            ),
+            Err(e) => msnr_log_err!("⚠️ Could not verify range test config on {}: {}", node, e),
+        }
+
+        Ok((node, original))
+    }
+
+    async fn restore_range_test(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        node: NodeId,
+        original: module_config::RangeTestConfig,
+    ) -> Result<()> {
+        msnr_log!(
+            "Restoring range test config on {} (enabled={}, sender={} s)...",
+            node,
+            original.enabled,
+            original.sender
+        );
+        let expected = original.clone();
+        self.send_range_test_config(node, original).await?;
+        tokio::time::sleep(self.timings.range_test_settle()).await;
+
+        let current = self.fetch_range_test_config(rx, node).await?;
+        if current.enabled != expected.enabled || current.sender != expected.sender {
+            anyhow::bail!(
+                "range test config on {} reads back enabled={}, sender={} s",
+                node,
+                current.enabled,
+                current.sender
+            );
+        }
+        msnr_log!("✅ Range test config restored on {}", node);
+        Ok(())
+    }
+
+    async fn send_range_test_config(
+        &mut self,
+        node: NodeId,
+        range_test: module_config::RangeTestConfig,
+    ) -> Result<()> {
+        let set_req = AdminMessage {
+            payload_variant: Some(admin_message::PayloadVariant::SetModuleConfig(
+                ModuleConfig {
+                    payload_variant: Some(module_config::PayloadVariant::RangeTest(range_test)),
+                },
+            )),
+            ..Default::default()
+        };
+        self.send_admin_with_session(node, &set_req).await?;
+        self.note_config_sent(node);
+        Ok(())
+    }
+
+    async fn fetch_range_test_config(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        node: NodeId,
+    ) -> Result<module_config::RangeTestConfig> {
+        let get_req = AdminMessage {
+            payload_variant: Some(admin_message::PayloadVariant::GetModuleConfigRequest(
+                admin_message::ModuleConfigType::RangetestConfig as i32,
+            )),
+            ..Default::default()
+        };
+
+        let attempts = self.timings.lna_max_attempts;
+        let timeout = self.timings.lna_wait_timeout();
+        let mut last_error = anyhow::anyhow!("no attempts made");
+        for attempt in 1..=attempts {
+            msnr_log!(
+                "Requesting Range Test config from {}... (Attempt {}/{})",
+                node,
+                attempt,
+                attempts
+            );
+            self.send_admin_with_session(node, &get_req).await?;
+            match self
+                .wait_for_range_test_config_response(rx, node.as_u32(), timeout)
+                .await
+            {
+                Ok(config) => return Ok(config),
+                Err(e) => {
+                    msnr_log!("Attempt {}/{} failed ({})", attempt, attempts, e);
+                    last_error = e;
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn wait_for_range_test_config_response(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        target_id: u32,
+        timeout: Duration,
+    ) -> Result<module_config::RangeTestConfig> {
+        use meshtastic::protobufs::{from_radio, mesh_packet};
+
+        let wait_start = Instant::now();
+        loop {
+            let Some(remaining) = timeout.checked_sub(wait_start.elapsed()) else {
+                anyhow::bail!(
+                    "Timed out waiting for range test config response after {} seconds",
+                    timeout.as_secs()
+                );
+            };
+            let packet = match tokio::time::timeout(remaining, rx.recv()).await {
+                Ok(Some(packet)) => packet,
+                Ok(None) => anyhow::bail!(
+                    "Transport channel closed while waiting for range test config response"
+                ),
+                Err(_) => continue,
+            };
+            self.observe_primary_packet(&packet);
+
+            let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = packet.payload_variant
+            else {
+                continue;
+            };
+            if mesh_packet.from != target_id {
+                continue;
+            }
+            let Some(mesh_packet::PayloadVariant::Decoded(data)) = mesh_packet.payload_variant
+            else {
+                continue;
+            };
+            if data.portnum != PortNum::AdminApp as i32 {
+                continue;
+            }
+            if let Ok(AdminMessage {
+                payload_variant:
+                    Some(admin_message::PayloadVariant::GetModuleConfigResponse(ModuleConfig {
+                        payload_variant: Some(module_config::PayloadVariant::RangeTest(config)),
+                    })),
+                ..
+            }) = AdminMessage::decode(data.payload.as_slice())
+            {
+                return Ok(config);
+            }
+        }
+    }
+
+    /// Tracks a `seq N` packet from the range test sender for loss, SNR and RSSI.
+    fn record_range_test_packet(
+        &mut self,
+        packet: &meshtastic::protobufs::FromRadio,
+        cycle: u32,
+        phase_name: &str,
+        is_lna_on: bool,
+    ) {
+        use meshtastic::protobufs::{from_radio, mesh_packet};
+
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = &packet.payload_variant else {
+            return;
+        };
+        let Some(mesh_packet::PayloadVariant::Decoded(data)) = &mesh_packet.payload_variant else {
+            return;
+        };
+        let from = NodeId::new(mesh_packet.from);
+        if data.portnum != PortNum::RangeTestApp as i32 || Some(from) != self.measured_node() {
+            return;
+        }
+        let Some(seq) = std::str::from_utf8(&data.payload)
+            .ok()
+            .and_then(|text| text.trim().strip_prefix("seq "))
+            .and_then(|seq| seq.trim().parse::<u32>().ok())
+        else {
+            msnr_warn!(
+                "Ignoring range test packet without a sequence number from {}",
+                from
+            );
+            return;
+        };
+
+        let restarted = self
+            .range_window
+            .and_then(|window| window.first_seq)
+            .is_some_and(|first_seq| seq < first_seq);
+        if restarted {
+            // Sender rebooted and restarted its counter; keep what was counted so far.
+            self.close_range_window(is_lna_on, Duration::ZERO);
+        }
+        let window = self.range_window.get_or_insert_with(RangeTestWindow::new);
+        window.first_seq.get_or_insert(seq);
+        window.received += 1;
+
+        let stats = &mut self.phase_stats_mut(is_lna_on).range_test;
+        stats.rx_snr.add_sample(mesh_packet.rx_snr);
+        stats.rx_rssi.add_sample(mesh_packet.rx_rssi as f32);
+
+        let geo = self.geo_columns();
+        let record = RangeTestRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            cycle,
+            phase: phase_name.to_string(),
+            from: from.to_string(),
+            seq,
+            hops_away: mesh_packet.hop_start.saturating_sub(mesh_packet.hop_limit),
+            rx_snr: mesh_packet.rx_snr,
+            rx_rssi: mesh_packet.rx_rssi,
+            latitude: geo.latitude,
+            longitude: geo.longitude,
+            altitude_m: geo.altitude_m,
+            speed_mps: geo.speed_mps,
+        };
+        msnr_log!(
+            "[rangetest] {} seq {} | SNR {:.2} dB | RSSI {} dBm ({})",
+            record.from,
+            seq,
+            record.rx_snr,
+            record.rx_rssi,
+            phase_name
+        );
+        self.record_geo_sample(
+            cycle,
+            phase_name,
+            is_lna_on,
+            "rangetest",
+            from,
+            Some(record.rx_snr),
+            Some(record.rx_rssi),
+        );
+        if let Err(e) = Self::append_csv_row(&self.sibling_output_path("rangetest.csv"), &record) {
+            msnr_log_err!("Error writing range test CSV: {}", e);
+        }
+    }
+
+    fn channel_util_stats(&self) -> Vec<ChannelUtilStats> {
+        let nodes: std::collections::BTreeSet<NodeId> = self
+            .stats_lna_off
+            .channel_util
+            .keys()
+            .chain(self.stats_lna_on.channel_util.keys())
+            .copied()
+            .collect();
+
+        nodes
+            .into_iter()
+            .map(|node| ChannelUtilStats {
+                node,
+                lna_off_percent: self
+                    .stats_lna_off
+                    .channel_util
+                    .get(&node)
+                    .and_then(ChannelStats::average),
+                lna_on_percent: self
+                    .stats_lna_on
+                    .channel_util
+                    .get(&node)
+                    .and_then(ChannelStats::average),
+            })
+            .collect()
+    }
+
+    /// Collects DeviceMetrics and LocalStats telemetry broadcast by the local and monitored
+    /// nodes. Nothing is requested, so this adds no airtime.
+    fn record_telemetry(
+        &mut self,
+        packet: &meshtastic::protobufs::FromRadio,
+        cycle: u32,
+        phase_name: &str,
+        is_lna_on: bool,
+    ) {
+        use meshtastic::protobufs::{Telemetry, from_radio, mesh_packet, telemetry};
+
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = &packet.payload_variant else {
+            return;
+        };
+        let Some(mesh_packet::PayloadVariant::Decoded(data)) = &mesh_packet.payload_variant else {
+            return;
+        };
+        if data.portnum != PortNum::TelemetryApp as i32 {
+            return;
+        }
+        let node = NodeId::new(mesh_packet.from);
+        if self.local_node != Some(node) && !self.monitored_nodes().contains(&node) {
+            return;
+        }
+
+        let telemetry = match Telemetry::decode(data.payload.as_slice()) {
+            Ok(telemetry) => telemetry,
+            Err(e) => {
+                msnr_warn!("Failed to decode telemetry from {}: {}", node, e);
+                return;
+            }
+        };
+
+        let geo = self.geo_columns();
+        let mut record = TelemetryRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            cycle,
+            phase: phase_name.to_string(),
+            node: node.to_string(),
+            kind: "",
+            battery_level: None,
+            voltage: None,
+            channel_utilization: None,
+            air_util_tx: None,
+            uptime_seconds: None,
+            num_packets_rx: None,
+            num_packets_rx_bad: None,
+            num_rx_dupe: None,
+            num_online_nodes: None,
+            latitude: geo.latitude,
+            longitude: geo.longitude,
+            altitude_m: geo.altitude_m,
+            speed_mps: geo.speed_mps,
+        };
+        match telemetry.variant {
+            Some(telemetry::Variant::DeviceMetrics(metrics)) => {
+                record.kind = "device";
+                record.battery_level = metrics.battery_level;
+                record.voltage = metrics.voltage;
+                record.channel_utilization = metrics.channel_utilization;
+                record.air_util_tx = metrics.air_util_tx;
+                record.uptime_seconds = metrics.uptime_seconds;
+            }
+            Some(telemetry::Variant::LocalStats(stats)) => {
+                record.kind = "local_stats";
+                record.channel_utilization = Some(stats.channel_utilization);
+                record.air_util_tx = Some(stats.air_util_tx);
+                record.uptime_seconds = Some(stats.uptime_seconds);
+                record.num_packets_rx = Some(stats.num_packets_rx);
+                record.num_packets_rx_bad = Some(stats.num_packets_rx_bad);
+                record.num_rx_dupe = Some(stats.num_rx_dupe);
+                record.num_online_nodes = Some(stats.num_online_nodes);
+            }
+            // Environment/power/air-quality sensors do not affect the comparison.
+            _ => return,
+        }
+
+        if let Some(utilization) = record.channel_utilization {
+            self.phase_stats_mut(is_lna_on)
+                .channel_util
+                .entry(node)
+                .or_default()
+                .add_sample(utilization);
+            self.cycle_channel_util
+                .entry((cycle, is_lna_on))
+                .or_default()
+                .entry(node)
+                .or_default()
+                .add_sample(utilization);
+        }
+
+        msnr_debug!(
+            "[telemetry] {} {} | ch util {}% | air tx {}% | rx bad {} | dupes {}",
+            record.node,
+            record.kind,
+            record
+                .channel_utilization
+                .map(|value| format!("{:.1}", value))
+                .unwrap_or_else(|| "--".into()),
+            record
+                .air_util_tx
+                .map(|value| format!("{:.1}", value))
+                .unwrap_or_else(|| "--".into()),
+            record
+                .num_packets_rx_bad
+                .map(|value| value.to_string())
+                .unwrap_or_else(|| "--".into()),
+            record
+                .num_rx_dupe
+                .map(|value| value.to_string())
+                .unwrap_or_else(|| "--".into())
+        );
+        if let Err(e) = Self::append_csv_row(&self.sibling_output_path("telemetry.csv"), &record) {
+            msnr_log_err!("Error writing telemetry CSV: {}", e);
+        }
+    }
+
+    /// Flags `cycle` when any node's channel utilization moved more than the configured
+    /// threshold between its OFF and ON phases; busier air alone can shift SNR/RTT.
+    fn check_channel_util_confound(&mut self, cycle: u32) {
+        let (Some(off), Some(on)) = (
+            self.cycle_channel_util.get(&(cycle, false)),
+            self.cycle_channel_util.get(&(cycle, true)),
+        ) else {
+            return;
+        };
+
+        let threshold = self.config.channel_util_confound_percent;
+        let mut confounded = false;
+        for (node, off_stats) in off {
+            let (Some(off_avg), Some(on_avg)) = (
+                off_stats.average(),
+                on.get(node).and_then(ChannelStats::average),
+            ) else {
+                continue;
+            };
+            if (on_avg - off_avg).abs() > threshold {
+                let message = format!(
+                    "Cycle {}: channel utilization at {} went from {:.1}% (OFF) to {:.1}% (ON); LNA comparison may be confounded.",
+                    cycle + 1,
+                    node,
+                    off_avg,
+                    on_avg
+                );
+                msnr_warn!("⚠️ {}", message);
+                self.emit(EngineEvent::Warning { message });
+                confounded = true;
+            }
+        }
+        if confounded {
+            self.confounded_cycles.push(cycle + 1);
+        }
+    }
+
+    fn observer_stats(&self) -> Vec<ObserverStats> {
+        let names: std::collections::BTreeSet<&String> = self
+            .stats_lna_off
+            .observers
+            .keys()
+            .chain(self.stats_lna_on.observers.keys())
+            .collect();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let off = self.stats_lna_off.observers.get(name);
+                let on = self.stats_lna_on.observers.get(name);
+                ObserverStats {
+                    name: name.clone(),
+                    node: self
+                        .observers
+                        .iter()
+                        .find(|observer| &observer.name == name)
+                        .and_then(|observer| observer.node_id),
+                    lna_off_samples: off.map(|stats| stats.snr_at_local.samples).unwrap_or(0),
+                    lna_off_snr_at_local: off.and_then(|stats| stats.snr_at_local.average()),
+                    lna_off_snr_at_observer: off.and_then(|stats| stats.snr_at_observer.average()),
+                    lna_off_rtt_median_ms: off.and_then(|stats| stats.rtt.median()),
+                    lna_on_samples: on.map(|stats| stats.snr_at_local.samples).unwrap_or(0),
+                    lna_on_snr_at_local: on.and_then(|stats| stats.snr_at_local.average()),
+                    lna_on_snr_at_observer: on.and_then(|stats| stats.snr_at_observer.average()),
+                    lna_on_rtt_median_ms: on.and_then(|stats| stats.rtt.median()),
+                }
+            })
+            .collect()
+    }
+
+    fn heard_node_stats(&self) -> Vec<HeardNodeStats> {
+        let nodes: std::collections::BTreeSet<NodeId> = self
+            .stats_lna_off
+            .heard
+            .keys()
+            .chain(self.stats_lna_on.heard.keys())
+            .copied()
+            .collect();
+
+        nodes
+            .into_iter()
+            .map(|node| {
+                let off = self.stats_lna_off.heard.get(&node);
+                let on = self.stats_lna_on.heard.get(&node);
+                HeardNodeStats {
+                    node,
+                    lna_off_samples: off.map(|stats| stats.rx_snr.samples).unwrap_or(0),
+                    lna_off_rx_snr: off.and_then(|stats| stats.rx_snr.average()),
+                    lna_off_rx_rssi: off.and_then(|stats| stats.rx_rssi.average()),
+                    lna_on_samples: on.map(|stats| stats.rx_snr.samples).unwrap_or(0),
+                    lna_on_rx_snr: on.and_then(|stats| stats.rx_snr.average()),
+                    lna_on_rx_rssi: on.and_then(|stats| stats.rx_rssi.average()),
+                }
+            })
+            .collect()
+    }
+
+    /// Nodes whose traffic passive mode listens for.
+    fn monitored_nodes(&self) -> Vec<NodeId> {
+        [
+            self.config.roof_node_id,
+            self.config.mountain_node_id,
+            self.config.target_node_id,
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|node| Some(*node) != self.local_node)
+        .collect()
     }
 
-    fn current_average_stats(&self) -> AverageStats {
-        AverageStats {
-            lna_off_samples: self.stats_lna_off.count_roof_to_mtn(),
-            lna_off_roof_to_mtn: self.stats_lna_off.average_roof_to_mtn(),
-            lna_off_mtn_to_roof: self.stats_lna_off.average_mtn_to_roof(),
-            lna_on_samples: self.stats_lna_on.count_roof_to_mtn(),
-            lna_on_roof_to_mtn: self.stats_lna_on.average_roof_to_mtn(),
-            lna_on_mtn_to_roof: self.stats_lna_on.average_mtn_to_roof(),
+    /// Records the neighbor SNRs a monitored node reports in its NeighborInfo broadcast.
+    ///
+    /// The reporter heard each neighbor at the given SNR, so the link is neighbor -> reporter.
+    /// Firmware averages over its own broadcast interval, which can straddle phase boundaries.
+    fn record_neighbor_info(
+        &mut self,
+        packet: &meshtastic::protobufs::FromRadio,
+        cycle: u32,
+        phase_name: &str,
+        is_lna_on: bool,
+    ) {
+        use meshtastic::protobufs::{NeighborInfo, from_radio, mesh_packet};
+
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = &packet.payload_variant else {
+            return;
+        };
+        let Some(mesh_packet::PayloadVariant::Decoded(data)) = &mesh_packet.payload_variant else {
+            return;
+        };
+        if data.portnum != PortNum::NeighborinfoApp as i32 {
+            return;
+        }
+        let reporter = NodeId::new(mesh_packet.from);
+        if !self.monitored_nodes().contains(&reporter) {
+            return;
         }
-    }
 
-    fn validate_relay_route(route: &[u32], roof_id: u32) -> Result<RouteValidationOutcome, String> {
-        if route.is_empty() {
-            return Err("route metadata is empty".to_string());
+        let info = match NeighborInfo::decode(data.payload.as_slice()) {
+            Ok(info) => info,
+            Err(e) => {
+                msnr_warn!("Failed to decode NeighborInfo from {}: {}", reporter, e);
+                return;
+            }
+        };
+
+        let path = self.sibling_output_path("neighbors.csv");
+        let geo = self.geo_columns();
+        for neighbor in &info.neighbors {
+            let neighbor_id = NodeId::new(neighbor.node_id);
+            self.phase_stats_mut(is_lna_on)
+                .neighbor_links
+                .entry((neighbor_id, reporter))
+                .or_default()
+                .add_sample(neighbor.snr);
+
+            msnr_log!(
+                "[neighborinfo] {} hears {} at {:.2} dB ({})",
+                reporter,
+                neighbor_id,
+                neighbor.snr,
+                phase_name
+            );
+            let record = NeighborInfoRecord {
+                timestamp: chrono::Local::now().to_rfc3339(),
+                cycle,
+                phase: phase_name.to_string(),
+                reporter: reporter.to_string(),
+                neighbor: neighbor_id.to_string(),
+                snr: neighbor.snr,
+                last_rx_time: neighbor.last_rx_time,
+                broadcast_interval_secs: info.node_broadcast_interval_secs,
+                latitude: geo.latitude,
+                longitude: geo.longitude,
+                altitude_m: geo.altitude_m,
+                speed_mps: geo.speed_mps,
+            };
+            if let Err(e) = Self::append_csv_row(&path, &record) {
+                msnr_log_err!("Error writing NeighborInfo CSV: {}", e);
+            }
         }
+    }
 
-        if route.len() != 1 {
-            return Err(format!(
-                "expected single-hop route via Roof ({}) but received {} hop(s): {}",
-                Self::format_node_id(Some(roof_id)),
-                route.len(),
-                Self::format_route(route)
-            ));
+    /// Records how the local radio heard a packet originating from a monitored node.
+    ///
+    /// For relayed packets (`hops_away > 0`) the SNR/RSSI belong to the last relay hop. Packets
+    /// that did not arrive over LoRa are skipped.
+    fn record_passive_packet(
+        &mut self,
+        packet: &meshtastic::protobufs::FromRadio,
+        cycle: u32,
+        phase_name: &str,
+        is_lna_on: bool,
+    ) {
+        use meshtastic::protobufs::{from_radio, mesh_packet};
+
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = &packet.payload_variant else {
+            return;
+        };
+        let from = NodeId::new(mesh_packet.from);
+        if !self.monitored_nodes().contains(&from) {
+            return;
+        }
+        // MQTT-bridged and locally generated packets never crossed the air and report no SNR/RSSI
+        if mesh_packet.via_mqtt || (mesh_packet.rx_snr == 0.0 && mesh_packet.rx_rssi == 0) {
+            return;
         }
 
-        let hop = route[0];
-        if hop != roof_id {
-            return Err(format!(
-                "single-hop route {} does not match configured Roof {}",
-                Self::format_node_id(Some(hop)),
-                Self::format_node_id(Some(roof_id))
-            ));
+        let portnum = match &mesh_packet.payload_variant {
+            Some(mesh_packet::PayloadVariant::Decoded(data)) => Some(data.portnum),
+            _ => None,
+        };
+        let geo = self.geo_columns();
+        let record = PassiveRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            cycle,
+            phase: phase_name.to_string(),
+            from: from.to_string(),
+            portnum,
+            hop_start: mesh_packet.hop_start,
+            hop_limit: mesh_packet.hop_limit,
+            hops_away: mesh_packet.hop_start.saturating_sub(mesh_packet.hop_limit),
+            rx_snr: mesh_packet.rx_snr,
+            rx_rssi: mesh_packet.rx_rssi,
+            latitude: geo.latitude,
+            longitude: geo.longitude,
+            altitude_m: geo.altitude_m,
+            speed_mps: geo.speed_mps,
+        };
+        self.record_geo_sample(
+            cycle,
+            phase_name,
+            is_lna_on,
+            "passive",
+            from,
+            Some(record.rx_snr),
+            Some(record.rx_rssi),
+        );
+
+        let stats = self
+            .phase_stats_mut(is_lna_on)
+            .heard
+            .entry(from)
+            .or_default();
+        stats.rx_snr.add_sample(record.rx_snr);
+        stats.rx_rssi.add_sample(record.rx_rssi as f32);
+
+        msnr_log!(
+            "[passive] {} from {} | SNR {:.2} dB | RSSI {} dBm | hops {}/{}",
+            phase_name,
+            record.from,
+            record.rx_snr,
+            record.rx_rssi,
+            record.hops_away,
+            record.hop_start
+        );
+        if let Err(e) = Self::append_csv_row(&self.sibling_output_path("passive.csv"), &record) {
+            msnr_log_err!("Error writing passive CSV: {}", e);
         }
+    }
+
+    /// Merges the per-link aggregates of both phase kinds into one row per node pair.
+    fn link_stats(&self) -> Vec<LinkStats> {
+        let pairs: std::collections::BTreeSet<(NodeId, NodeId)> = self
+            .stats_lna_off
+            .links
+            .keys()
+            .chain(self.stats_lna_on.links.keys())
+            .chain(self.stats_lna_off.neighbor_links.keys())
+            .chain(self.stats_lna_on.neighbor_links.keys())
+            .copied()
+            .collect();
 
-        Ok(RouteValidationOutcome::RoofOnly)
+        pairs
+            .into_iter()
+            .map(|(from, to)| {
+                let off = self.stats_lna_off.links.get(&(from, to));
+                let on = self.stats_lna_on.links.get(&(from, to));
+                let neighbor_off = self.stats_lna_off.neighbor_links.get(&(from, to));
+                let neighbor_on = self.stats_lna_on.neighbor_links.get(&(from, to));
+                LinkStats {
+                    from,
+                    to,
+                    lna_off_samples: off.map(|stats| stats.samples).unwrap_or(0),
+                    lna_off_snr: off.and_then(ChannelStats::average),
+                    lna_on_samples: on.map(|stats| stats.samples).unwrap_or(0),
+                    lna_on_snr: on.and_then(ChannelStats::average),
+                    lna_off_neighbor_samples: neighbor_off.map(|stats| stats.samples).unwrap_or(0),
+                    lna_off_neighbor_snr: neighbor_off.and_then(ChannelStats::average),
+                    lna_on_neighbor_samples: neighbor_on.map(|stats| stats.samples).unwrap_or(0),
+                    lna_on_neighbor_snr: neighbor_on.and_then(ChannelStats::average),
+                }
+            })
+            .collect()
     }
 
-    fn validate_relay_return_route(
-        route_back: &[u32],
-        roof_id: u32,
-    ) -> Result<RouteValidationOutcome, String> {
-        if route_back.is_empty() {
-            return Err("return route metadata is empty".to_string());
-        }
+    /// Adds one SNR sample per link of a traceroute reply, forward and return path alike.
+    fn record_route_links(
+        &mut self,
+        is_lna_on: bool,
+        origin: NodeId,
+        target: NodeId,
+        route_discovery: &meshtastic::protobufs::RouteDiscovery,
+        snr_towards: &[f32],
+        snr_back: &[f32],
+    ) {
+        let mut forward = vec![origin];
+        forward.extend(route_discovery.route.iter().map(|hop| NodeId::new(*hop)));
+        forward.push(target);
 
-        if route_back.len() != 1 {
-            return Err(format!(
-                "expected single-hop return route via Roof ({}) but received {} hop(s): {}",
-                Self::format_node_id(Some(roof_id)),
-                route_back.len(),
-                Self::format_route(route_back)
-            ));
+        let mut back = vec![target];
+        back.extend(
+            route_discovery
+                .route_back
+                .iter()
+                .map(|hop| NodeId::new(*hop)),
+        );
+        back.push(origin);
+
+        let stats = self.phase_stats_mut(is_lna_on);
+        stats.add_link_samples(&forward, snr_towards);
+        stats.add_link_samples(&back, snr_back);
+    }
+
+    /// Checks a traceroute route against the expected hops; `*` matches exactly one node.
+    fn validate_path(route: &[u32], expected: &[PathHop]) -> Result<(), String> {
+        if route.is_empty() {
+            return Err("route metadata is empty".to_string());
         }
 
-        let hop = route_back[0];
-        if hop != roof_id {
+        if route.len() != expected.len() {
             return Err(format!(
-                "return route hop {} does not match configured Roof {}",
-                Self::format_node_id(Some(hop)),
-                Self::format_node_id(Some(roof_id))
+                "expected {}-hop route {} but received {} hop(s): {}",
+                expected.len(),
+                Self::format_path(expected),
+                route.len(),
+                Self::format_route(route)
             ));
         }
 
-        Ok(RouteValidationOutcome::RoofOnly)
-    }
+        for (position, (hop, expected_hop)) in route.iter().zip(expected).enumerate() {
+            if !expected_hop.matches(NodeId::new(*hop)) {
+                return Err(format!(
+                    "hop {} is {} but expected {}",
+                    position + 1,
+                    Self::format_node_id(Some(*hop)),
+                    expected_hop
+                ));
+            }
+        }
 
-    fn parse_configured_node_u32(node_id: &Option<String>) -> Option<u32> {
-        node_id.as_deref().and_then(Self::parse_node_id_str)
+        Ok(())
     }
 
-    fn parse_node_id_str(node_id: &str) -> Option<u32> {
-        if node_id.is_empty() {
-            return None;
-        }
-
-        if let Some(stripped) = node_id.strip_prefix('!') {
-            u32::from_str_radix(stripped, 16).ok()
-        } else if let Some(stripped) = node_id
-            .strip_prefix("0x")
-            .or_else(|| node_id.strip_prefix("0X"))
-        {
-            u32::from_str_radix(stripped, 16).ok()
-        } else {
-            node_id.parse::<u32>().ok()
-        }
+    fn format_path(path: &[PathHop]) -> String {
+        let parts: Vec<String> = path.iter().map(|hop| hop.to_string()).collect();
+        format!("[{}]", parts.join(", "))
     }
 
     fn format_node_id(id: Option<u32>) -> String {
         match id {
-            Some(value) => format!("!{:08x}", value),
+            Some(value) => NodeId::new(value).to_string(),
             None => "unknown".to_string(),
         }
     }
@@ -953,51 +3573,496 @@ impl Engine {
         format!("[{}]", parts.join(", "))
     }
 
-    fn normalized_node_id(node_id: &str) -> Option<String> {
-        if node_id.is_empty() {
-            return None;
+    fn store_session_key(&mut self, node_num: u32, key: &[u8]) {
+        if key.is_empty() {
+            return;
+        }
+        let node = NodeId::new(node_num);
+        if !self.session_keys.contains_key(&node) {
+            msnr_debug!("Stored session key for node {}", node);
+        }
+        self.session_keys.insert(node, key.to_vec());
+    }
+
+    fn apply_session_key(&self, node_id: NodeId, msg: &mut AdminMessage) {
+        if let Some(key) = self.session_keys.get(&node_id) {
+            msg.session_passkey = key.clone();
         }
+    }
 
-        if let Some(id) = node_id.strip_prefix('!') {
-            u32::from_str_radix(id, 16)
-                .ok()
-                .map(|num| format!("!{:08x}", num))
-        } else if let Some(id) = node_id
-            .strip_prefix("0x")
-            .or_else(|| node_id.strip_prefix("0X"))
+    fn observe_primary_packet(&mut self, packet: &meshtastic::protobufs::FromRadio) {
+        self.remember_session_key_from_packet(packet);
+        self.observe_reboot_signals(packet);
+        if let Some(meshtastic::protobufs::from_radio::PayloadVariant::MyInfo(info)) =
+            &packet.payload_variant
         {
-            u32::from_str_radix(id, 16)
-                .ok()
-                .map(|num| format!("!{:08x}", num))
-        } else if node_id.eq_ignore_ascii_case("broadcast") {
-            Some(format!("!{:08x}", u32::MAX))
-        } else {
-            node_id
-                .parse::<u32>()
-                .ok()
-                .map(|num| format!("!{:08x}", num))
+            let node = NodeId::new(info.my_node_num);
+            if self.local_node != Some(node) {
+                msnr_log!("Primary radio is node {}", node);
+            }
+            self.local_node = Some(node);
+        }
+        if let Some(meshtastic::protobufs::from_radio::PayloadVariant::Config(MeshConfig {
+            payload_variant: Some(config::PayloadVariant::Lora(lora)),
+        })) = &packet.payload_variant
+        {
+            if let Some(params) = LoraParams::from_lora_config(lora) {
+                msnr_log!(
+                    "Local LoRa: SF{} / {:.1} kHz / CR 4/{}",
+                    params.spreading_factor,
+                    params.bandwidth_hz / 1000.0,
+                    params.coding_rate
+                );
+            }
+            self.local_lora = Some(lora.clone());
+        }
+        self.observe_local_position(packet);
+    }
+
+    /// Tracks the local node's own position from its NodeInfo and POSITION_APP packets.
+    fn observe_local_position(&mut self, packet: &meshtastic::protobufs::FromRadio) {
+        use meshtastic::protobufs::{Position, from_radio, mesh_packet};
+
+        let Some(local) = self.local_node else {
+            return;
+        };
+        let position = match &packet.payload_variant {
+            Some(from_radio::PayloadVariant::NodeInfo(info)) if info.num == local.as_u32() => {
+                info.position.clone()
+            }
+            Some(from_radio::PayloadVariant::Packet(mesh_packet))
+                if mesh_packet.from == local.as_u32() =>
+            {
+                match &mesh_packet.payload_variant {
+                    Some(mesh_packet::PayloadVariant::Decoded(data))
+                        if data.portnum == PortNum::PositionApp as i32 =>
+                    {
+                        Position::decode(data.payload.as_slice()).ok()
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        if let Some(fix) = position.as_ref().and_then(GeoFix::from_position) {
+            self.node_fix = Some(fix);
         }
     }
 
-    fn format_node_from_u32(node_num: u32) -> String {
-        format!("!{:08x}", node_num)
+    /// Freshest known position: the node's own fix, else the NMEA GPS, else a stale node fix.
+    fn current_fix(&self) -> Option<GeoFix> {
+        let max_age = Duration::from_secs(NODE_FIX_MAX_AGE_SECS);
+        let node_fix = self.node_fix.filter(|fix| fix.age() <= max_age);
+        let nmea_fix = self.nmea_gps.as_ref().and_then(NmeaGps::latest);
+        node_fix.or(nmea_fix).or(self.node_fix)
     }
 
-    fn store_session_key(&mut self, node_num: u32, key: &[u8]) {
-        if key.is_empty() {
+    fn geo_columns(&self) -> GeoColumns {
+        match self.current_fix() {
+            Some(fix) => GeoColumns {
+                latitude: Some(fix.latitude),
+                longitude: Some(fix.longitude),
+                altitude_m: fix.altitude_m,
+                speed_mps: fix.speed_mps,
+            },
+            None => GeoColumns::default(),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn record_geo_sample(
+        &mut self,
+        cycle: u32,
+        phase_name: &str,
+        is_lna_on: bool,
+        kind: &'static str,
+        node: NodeId,
+        snr: Option<f32>,
+        rssi: Option<i32>,
+    ) {
+        let Some(fix) = self.current_fix() else {
             return;
+        };
+        self.geo_samples.push(GeoSample {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            cycle,
+            phase: phase_name.to_string(),
+            lna_on: is_lna_on,
+            kind: kind.to_string(),
+            node: node.to_string(),
+            delivered: true,
+            snr,
+            rssi,
+            fix,
+        });
+    }
+
+    /// Writes `<stem>.geojson` and `<stem>.kml` next to the main output when any sample had a fix.
+    fn export_geo_layers(&self) {
+        if self.geo_samples.is_empty() {
+            return;
+        }
+        let metric = self.config.geo_color_metric;
+        let geojson_path = self.sibling_output_path("geojson");
+        match crate::geo::write_geojson(&geojson_path, &self.geo_samples, metric) {
+            Ok(()) => msnr_log!("GeoJSON layer written to {}", geojson_path.display()),
+            Err(e) => msnr_log_err!("Error writing GeoJSON: {}", e),
+        }
+        let kml_path = self.sibling_output_path("kml");
+        match crate::geo::write_kml(&kml_path, &self.geo_samples, metric) {
+            Ok(()) => msnr_log!("KML layer written to {}", kml_path.display()),
+            Err(e) => msnr_log_err!("Error writing KML: {}", e),
+        }
+    }
+
+    fn observer_name(&self, observer: Option<usize>) -> &str {
+        observer
+            .and_then(|index| self.observers.get(index))
+            .map(|radio| radio.name.as_str())
+            .unwrap_or(PRIMARY_OBSERVER)
+    }
+
+    /// Records rx_snr/rx_rssi whenever one of our radios hears another of our radios.
+    fn record_link_observation(
+        &self,
+        observer: Option<usize>,
+        packet: &meshtastic::protobufs::FromRadio,
+        cycle: u32,
+        phase_name: &str,
+    ) {
+        use meshtastic::protobufs::{from_radio, mesh_packet};
+
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = &packet.payload_variant else {
+            return;
+        };
+        let from = NodeId::new(mesh_packet.from);
+        let heard_other_radio = match observer {
+            None => self
+                .observers
+                .iter()
+                .any(|radio| radio.node_id == Some(from)),
+            Some(index) => {
+                self.local_node == Some(from)
+                    || self
+                        .observers
+                        .iter()
+                        .enumerate()
+                        .any(|(i, radio)| i != index && radio.node_id == Some(from))
+            }
+        };
+        if !heard_other_radio {
+            return;
+        }
+
+        let portnum = match &mesh_packet.payload_variant {
+            Some(mesh_packet::PayloadVariant::Decoded(data)) => Some(data.portnum),
+            _ => None,
+        };
+        let geo = self.geo_columns();
+        let record = LinkObservationRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            cycle,
+            phase: phase_name.to_string(),
+            observer: self.observer_name(observer).to_string(),
+            from: from.to_string(),
+            portnum,
+            hops_away: mesh_packet.hop_start.saturating_sub(mesh_packet.hop_limit),
+            rx_snr: mesh_packet.rx_snr,
+            rx_rssi: mesh_packet.rx_rssi,
+            latitude: geo.latitude,
+            longitude: geo.longitude,
+            altitude_m: geo.altitude_m,
+            speed_mps: geo.speed_mps,
+        };
+        msnr_log!(
+            "[{}] heard {} | SNR {:.2} dB | RSSI {} dBm",
+            record.observer,
+            record.from,
+            record.rx_snr,
+            record.rx_rssi
+        );
+        if let Err(e) = Self::append_csv_row(&self.sibling_output_path("observations.csv"), &record)
+        {
+            msnr_log_err!("Error writing observation CSV: {}", e);
+        }
+    }
+
+    fn handle_observer_packet(
+        &mut self,
+        index: usize,
+        packet: meshtastic::protobufs::FromRadio,
+        cycle: u32,
+        phase_name: &str,
+        phase_index: u32,
+        is_lna_on: bool,
+    ) {
+        use meshtastic::protobufs::{RouteDiscovery, from_radio, mesh_packet};
+
+        if let Some(from_radio::PayloadVariant::MyInfo(info)) = &packet.payload_variant {
+            let node = NodeId::new(info.my_node_num);
+            msnr_log!("Observer '{}' is node {}", self.observers[index].name, node);
+            self.observers[index].node_id = Some(node);
+            return;
+        }
+
+        self.record_link_observation(Some(index), &packet, cycle, phase_name);
+
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = packet.payload_variant else {
+            return;
+        };
+        if self.local_node != Some(NodeId::new(mesh_packet.from)) {
+            return;
+        }
+        if let Some(mesh_packet::PayloadVariant::Decoded(data)) = mesh_packet.payload_variant {
+            if data.portnum != PortNum::TracerouteApp as i32 {
+                return;
+            }
+            match RouteDiscovery::decode(data.payload.as_slice()) {
+                Ok(route_discovery) => {
+                    let Some(timing) = self.match_traceroute_reply(
+                        Some(index),
+                        data.request_id,
+                        phase_index,
+                        is_lna_on,
+                    ) else {
+                        return;
+                    };
+                    let snr_towards: Vec<f32> = route_discovery
+                        .snr_towards
+                        .iter()
+                        .map(|&x| x as f32 / 4.0)
+                        .collect();
+                    let snr_back: Vec<f32> = route_discovery
+                        .snr_back
+                        .iter()
+                        .map(|&x| x as f32 / 4.0)
+                        .collect();
+                    let observer = self.observers[index].name.clone();
+                    msnr_log!(
+                        "[{}] TRACEROUTE RESPONSE ({}) | SNR Towards: {:?} | SNR Back: {:?}",
+                        observer,
+                        phase_name,
+                        snr_towards,
+                        snr_back
+                    );
+                    let hit_floor = snr_towards
+                        .iter()
+                        .chain(snr_back.iter())
+                        .any(|value| (*value + 32.0).abs() < f32::EPSILON);
+                    if hit_floor {
+                        msnr_warn!(
+                            "[{}] Skipping traceroute sample (SNR hit -32 dB floor).",
+                            observer
+                        );
+                        self.emit(EngineEvent::SampleRejected {
+                            request_id: data.request_id,
+                            reason: RejectReason::SnrFloor,
+                        });
+                        return;
+                    }
+
+                    // The request ends at the primary node and the reply at the observer
+                    let stats = self
+                        .phase_stats_mut(is_lna_on)
+                        .observers
+                        .entry(observer.clone())
+                        .or_default();
+                    if let Some(snr) = snr_towards.last() {
+                        stats.snr_at_local.add_sample(*snr);
+                    }
+                    if let Some(snr) = snr_back.last() {
+                        stats.snr_at_observer.add_sample(*snr);
+                    }
+                    stats.rtt.add_sample(timing.rtt);
+                    if let Some(origin) = self.observers[index].node_id {
+                        self.record_route_links(
+                            is_lna_on,
+                            origin,
+                            NodeId::new(mesh_packet.from),
+                            &route_discovery,
+                            &snr_towards,
+                            &snr_back,
+                        );
+                    }
+                    self.emit(EngineEvent::SampleAccepted {
+                        observer: observer.clone(),
+                        phase: phase_name.to_string(),
+                        request_id: data.request_id,
+                        rtt_ms: timing.rtt.as_millis() as u64,
+                        snr_towards: snr_towards.clone(),
+                        snr_back: snr_back.clone(),
+                    });
+
+                    let record = TracerouteRecord {
+                        timestamp: chrono::Local::now().to_rfc3339(),
+                        cycle,
+                        phase: phase_name.to_string(),
+                        observer,
+                        request_id: data.request_id,
+                        sent_at: timing.sent_at.to_rfc3339(),
+                        rtt_ms: timing.rtt.as_millis() as u64,
+                        hops_towards: route_discovery.route.len(),
+                        hops_back: route_discovery.route_back.len(),
+                        route: format!("{:?}", route_discovery.route),
+                        route_back: format!("{:?}", route_discovery.route_back),
+                        snr_towards,
+                        snr_back,
+                        // Observer paths differ from the configured relay path
+                        link_snr: Vec::new(),
+                        geo: self.geo_columns(),
+                    };
+                    if let Err(e) = self.append_csv_record(&record) {
+                        msnr_log_err!("Error writing CSV: {}", e);
+                    }
+                }
+                Err(e) => msnr_warn!("Failed to decode RouteDiscovery: {}", e),
+            }
         }
-        let normalized = Self::format_node_from_u32(node_num);
-        if !self.session_keys.contains_key(&normalized) {
-            msnr_log!("Stored session key for node {}", normalized);
+    }
+
+    fn phase_stats_mut(&mut self, is_lna_on: bool) -> &mut PhaseStats {
+        self.checkpoint_due = true;
+        if is_lna_on {
+            &mut self.stats_lna_on
+        } else {
+            &mut self.stats_lna_off
+        }
+    }
+
+    fn track_traceroute(
+        &mut self,
+        observer: Option<usize>,
+        request: TracerouteRequest,
+        phase_index: u32,
+        is_lna_on: bool,
+    ) {
+        let fix = self.current_fix();
+        self.pending_traceroutes.insert(
+            request.packet_id,
+            PendingTraceroute {
+                observer,
+                target: request.target,
+                phase_index,
+                is_lna_on,
+                sent_at: Instant::now(),
+                sent_wallclock: chrono::Local::now(),
+                fix,
+            },
+        );
+        self.phase_stats_mut(is_lna_on).traceroutes_sent += 1;
+    }
+
+    /// Drops requests sent before the phase preceding `phase_index`; they are never answered.
+    fn expire_pending_traceroutes(&mut self, phase_index: u32) {
+        let expired: Vec<u32> = self
+            .pending_traceroutes
+            .iter()
+            .filter(|(_, pending)| pending.phase_index.saturating_add(1) < phase_index)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            if let Some(pending) = self.pending_traceroutes.remove(&id) {
+                self.record_undelivered_traceroute(&pending);
+                if self.expired_request_ids.len() == EXPIRED_REQUEST_IDS_KEPT {
+                    self.expired_request_ids.pop_front();
+                }
+                self.expired_request_ids.push_back(id);
+            }
         }
-        self.session_keys.insert(normalized, key.to_vec());
     }
 
-    fn apply_session_key(&self, node_id: &str, msg: &mut AdminMessage) {
-        if let Some(normalized) = Self::normalized_node_id(node_id) {
-            if let Some(key) = self.session_keys.get(&normalized) {
-                msg.session_passkey = key.clone();
+    /// Adds a failed-delivery point to the coverage samples for a primary request.
+    fn record_undelivered_traceroute(&mut self, pending: &PendingTraceroute) {
+        if self.reboot.is_none() {
+            let lookback = Duration::from_secs(REBOOT_LOOKBACK_SECS);
+            self.lost_traceroutes
+                .retain(|lost| lost.sent_at.elapsed() < lookback);
+        }
+        self.lost_traceroutes.push(pending.clone());
+        let (None, Some(fix)) = (pending.observer, pending.fix) else {
+            return;
+        };
+        self.geo_samples.push(GeoSample {
+            timestamp: pending.sent_wallclock.to_rfc3339(),
+            cycle: pending.phase_index / 2,
+            phase: if pending.is_lna_on {
+                "LNA ON"
+            } else {
+                "LNA OFF"
+            }
+            .to_string(),
+            lna_on: pending.is_lna_on,
+            kind: "traceroute".to_string(),
+            node: pending.target.to_string(),
+            delivered: false,
+            snr: None,
+            rssi: None,
+            fix,
+        });
+    }
+
+    /// Matches a traceroute reply to the request we sent and returns its round-trip time.
+    /// Replies to someone else's request, or arriving after their phase ended, are dropped.
+    fn match_traceroute_reply(
+        &mut self,
+        observer: Option<usize>,
+        request_id: u32,
+        phase_index: u32,
+        is_lna_on: bool,
+    ) -> Option<TracerouteTiming> {
+        if request_id == 0 {
+            // A traceroute request addressed to us, not a reply
+            return None;
+        }
+
+        match self.pending_traceroutes.remove(&request_id) {
+            Some(pending) if pending.observer == observer => {
+                if pending.phase_index != phase_index {
+                    self.record_undelivered_traceroute(&pending);
+                    self.late_replies += 1;
+                    msnr_warn!(
+                        "Discarding late traceroute reply {:08x} sent in an earlier phase ({} ms).",
+                        request_id,
+                        pending.sent_at.elapsed().as_millis()
+                    );
+                    self.emit(EngineEvent::SampleRejected {
+                        request_id,
+                        reason: RejectReason::LateReply,
+                    });
+                    return None;
+                }
+                let rtt = pending.sent_at.elapsed();
+                self.phase_stats_mut(is_lna_on).replies_matched += 1;
+                Some(TracerouteTiming {
+                    sent_at: pending.sent_wallclock,
+                    rtt,
+                })
+            }
+            Some(pending) => {
+                // Belongs to another radio's request; keep it pending for that radio.
+                self.pending_traceroutes.insert(request_id, pending);
+                None
+            }
+            None if self.expired_request_ids.contains(&request_id) => {
+                self.expired_request_ids.retain(|id| *id != request_id);
+                self.late_replies += 1;
+                msnr_warn!(
+                    "Discarding late traceroute reply {:08x} to a request that already expired.",
+                    request_id
+                );
+                self.emit(EngineEvent::SampleRejected {
+                    request_id,
+                    reason: RejectReason::LateReply,
+                });
+                None
+            }
+            None => {
+                self.unmatched_replies += 1;
+                msnr_warn!(
+                    "Ignoring traceroute reply {:08x} that matches no request we sent.",
+                    request_id
+                );
+                None
             }
         }
     }
@@ -1020,15 +4085,27 @@ impl Engine {
         }
     }
 
-    fn has_session_key(&self, node_id: &str) -> bool {
-        Self::normalized_node_id(node_id)
-            .map(|id| self.session_keys.contains_key(&id))
-            .unwrap_or(false)
+    fn has_session_key(&self, node_id: NodeId) -> bool {
+        self.session_keys.contains_key(&node_id)
+    }
+
+    /// Asks `node` for a session key unless we already hold one.
+    async fn ensure_session_key(&mut self, node: NodeId) -> Result<()> {
+        if self.has_session_key(node) {
+            return Ok(());
+        }
+        let session_req = AdminMessage {
+            payload_variant: Some(admin_message::PayloadVariant::GetConfigRequest(
+                admin_message::ConfigType::SessionkeyConfig as i32,
+            )),
+            ..Default::default()
+        };
+        self.send_admin_with_session(node, &session_req).await
     }
 
     async fn send_admin_with_session(
         &mut self,
-        target: &str,
+        target: NodeId,
         template: &AdminMessage,
     ) -> Result<()> {
         let mut msg = template.clone();
@@ -1036,3 +4113,74 @@ impl Engine {
         self.transport.send_admin(target, msg).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOF: NodeId = NodeId::new(0x0000_aaaa);
+    const RELAY: NodeId = NodeId::new(0x0000_bbbb);
+    const OTHER: NodeId = NodeId::new(0x0000_cccc);
+
+    fn route(hops: &[NodeId]) -> Vec<u32> {
+        hops.iter().map(|hop| hop.as_u32()).collect()
+    }
+
+    #[test]
+    fn validate_path_accepts_an_exact_match() {
+        let expected = [PathHop::Node(ROOF), PathHop::Node(RELAY)];
+        assert_eq!(
+            Engine::validate_path(&route(&[ROOF, RELAY]), &expected),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_path_accepts_any_node_for_a_wildcard() {
+        let expected = [PathHop::Node(ROOF), PathHop::Any];
+        assert_eq!(
+            Engine::validate_path(&route(&[ROOF, RELAY]), &expected),
+            Ok(())
+        );
+        assert_eq!(
+            Engine::validate_path(&route(&[ROOF, OTHER]), &expected),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn validate_path_rejects_a_wrong_length() {
+        let expected = [PathHop::Node(ROOF), PathHop::Any];
+        let error = Engine::validate_path(&route(&[ROOF]), &expected).unwrap_err();
+        assert!(error.contains("expected 2-hop route"), "{error}");
+        assert!(Engine::validate_path(&route(&[ROOF, RELAY, OTHER]), &expected).is_err());
+        assert_eq!(
+            Engine::validate_path(&[], &expected),
+            Err("route metadata is empty".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_path_rejects_a_mismatched_hop() {
+        let expected = [PathHop::Node(ROOF), PathHop::Node(RELAY)];
+        assert_eq!(
+            Engine::validate_path(&route(&[ROOF, OTHER]), &expected),
+            Err("hop 2 is !0000cccc but expected !0000bbbb".to_string())
+        );
+    }
+
+    #[test]
+    fn validate_path_checks_the_return_route_against_the_reversed_forward_path() {
+        let config = Config {
+            roof_node_id: Some(ROOF),
+            expected_route: Some(vec![PathHop::Node(ROOF), PathHop::Node(RELAY)]),
+            ..Config::default()
+        };
+        let return_path = config.return_path();
+        assert_eq!(
+            Engine::validate_path(&route(&[RELAY, ROOF]), &return_path),
+            Ok(())
+        );
+        assert!(Engine::validate_path(&route(&[ROOF, RELAY]), &return_path).is_err());
+    }
+}
@@ -0,0 +1,479 @@
+//! Pre-flight checks run before committing to a long test; nothing remote is changed.
+
+use super::{Engine, PRIMARY_OBSERVER, RANGE_TEST_PACKET_BYTES};
+use crate::airtime::LoraParams;
+use crate::config::{MeasurementMode, Topology};
+use crate::node_id::NodeId;
+use crate::{msnr_log, msnr_warn};
+use anyhow::Result;
+use meshtastic::protobufs::{
+    AdminMessage, Data, PortNum, RouteDiscovery, User, admin_message, from_radio, mesh_packet,
+};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Multi-hop traceroutes can take a while to come back.
+const TRACEROUTE_TIMEOUT_SECS: u64 = 60;
+/// Reading the remote config is retried a few times; a run retries much longer.
+const LORA_READ_ATTEMPTS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    /// `true` when no check failed; warnings do not block a run.
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Fail)
+    }
+
+    fn push(&mut self, name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) {
+        let check = PreflightCheck {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        };
+        msnr_log!("[{}] {}: {}", check.status, check.name, check.detail);
+        self.checks.push(check);
+    }
+
+    fn pass(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Pass, detail);
+    }
+
+    fn warn(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Warn, detail);
+    }
+
+    fn fail(&mut self, name: impl Into<String>, detail: impl Into<String>) {
+        self.push(name, CheckStatus::Fail, detail);
+    }
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+impl Engine {
+    /// Connects and checks everything a run depends on — local identity, reachability of
+    /// each configured node, session key, remote LoRa config, route shape and airtime —
+    /// without sending any `SetConfig`.
+    ///
+    /// Only connection-level errors are returned as `Err`; everything else lands in the report.
+    pub async fn preflight(&mut self) -> Result<PreflightReport> {
+        let context = self.log_context();
+        context.scope(self.run_preflight_checks()).await
+    }
+
+    async fn run_preflight_checks(&mut self) -> Result<PreflightReport> {
+        let mut report = PreflightReport::default();
+
+        if let Err(e) = self.config.validate() {
+            report.fail("Config", e.to_string());
+            return Ok(report);
+        }
+        report.pass("Config", "valid");
+
+        let mut rx = match self.transport.connect().await {
+            Ok(rx) => rx,
+            Err(e) => {
+                report.fail("Connect", e.to_string());
+                return Ok(report);
+            }
+        };
+        report.pass(
+            "Connect",
+            format!("{:?} transport", self.config.transport_mode),
+        );
+        self.inject_identity().await;
+        self.await_local_lora(&mut rx).await;
+        self.resolve_timings();
+        let timings = self.timings;
+        let detail = format!(
+            "{} attempt(s), config wait {} ms, ack {} ms, settle {} ms",
+            timings.lna_max_attempts,
+            timings.lna_wait_timeout_ms,
+            timings.lna_ack_timeout_ms,
+            timings.settle_ms
+        );
+        if self.local_lora.is_some() {
+            report.pass("Timings", detail);
+        } else {
+            report.warn("Timings", format!("{} (local preset unknown)", detail));
+        }
+
+        // Still disconnect below: the Tauri app goes on to a real run over the same radio
+        if let Err(e) = self.check_local_identity(&mut rx, &mut report).await {
+            report.fail("Local identity", e.to_string());
+        }
+
+        let nodes = match self.config.topology {
+            Topology::Relay => vec![
+                ("Roof", self.config.roof_node_id),
+                ("Mountain", self.config.mountain_node_id),
+            ],
+            Topology::Direct => vec![("Target", self.config.target_node_id)],
+        };
+        for (role, node) in nodes {
+            let Some(node) = node else { continue };
+            self.check_reachability(&mut rx, &mut report, role, node)
+                .await;
+        }
+
+        self.check_remote_lora(&mut rx, &mut report).await;
+        self.check_airtime(&mut report);
+
+        if let Err(e) = self.transport.disconnect().await {
+            msnr_warn!("Warning: Failed to disconnect cleanly: {e}");
+        }
+        msnr_log!(
+            "Pre-flight {}",
+            if report.passed() { "PASSED" } else { "FAILED" }
+        );
+        Ok(report)
+    }
+
+    async fn check_local_identity(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        report: &mut PreflightReport,
+    ) -> Result<()> {
+        let owner_req = AdminMessage {
+            payload_variant: Some(admin_message::PayloadVariant::GetOwnerRequest(true)),
+            ..Default::default()
+        };
+        self.send_admin_with_session(NodeId::LOCAL, &owner_req)
+            .await?;
+
+        let mut owner: Option<User> = None;
+        let owner_timeout = self.timings.owner_info_timeout();
+        let deadline = Instant::now() + owner_timeout;
+        while owner.is_none() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let Ok(Some(packet)) = tokio::time::timeout(remaining, rx.recv()).await else {
+                break;
+            };
+            self.observe_primary_packet(&packet);
+            if let Some(admin) = Self::decode_admin(&packet) {
+                if let Some(admin_message::PayloadVariant::GetOwnerResponse(user)) =
+                    admin.payload_variant
+                {
+                    owner = Some(user);
+                }
+            }
+        }
+
+        match (owner, self.local_node) {
+            (Some(user), _) => report.pass(
+                "Local identity",
+                format!(
+                    "{} ({} / {}); it must be in the controlled node's admin list",
+                    user.id, user.long_name, user.short_name
+                ),
+            ),
+            (None, Some(node)) => report.warn(
+                "Local identity",
+                format!(
+                    "node {} but no owner info within {} ms",
+                    node,
+                    owner_timeout.as_millis()
+                ),
+            ),
+            (None, None) => report.fail(
+                "Local identity",
+                format!("no node info within {} ms", owner_timeout.as_millis()),
+            ),
+        }
+        Ok(())
+    }
+
+    /// Traceroutes `node` and, for the measured far end, checks the route against the topology.
+    async fn check_reachability(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        report: &mut PreflightReport,
+        role: &str,
+        node: NodeId,
+    ) {
+        let name = format!("{} {} reachable", role, node);
+        let (route, rtt) = match self.preflight_traceroute(rx, node).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                report.fail(name, e.to_string());
+                return;
+            }
+        };
+        let snr_back = route.snr_back.last().map(|&x| x as f32 / 4.0);
+        report.pass(
+            name,
+            format!(
+                "RTT {} ms, route {} / back {}, SNR into {} {}",
+                rtt.as_millis(),
+                Self::format_route(&route.route),
+                Self::format_route(&route.route_back),
+                PRIMARY_OBSERVER,
+                snr_back
+                    .map(|snr| format!("{:.2} dB", snr))
+                    .unwrap_or_else(|| "--".into())
+            ),
+        );
+
+        let measured = match self.config.topology {
+            Topology::Relay => self.config.mountain_node_id,
+            Topology::Direct => self.config.target_node_id,
+        };
+        if measured != Some(node) {
+            return;
+        }
+        match self.config.topology {
+            Topology::Relay => {
+                let checks = [
+                    ("Forward route", &route.route, self.config.forward_path()),
+                    ("Return route", &route.route_back, self.config.return_path()),
+                ];
+                for (label, hops, expected) in checks {
+                    match Self::validate_path(hops, &expected) {
+                        Ok(()) => report.pass(label, Self::format_path(&expected)),
+                        Err(reason) => report.fail(label, reason),
+                    }
+                }
+            }
+            Topology::Direct => {
+                if route.route.is_empty() && route.route_back.is_empty() {
+                    report.pass("Route", "single hop");
+                } else {
+                    report.fail(
+                        "Route",
+                        format!(
+                            "Direct topology expects a single hop, got {} / back {}",
+                            Self::format_route(&route.route),
+                            Self::format_route(&route.route_back)
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Reads the controlled node's LoRa config, which also proves the session key and admin access.
+    async fn check_remote_lora(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        report: &mut PreflightReport,
+    ) {
+        let Some(node) = self.resolve_lna_control_node() else {
+            report.warn(
+                "LNA control",
+                "disabled or no node configured; LNA will not be toggled",
+            );
+            return;
+        };
+
+        if let Err(e) = self.ensure_session_key(node).await {
+            report.fail(format!("Session key {}", node), e.to_string());
+            return;
+        }
+        let get_req = AdminMessage {
+            payload_variant: Some(admin_message::PayloadVariant::GetConfigRequest(
+                admin_message::ConfigType::LoraConfig as i32,
+            )),
+            ..Default::default()
+        };
+        let lora = match self
+            .fetch_lora_config_with_retry(rx, node, &get_req, LORA_READ_ATTEMPTS)
+            .await
+        {
+            Ok(lora) => lora,
+            Err(e) => {
+                report.fail(
+                    format!("LoRa config {}", node),
+                    format!("{} (is the local ID in its admin list?)", e),
+                );
+                return;
+            }
+        };
+
+        if self.has_session_key(node) {
+            report.pass(format!("Session key {}", node), "received");
+        } else {
+            report.warn(
+                format!("Session key {}", node),
+                "config readable but no session key seen; SetConfig may be rejected",
+            );
+        }
+
+        let remote = LoraParams::from_lora_config(&lora);
+        let local = self.local_lora_params();
+        let detail = format!(
+            "RX boosted gain {}, {}",
+            lora.sx126x_rx_boosted_gain,
+            remote
+                .map(|params| format!(
+                    "SF{} / {:.1} kHz / CR 4/{}",
+                    params.spreading_factor,
+                    params.bandwidth_hz / 1000.0,
+                    params.coding_rate
+                ))
+                .unwrap_or_else(|| "modem settings unknown".into())
+        );
+        match (remote, local) {
+            (Some(remote), Some(local)) if remote != local => report.fail(
+                format!("LoRa config {}", node),
+                format!("{}; differs from the local radio", detail),
+            ),
+            _ => report.pass(format!("LoRa config {}", node), detail),
+        }
+    }
+
+    /// Duty-cycle use of whatever this measurement mode transmits.
+    fn check_airtime(&self, report: &mut PreflightReport) {
+        match self.config.measurement_mode {
+            MeasurementMode::Traceroute => self.check_traceroute_airtime(report),
+            MeasurementMode::RangeTest => self.check_range_test_airtime(report),
+            MeasurementMode::Passive => report.pass("Airtime", "passive mode; no transmissions"),
+        }
+    }
+
+    fn check_traceroute_airtime(&self, report: &mut PreflightReport) {
+        let (Some(tx_airtime), Some(channel_airtime)) = (
+            self.traceroute_tx_airtime(),
+            self.traceroute_channel_airtime(),
+        ) else {
+            report.warn("Airtime", "local LoRa config not received; cannot estimate");
+            return;
+        };
+        match self.plan_traceroute_interval() {
+            Ok(0) => report.fail(
+                "Airtime",
+                format!(
+                    "interval {} ms is under 1 s; no traceroutes would be sent",
+                    self.config.interval_ms
+                ),
+            ),
+            Ok(interval_secs) => {
+                let per_phase = channel_airtime.as_secs_f64()
+                    * (self.phase_duration_ms() / 1000 / interval_secs) as f64;
+                let detail = format!(
+                    "{:.0} ms TX per traceroute every {} s ({:.2}% duty), ~{:.1} s channel airtime per phase",
+                    tx_airtime.as_secs_f64() * 1000.0,
+                    interval_secs,
+                    tx_airtime.as_secs_f64() / interval_secs as f64 * 100.0,
+                    per_phase
+                );
+                if interval_secs * 1000 > self.config.interval_ms {
+                    report.warn(
+                        "Airtime",
+                        format!("{}; interval stretched for the duty-cycle limit", detail),
+                    );
+                } else {
+                    report.pass("Airtime", detail);
+                }
+            }
+            Err(e) => report.fail("Airtime", e.to_string()),
+        }
+    }
+
+    /// The remote node is the one transmitting; it is assumed to share the local modem preset.
+    fn check_range_test_airtime(&self, report: &mut PreflightReport) {
+        let Some(airtime) = self
+            .local_lora_params()
+            .map(|params| params.packet_airtime(RANGE_TEST_PACKET_BYTES))
+        else {
+            report.warn("Airtime", "local LoRa config not received; cannot estimate");
+            return;
+        };
+        match self.plan_range_test_sender() {
+            Ok(interval_secs) => {
+                let detail = format!(
+                    "{:.0} ms TX per range test packet from {} every {} s ({:.2}% duty)",
+                    airtime.as_secs_f64() * 1000.0,
+                    Self::format_node_id(self.measured_node().map(NodeId::as_u32)),
+                    interval_secs,
+                    airtime.as_secs_f64() / interval_secs as f64 * 100.0
+                );
+                if interval_secs as u64 > (self.config.interval_ms / 1000).max(1) {
+                    report.warn(
+                        "Airtime",
+                        format!("{}; interval stretched for the duty-cycle limit", detail),
+                    );
+                } else {
+                    report.pass("Airtime", detail);
+                }
+            }
+            Err(e) => report.fail("Airtime", e.to_string()),
+        }
+    }
+
+    async fn preflight_traceroute(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+        target: NodeId,
+    ) -> Result<(RouteDiscovery, Duration)> {
+        let request = self.transport.run_traceroute(target).await?;
+        let sent_at = Instant::now();
+        let deadline = sent_at + Duration::from_secs(TRACEROUTE_TIMEOUT_SECS);
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let packet = match tokio::time::timeout(remaining, rx.recv()).await {
+                Ok(Some(packet)) => packet,
+                Ok(None) => anyhow::bail!("transport channel closed"),
+                Err(_) => anyhow::bail!("no traceroute reply within {} s", TRACEROUTE_TIMEOUT_SECS),
+            };
+            self.observe_primary_packet(&packet);
+            if let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = packet.payload_variant {
+                if let Some(mesh_packet::PayloadVariant::Decoded(Data {
+                    portnum,
+                    payload,
+                    request_id,
+                    ..
+                })) = mesh_packet.payload_variant
+                {
+                    if portnum == PortNum::TracerouteApp as i32 && request_id == request.packet_id {
+                        return Ok((
+                            RouteDiscovery::decode(payload.as_slice())?,
+                            sent_at.elapsed(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    fn decode_admin(packet: &meshtastic::protobufs::FromRadio) -> Option<AdminMessage> {
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = &packet.payload_variant else {
+            return None;
+        };
+        let Some(mesh_packet::PayloadVariant::Decoded(data)) = &mesh_packet.payload_variant else {
+            return None;
+        };
+        if data.portnum != PortNum::AdminApp as i32 {
+            return None;
+        }
+        AdminMessage::decode(data.payload.as_slice()).ok()
+    }
+}
@@ -0,0 +1,268 @@
+//! Reboot handling for nodes we reconfigure. Firmware may restart after a LoRa or module
+//! SetConfig; while it is down every traceroute is lost and the old session key stops
+//! working, so the outage is paused out of the measurement instead of being counted.
+
+use super::{Engine, PendingTraceroute};
+use crate::events::EngineEvent;
+use crate::node_id::NodeId;
+use crate::{msnr_log, msnr_log_err, msnr_warn};
+use anyhow::Result;
+use meshtastic::protobufs::{
+    PortNum, Routing, Telemetry, from_radio, mesh_packet, routing, telemetry,
+};
+use prost::Message;
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// What made us conclude the node restarted.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum RebootSignal {
+    /// Announced itself with NodeInfo shortly after we changed its config.
+    NodeInfo,
+    /// DeviceMetrics/LocalStats uptime went backwards.
+    UptimeReset { before: u32, after: u32 },
+    /// Rejected an admin message with ADMIN_BAD_SESSION_KEY.
+    SessionKeyLost,
+}
+
+impl fmt::Display for RebootSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RebootSignal::NodeInfo => write!(f, "NodeInfo after config change"),
+            RebootSignal::UptimeReset { before, after } => {
+                write!(f, "uptime reset from {} s to {} s", before, after)
+            }
+            RebootSignal::SessionKeyLost => write!(f, "session key rejected"),
+        }
+    }
+}
+
+/// An outage that is still being waited out.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct RebootWindow {
+    node: NodeId,
+    signal: RebootSignal,
+    /// Best estimate of when the node went down, which can precede detection.
+    started: Instant,
+    detected_at: chrono::DateTime<chrono::Local>,
+}
+
+#[derive(Debug, Serialize)]
+struct RebootRecord {
+    detected_at: String,
+    recovered_at: String,
+    node: String,
+    signal: String,
+    downtime_ms: u64,
+}
+
+impl Engine {
+    /// Nodes whose restarts we track: the LNA-controlled node and whichever node we last
+    /// sent a config change to.
+    fn watches_reboots_of(&self, node: NodeId) -> bool {
+        Some(node) == self.resolve_lna_control_node()
+            || self.config_sent.map(|(sent_to, _)| sent_to) == Some(node)
+    }
+
+    /// Remembers a config change so a NodeInfo announcement soon after reads as a reboot.
+    pub(super) fn note_config_sent(&mut self, node: NodeId) {
+        self.config_sent = Some((node, Instant::now()));
+    }
+
+    /// Looks for signs of a restart in every packet the primary radio delivers.
+    pub(super) fn observe_reboot_signals(&mut self, packet: &meshtastic::protobufs::FromRadio) {
+        let Some(from_radio::PayloadVariant::Packet(mesh_packet)) = &packet.payload_variant else {
+            return;
+        };
+        let Some(mesh_packet::PayloadVariant::Decoded(data)) = &mesh_packet.payload_variant else {
+            return;
+        };
+        let node = NodeId::new(mesh_packet.from);
+        if !self.watches_reboots_of(node) {
+            return;
+        }
+
+        if data.portnum == PortNum::NodeinfoApp as i32 {
+            // Nodes announce themselves on boot; only trust that right after a config change
+            let nodeinfo_window = self.timings.lna_wait_timeout() * 2;
+            if let Some((sent_to, sent_at)) = self.config_sent {
+                if sent_to == node && sent_at.elapsed() < nodeinfo_window {
+                    self.begin_reboot_window(node, RebootSignal::NodeInfo, sent_at);
+                }
+            }
+        } else if data.portnum == PortNum::TelemetryApp as i32 {
+            let uptime = match Telemetry::decode(data.payload.as_slice()).map(|t| t.variant) {
+                Ok(Some(telemetry::Variant::DeviceMetrics(metrics))) => metrics.uptime_seconds,
+                Ok(Some(telemetry::Variant::LocalStats(stats))) => Some(stats.uptime_seconds),
+                _ => None,
+            };
+            let Some(uptime) = uptime else {
+                return;
+            };
+            if let Some(before) = self.node_uptime.insert(node, uptime) {
+                if uptime < before {
+                    let started = Instant::now()
+                        .checked_sub(Duration::from_secs(uptime as u64))
+                        .unwrap_or_else(Instant::now);
+                    self.begin_reboot_window(
+                        node,
+                        RebootSignal::UptimeReset {
+                            before,
+                            after: uptime,
+                        },
+                        started,
+                    );
+                }
+            }
+        } else if data.portnum == PortNum::RoutingApp as i32 {
+            let Ok(routing) = Routing::decode(data.payload.as_slice()) else {
+                return;
+            };
+            if let Some(routing::Variant::ErrorReason(reason)) = routing.variant {
+                if reason == routing::Error::AdminBadSessionKey as i32 {
+                    let started = self
+                        .config_sent
+                        .filter(|(sent_to, _)| *sent_to == node)
+                        .map_or_else(Instant::now, |(_, sent_at)| sent_at);
+                    self.begin_reboot_window(node, RebootSignal::SessionKeyLost, started);
+                }
+            }
+        }
+    }
+
+    fn begin_reboot_window(&mut self, node: NodeId, signal: RebootSignal, started: Instant) {
+        if self.reboot.is_some() {
+            return;
+        }
+        let message = format!(
+            "Node {} appears to have rebooted ({}); pausing measurement until it is back.",
+            node, signal
+        );
+        msnr_warn!("⚠️ {}", message);
+        self.emit(EngineEvent::Warning { message });
+        // A restarted node hands out a new session key and a new uptime baseline
+        self.session_keys.remove(&node);
+        self.node_uptime.remove(&node);
+        self.config_sent = None;
+        self.reboot = Some(RebootWindow {
+            node,
+            signal,
+            started,
+            detected_at: chrono::Local::now(),
+        });
+    }
+
+    /// Waits for a rebooted node to answer with a fresh session key, then lets it settle.
+    ///
+    /// Returns how long the node was unusable, measured from the estimated reboot; traceroutes
+    /// sent in that time are dropped without counting as losses. Does nothing if no reboot
+    /// is pending.
+    pub(super) async fn await_reboot_recovery(
+        &mut self,
+        rx: &mut meshtastic::packet::PacketReceiver,
+    ) -> Result<Duration> {
+        let Some(window) = self.reboot else {
+            return Ok(Duration::ZERO);
+        };
+
+        let attempts = self.timings.lna_max_attempts;
+        let mut recovered = false;
+        for attempt in 1..=attempts {
+            msnr_log!(
+                "Waiting for node {} to come back (attempt {}/{})...",
+                window.node,
+                attempt,
+                attempts
+            );
+            self.ensure_session_key(window.node).await?;
+            let deadline = Instant::now() + self.timings.lna_wait_timeout();
+            while !self.has_session_key(window.node) {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match tokio::time::timeout(remaining, rx.recv()).await {
+                    Ok(Some(packet)) => self.observe_primary_packet(&packet),
+                    Ok(None) => anyhow::bail!(
+                        "Transport channel closed while waiting for node {} to come back",
+                        window.node
+                    ),
+                    Err(_) => break,
+                }
+            }
+            if self.has_session_key(window.node) {
+                recovered = true;
+                break;
+            }
+        }
+        if !recovered {
+            anyhow::bail!(
+                "Node {} did not come back after rebooting ({}) within {} attempts",
+                window.node,
+                window.signal,
+                attempts
+            );
+        }
+
+        tokio::time::sleep(self.timings.settle()).await;
+        self.reboot = None;
+        let downtime = window.started.elapsed();
+        let discarded = self.discard_traceroutes_since(window.started);
+        msnr_log!(
+            "✅ Node {} is back; excluding {:.0} s and {} traceroute(s) from the measurement.",
+            window.node,
+            downtime.as_secs_f32(),
+            discarded
+        );
+
+        let record = RebootRecord {
+            detected_at: window.detected_at.to_rfc3339(),
+            recovered_at: chrono::Local::now().to_rfc3339(),
+            node: window.node.to_string(),
+            signal: window.signal.to_string(),
+            downtime_ms: downtime.as_millis() as u64,
+        };
+        if let Err(e) = Self::append_csv_row(&self.sibling_output_path("reboots.csv"), &record) {
+            msnr_log_err!("Error writing reboot CSV: {}", e);
+        }
+        Ok(downtime)
+    }
+
+    /// Forgets traceroutes sent since `since`, whether still pending or already counted as
+    /// lost; they went out while the node was down.
+    fn discard_traceroutes_since(&mut self, since: Instant) -> u32 {
+        let pending_ids: Vec<u32> = self
+            .pending_traceroutes
+            .iter()
+            .filter(|(_, pending)| pending.sent_at >= since)
+            .map(|(id, _)| *id)
+            .collect();
+        let mut discarded: Vec<PendingTraceroute> = pending_ids
+            .iter()
+            .filter_map(|id| self.pending_traceroutes.remove(id))
+            .collect();
+
+        // Older losses predate this outage, and a later one cannot start before it ended
+        for lost in std::mem::take(&mut self.lost_traceroutes) {
+            if lost.sent_at < since {
+                continue;
+            }
+            if lost.observer.is_none() && lost.fix.is_some() {
+                let timestamp = lost.sent_wallclock.to_rfc3339();
+                let node = lost.target.to_string();
+                self.geo_samples.retain(|sample| {
+                    sample.delivered
+                        || sample.kind != "traceroute"
+                        || sample.timestamp != timestamp
+                        || sample.node != node
+                });
+            }
+            discarded.push(lost);
+        }
+
+        for pending in &discarded {
+            let stats = self.phase_stats_mut(pending.is_lna_on);
+            stats.traceroutes_sent = stats.traceroutes_sent.saturating_sub(1);
+        }
+        self.checkpoint_due |= !discarded.is_empty();
+        discarded.len() as u32
+    }
+}
@@ -0,0 +1,320 @@
+//! Typed events the engine emits while running.
+//!
+//! Every consumer (CLI, GUI, integrations) subscribes to the same `EngineEvent` stream;
+//! `ProgressState` is only a view folded from it by `ProgressTracker`.
+
+use crate::engine::{AverageStats, PRIMARY_OBSERVER, ProgressState};
+use crate::node_id::NodeId;
+use serde::{Deserialize, Serialize};
+
+/// Why a traceroute reply was not counted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum RejectReason {
+    /// At least one link reported the -32 dB floor, which means "not measured".
+    SnrFloor,
+    /// The reply took a different path than the configured relay route.
+    RouteMismatch { direction: String, detail: String },
+    /// The reply arrived after the phase it was sent in had ended.
+    LateReply,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum EngineEvent {
+    PhaseStarted {
+        /// 0-based.
+        cycle: u32,
+        total_cycles: u32,
+        phase: String,
+        /// 1 for LNA OFF, 2 for LNA ON.
+        phase_num: u8,
+        eta_seconds: u64,
+    },
+    /// Once per second while a phase is measuring.
+    PhaseProgress {
+        cycle: u32,
+        phase: String,
+        step: u64,
+        total_steps: u64,
+        /// Progress through the current phase, 0..=1.
+        phase_progress: f32,
+        /// Progress through the whole run, 0..=1.
+        total_progress: f32,
+        eta_seconds: u64,
+    },
+    LnaSetRequested {
+        node: NodeId,
+        enable: bool,
+        attempt: u32,
+        max_attempts: u32,
+    },
+    LnaVerified {
+        node: NodeId,
+        enable: bool,
+    },
+    TracerouteSent {
+        observer: String,
+        target: NodeId,
+        request_id: u32,
+    },
+    SampleAccepted {
+        observer: String,
+        phase: String,
+        request_id: u32,
+        rtt_ms: u64,
+        snr_towards: Vec<f32>,
+        snr_back: Vec<f32>,
+    },
+    SampleRejected {
+        request_id: u32,
+        reason: RejectReason,
+    },
+    StatsUpdated {
+        stats: AverageStats,
+    },
+    Warning {
+        message: String,
+    },
+    Finished {
+        stats: AverageStats,
+    },
+}
+
+/// Folds the event stream into the `ProgressState` snapshots the progress callback receives.
+#[derive(Debug, Clone)]
+pub struct ProgressTracker {
+    state: ProgressState,
+}
+
+impl Default for ProgressTracker {
+    fn default() -> Self {
+        Self {
+            state: ProgressState {
+                total_progress: 0.0,
+                current_round_progress: 0.0,
+                status_message: "Idle".to_string(),
+                eta_seconds: 0,
+                snr_towards: None,
+                snr_back: None,
+                phase: "Idle".to_string(),
+                average_stats: None,
+            },
+        }
+    }
+}
+
+impl ProgressTracker {
+    /// Applies `event` and returns the new snapshot, or `None` if it changes nothing shown.
+    pub fn apply(&mut self, event: &EngineEvent) -> Option<ProgressState> {
+        let state = &mut self.state;
+        // Live readings only accompany the sample that produced them; averages stay until replaced
+        state.snr_towards = None;
+        state.snr_back = None;
+
+        match event {
+            EngineEvent::PhaseStarted {
+                cycle,
+                total_cycles,
+                phase,
+                phase_num,
+                eta_seconds,
+            } => {
+                let passed_phases = cycle * 2 + (*phase_num as u32 - 1);
+                state.total_progress = passed_phases as f32 / (total_cycles * 2).max(1) as f32;
+                state.current_round_progress = 0.0;
+                state.status_message = format!(
+                    "Cycle {}/{}: Starting Phase {} ({})",
+                    cycle + 1,
+                    total_cycles,
+                    phase_num,
+                    phase
+                );
+                state.eta_seconds = *eta_seconds;
+                state.phase = phase.clone();
+            }
+            EngineEvent::PhaseProgress {
+                cycle,
+                phase,
+                step,
+                total_steps,
+                phase_progress,
+                total_progress,
+                eta_seconds,
+            } => {
+                state.total_progress = total_progress.min(0.99);
+                state.current_round_progress = *phase_progress;
+                state.status_message = format!(
+                    "Cycle {}: {} - Step {}/{}",
+                    cycle + 1,
+                    phase,
+                    step,
+                    total_steps
+                );
+                state.eta_seconds = *eta_seconds;
+                state.phase = phase.clone();
+            }
+            EngineEvent::LnaSetRequested {
+                node,
+                enable,
+                attempt,
+                max_attempts,
+            } => {
+                state.status_message = format!(
+                    "Setting LNA {} on {} (attempt {}/{})",
+                    if *enable { "ON" } else { "OFF" },
+                    node,
+                    attempt,
+                    max_attempts
+                );
+            }
+            EngineEvent::LnaVerified { node, enable } => {
+                state.status_message = format!(
+                    "LNA {} verified on {}",
+                    if *enable { "ON" } else { "OFF" },
+                    node
+                );
+            }
+            EngineEvent::SampleAccepted {
+                observer,
+                phase,
+                snr_towards,
+                snr_back,
+                ..
+            } => {
+                // Observer paths differ from the roof/mountain links the live chart shows
+                if observer != PRIMARY_OBSERVER {
+                    return None;
+                }
+                state.status_message = format!("Received Result ({})", phase);
+                state.snr_towards = Some(snr_towards.clone());
+                state.snr_back = Some(snr_back.clone());
+            }
+            EngineEvent::StatsUpdated { stats } => {
+                state.average_stats = Some(stats.clone());
+            }
+            EngineEvent::Warning { message } => {
+                state.status_message = format!("⚠️ {}", message);
+            }
+            EngineEvent::Finished { stats } => {
+                state.total_progress = 1.0;
+                state.current_round_progress = 1.0;
+                state.status_message = "Test Completed".to_string();
+                state.eta_seconds = 0;
+                state.phase = "Done".to_string();
+                state.average_stats = Some(stats.clone());
+            }
+            EngineEvent::TracerouteSent { .. } | EngineEvent::SampleRejected { .. } => {
+                return None;
+            }
+        }
+        Some(state.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phase_started(cycle: u32, phase_num: u8) -> EngineEvent {
+        EngineEvent::PhaseStarted {
+            cycle,
+            total_cycles: 3,
+            phase: if phase_num == 1 { "LNA OFF" } else { "LNA ON" }.to_string(),
+            phase_num,
+            eta_seconds: 600,
+        }
+    }
+
+    fn sample_accepted(observer: &str) -> EngineEvent {
+        EngineEvent::SampleAccepted {
+            observer: observer.to_string(),
+            phase: "LNA ON".to_string(),
+            request_id: 7,
+            rtt_ms: 1200,
+            snr_towards: vec![6.5],
+            snr_back: vec![-3.25],
+        }
+    }
+
+    #[test]
+    fn phase_started_counts_finished_phases() {
+        let mut tracker = ProgressTracker::default();
+        let state = tracker.apply(&phase_started(0, 1)).unwrap();
+        assert_eq!(state.total_progress, 0.0);
+
+        let state = tracker.apply(&phase_started(1, 2)).unwrap();
+        assert_eq!(state.total_progress, 0.5);
+        assert_eq!(state.current_round_progress, 0.0);
+        assert_eq!(state.phase, "LNA ON");
+        assert_eq!(state.status_message, "Cycle 2/3: Starting Phase 2 (LNA ON)");
+        assert_eq!(state.eta_seconds, 600);
+    }
+
+    #[test]
+    fn phase_progress_never_reports_done() {
+        let mut tracker = ProgressTracker::default();
+        let state = tracker
+            .apply(&EngineEvent::PhaseProgress {
+                cycle: 2,
+                phase: "LNA ON".to_string(),
+                step: 60,
+                total_steps: 60,
+                phase_progress: 1.0,
+                total_progress: 1.0,
+                eta_seconds: 0,
+            })
+            .unwrap();
+        assert_eq!(state.total_progress, 0.99);
+        assert_eq!(state.current_round_progress, 1.0);
+    }
+
+    #[test]
+    fn live_snr_comes_from_primary_samples_only() {
+        let mut tracker = ProgressTracker::default();
+        assert!(tracker.apply(&sample_accepted("observer-1")).is_none());
+
+        let state = tracker.apply(&sample_accepted(PRIMARY_OBSERVER)).unwrap();
+        assert_eq!(state.snr_towards, Some(vec![6.5]));
+        assert_eq!(state.snr_back, Some(vec![-3.25]));
+
+        let state = tracker.apply(&phase_started(1, 1)).unwrap();
+        assert_eq!(state.snr_towards, None);
+        assert_eq!(state.snr_back, None);
+    }
+
+    #[test]
+    fn unshown_events_produce_no_snapshot() {
+        let mut tracker = ProgressTracker::default();
+        let sent = EngineEvent::TracerouteSent {
+            observer: PRIMARY_OBSERVER.to_string(),
+            target: NodeId::new(0x1234_5678),
+            request_id: 1,
+        };
+        let rejected = EngineEvent::SampleRejected {
+            request_id: 1,
+            reason: RejectReason::SnrFloor,
+        };
+        assert!(tracker.apply(&sent).is_none());
+        assert!(tracker.apply(&rejected).is_none());
+    }
+
+    #[test]
+    fn finished_completes_progress() {
+        let mut tracker = ProgressTracker::default();
+        tracker.apply(&phase_started(2, 2));
+        let stats = AverageStats {
+            lna_on_samples: 12,
+            ..Default::default()
+        };
+        let state = tracker.apply(&EngineEvent::Finished { stats }).unwrap();
+        assert_eq!(state.total_progress, 1.0);
+        assert_eq!(state.current_round_progress, 1.0);
+        assert_eq!(state.phase, "Done");
+        assert_eq!(state.eta_seconds, 0);
+        assert_eq!(
+            state.average_stats.map(|stats| stats.lna_on_samples),
+            Some(12)
+        );
+    }
+}
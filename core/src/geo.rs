@@ -0,0 +1,468 @@
+use crate::logging;
+use crate::msnr_log_err;
+use anyhow::Result;
+use meshtastic::protobufs::Position;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio_serial::SerialPortBuilderExt;
+
+const KNOTS_TO_MPS: f32 = 0.514_444;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GeoSource {
+    /// Position reported by the local Meshtastic node.
+    Node,
+    /// External GPS receiver read over NMEA.
+    Nmea,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoFix {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude_m: Option<f32>,
+    pub speed_mps: Option<f32>,
+    pub source: GeoSource,
+    /// Not persisted; a fix restored from a checkpoint counts as fresh.
+    #[serde(skip, default = "Instant::now")]
+    pub received_at: Instant,
+}
+
+impl GeoFix {
+    /// Converts a node `Position`; `None` when the node has no fix (lat/lon unset or 0,0).
+    pub fn from_position(position: &Position) -> Option<Self> {
+        let latitude_i = position.latitude_i?;
+        let longitude_i = position.longitude_i?;
+        if latitude_i == 0 && longitude_i == 0 {
+            return None;
+        }
+
+        Some(Self {
+            latitude: latitude_i as f64 * 1e-7,
+            longitude: longitude_i as f64 * 1e-7,
+            altitude_m: position.altitude.map(|alt| alt as f32),
+            speed_mps: position.ground_speed.map(|speed| speed as f32),
+            source: GeoSource::Node,
+            received_at: Instant::now(),
+        })
+    }
+
+    pub fn age(&self) -> Duration {
+        self.received_at.elapsed()
+    }
+}
+
+/// Which reading colours the exported map layers.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub enum GeoColorMetric {
+    #[default]
+    Snr,
+    Rssi,
+}
+
+/// One geotagged measurement, as exported to GeoJSON/KML.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoSample {
+    pub timestamp: String,
+    pub cycle: u32,
+    pub phase: String,
+    pub lna_on: bool,
+    /// `traceroute` or `passive`.
+    pub kind: String,
+    pub node: String,
+    /// `false` for a traceroute that never got an answer.
+    pub delivered: bool,
+    /// SNR of the last hop into the local radio.
+    pub snr: Option<f32>,
+    pub rssi: Option<i32>,
+    pub fix: GeoFix,
+}
+
+impl GeoSample {
+    fn metric(&self, metric: GeoColorMetric) -> Option<f32> {
+        match metric {
+            GeoColorMetric::Snr => self.snr,
+            GeoColorMetric::Rssi => self.rssi.map(|rssi| rssi as f32),
+        }
+    }
+}
+
+/// Red-to-green colour for a reading, `None` rendered grey.
+fn metric_rgb(value: Option<f32>, metric: GeoColorMetric) -> (u8, u8, u8) {
+    let Some(value) = value else {
+        return (128, 128, 128);
+    };
+    let (worst, best) = match metric {
+        GeoColorMetric::Snr => (-20.0, 10.0),
+        GeoColorMetric::Rssi => (-130.0, -60.0),
+    };
+    let t = ((value - worst) / (best - worst)).clamp(0.0, 1.0);
+    let red = (255.0 * (1.0 - t)).round() as u8;
+    let green = (255.0 * t).round() as u8;
+    (red, green, 0)
+}
+
+pub fn write_geojson(path: &Path, samples: &[GeoSample], metric: GeoColorMetric) -> Result<()> {
+    let features: Vec<serde_json::Value> = samples
+        .iter()
+        .map(|sample| {
+            let (r, g, b) = metric_rgb(sample.metric(metric), metric);
+            let mut coordinates = vec![sample.fix.longitude, sample.fix.latitude];
+            if let Some(alt) = sample.fix.altitude_m {
+                coordinates.push(alt as f64);
+            }
+            serde_json::json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": coordinates },
+                "properties": {
+                    "timestamp": sample.timestamp,
+                    "cycle": sample.cycle,
+                    "phase": sample.phase,
+                    "lna_on": sample.lna_on,
+                    "kind": sample.kind,
+                    "node": sample.node,
+                    "delivered": sample.delivered,
+                    "snr": sample.snr,
+                    "rssi": sample.rssi,
+                    "speed_mps": sample.fix.speed_mps,
+                    "marker-color": format!("#{:02x}{:02x}{:02x}", r, g, b),
+                },
+            })
+        })
+        .collect();
+
+    let collection = serde_json::json!({
+        "type": "FeatureCollection",
+        "features": features,
+    });
+    std::fs::write(path, serde_json::to_string_pretty(&collection)?)?;
+    Ok(())
+}
+
+pub fn write_kml(path: &Path, samples: &[GeoSample], metric: GeoColorMetric) -> Result<()> {
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n");
+    kml.push_str("<name>MSNR samples</name>\n");
+
+    for sample in samples {
+        let value = sample.metric(metric);
+        let (r, g, b) = metric_rgb(value, metric);
+        let label = value
+            .map(|value| format!("{:.1}", value))
+            .unwrap_or_else(|| "--".into());
+        // KML colours are aabbggrr
+        writeln!(
+            kml,
+            "<Placemark><name>{}</name><description>{} {} {} LNA {} | SNR {} dB | RSSI {} dBm</description>\
+             <Style><IconStyle><color>ff{:02x}{:02x}{:02x}</color></IconStyle></Style>\
+             <Point><coordinates>{:.7},{:.7},{}</coordinates></Point></Placemark>",
+            xml_escape(&label),
+            xml_escape(&sample.timestamp),
+            sample.kind,
+            xml_escape(&sample.node),
+            if sample.lna_on { "ON" } else { "OFF" },
+            sample
+                .snr
+                .map(|snr| format!("{:.2}", snr))
+                .unwrap_or_else(|| "--".into()),
+            sample
+                .rssi
+                .map(|rssi| rssi.to_string())
+                .unwrap_or_else(|| "--".into()),
+            b,
+            g,
+            r,
+            sample.fix.longitude,
+            sample.fix.latitude,
+            sample.fix.altitude_m.unwrap_or(0.0)
+        )?;
+    }
+
+    kml.push_str("</Document>\n</kml>\n");
+    std::fs::write(path, kml)?;
+    Ok(())
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Background reader for an NMEA GPS on a serial port, publishing the latest fix.
+pub struct NmeaGps {
+    fix: watch::Receiver<Option<GeoFix>>,
+    task: JoinHandle<()>,
+}
+
+impl NmeaGps {
+    pub fn open(port_name: &str, baud_rate: u32) -> Result<Self> {
+        let port = tokio_serial::new(port_name, baud_rate).open_native_async()?;
+        let (tx, rx) = watch::channel(None);
+        let port_name = port_name.to_string();
+
+        let task = tokio::spawn(logging::in_current_context(async move {
+            let mut reader = BufReader::new(port);
+            let mut line = Vec::new();
+            let mut altitude_m = None;
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line).await {
+                    // Line noise can garble bytes; such sentences fail their checksum and are skipped
+                    Ok(read) if read > 0 => match parse_nmea(&String::from_utf8_lossy(&line)) {
+                        Some(NmeaSentence::Gga { fix }) => {
+                            altitude_m = fix.altitude_m;
+                            let speed_mps = tx.borrow().and_then(|last| last.speed_mps);
+                            let _ = tx.send(Some(GeoFix { speed_mps, ..fix }));
+                        }
+                        Some(NmeaSentence::Rmc { fix }) => {
+                            let _ = tx.send(Some(GeoFix { altitude_m, ..fix }));
+                        }
+                        None => {}
+                    },
+                    Ok(_) => {
+                        msnr_log_err!("NMEA GPS on {} closed", port_name);
+                        break;
+                    }
+                    Err(e) => {
+                        msnr_log_err!("NMEA GPS read error on {}: {}", port_name, e);
+                        break;
+                    }
+                }
+            }
+        }));
+
+        Ok(Self { fix: rx, task })
+    }
+
+    pub fn latest(&self) -> Option<GeoFix> {
+        *self.fix.borrow()
+    }
+}
+
+impl Drop for NmeaGps {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+enum NmeaSentence {
+    Gga { fix: GeoFix },
+    Rmc { fix: GeoFix },
+}
+
+/// Parses the GGA and RMC sentences from any talker (GP, GN, GL, ...). Sentences without
+/// a valid `*hh` checksum are rejected, since a garbled one may still look well-formed.
+fn parse_nmea(line: &str) -> Option<NmeaSentence> {
+    let line = line.trim();
+    let body = line.strip_prefix('$')?;
+    let (body, checksum) = body.split_once('*')?;
+    let checksum = checksum.get(..2)?;
+    if !checksum.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let expected = u8::from_str_radix(checksum, 16).ok()?;
+    if body.bytes().fold(0u8, |acc, byte| acc ^ byte) != expected {
+        return None;
+    }
+
+    let fields: Vec<&str> = body.split(',').collect();
+    let kind = fields.first()?.get(2..)?;
+    match kind {
+        "GGA" => {
+            // Fix quality 0 means no fix
+            if fields.get(6).copied().unwrap_or("0") == "0" {
+                return None;
+            }
+            let latitude = parse_coordinate(fields.get(2)?, fields.get(3)?)?;
+            let longitude = parse_coordinate(fields.get(4)?, fields.get(5)?)?;
+            let altitude_m = fields.get(9).and_then(|alt| alt.parse().ok());
+            Some(NmeaSentence::Gga {
+                fix: GeoFix {
+                    latitude,
+                    longitude,
+                    altitude_m,
+                    speed_mps: None,
+                    source: GeoSource::Nmea,
+                    received_at: Instant::now(),
+                },
+            })
+        }
+        "RMC" => {
+            if fields.get(2).copied() != Some("A") {
+                return None;
+            }
+            let latitude = parse_coordinate(fields.get(3)?, fields.get(4)?)?;
+            let longitude = parse_coordinate(fields.get(5)?, fields.get(6)?)?;
+            let speed_mps = fields
+                .get(7)
+                .and_then(|knots| knots.parse::<f32>().ok())
+                .map(|knots| knots * KNOTS_TO_MPS);
+            Some(NmeaSentence::Rmc {
+                fix: GeoFix {
+                    latitude,
+                    longitude,
+                    altitude_m: None,
+                    speed_mps,
+                    source: GeoSource::Nmea,
+                    received_at: Instant::now(),
+                },
+            })
+        }
+        _ => None,
+    }
+}
+
+/// `ddmm.mmmm` / `dddmm.mmmm` plus hemisphere to signed decimal degrees.
+fn parse_coordinate(value: &str, hemisphere: &str) -> Option<f64> {
+    // `f64::from_str` would also take signs, exponents, "inf" and "NaN"
+    if !value
+        .bytes()
+        .all(|byte| byte.is_ascii_digit() || byte == b'.')
+    {
+        return None;
+    }
+    let dot = value.find('.').unwrap_or(value.len());
+    if dot < 3 {
+        return None;
+    }
+    let degrees: f64 = value.get(..dot - 2)?.parse().ok()?;
+    let minutes: f64 = value.get(dot - 2..)?.parse().ok()?;
+    if minutes >= 60.0 {
+        return None;
+    }
+    let decimal = degrees + minutes / 60.0;
+    match hemisphere {
+        "N" | "E" => Some(decimal),
+        "S" | "W" => Some(-decimal),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    fn gga(line: &str) -> GeoFix {
+        match parse_nmea(line) {
+            Some(NmeaSentence::Gga { fix }) => fix,
+            _ => panic!("{line} did not parse as GGA"),
+        }
+    }
+
+    fn rmc(line: &str) -> GeoFix {
+        match parse_nmea(line) {
+            Some(NmeaSentence::Rmc { fix }) => fix,
+            _ => panic!("{line} did not parse as RMC"),
+        }
+    }
+
+    #[test]
+    fn parses_gga_from_gp_and_gn_talkers() {
+        let fix = gga("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47\r\n");
+        assert_close(fix.latitude, 48.0 + 7.038 / 60.0);
+        assert_close(fix.longitude, 11.0 + 31.0 / 60.0);
+        assert_eq!(fix.altitude_m, Some(545.4));
+        assert_eq!(fix.speed_mps, None);
+        assert_eq!(fix.source, GeoSource::Nmea);
+
+        let fix = gga("$GNGGA,001043.00,3352.2046,S,15112.3080,E,2,12,0.8,28.6,M,22.1,M,,*56");
+        assert_close(fix.latitude, -(33.0 + 52.2046 / 60.0));
+        assert_close(fix.longitude, 151.0 + 12.308 / 60.0);
+        assert_eq!(fix.altitude_m, Some(28.6));
+    }
+
+    #[test]
+    fn parses_rmc_from_gp_and_gn_talkers() {
+        let fix = rmc("$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A");
+        assert_close(fix.latitude, 48.0 + 7.038 / 60.0);
+        assert_close(fix.longitude, 11.0 + 31.0 / 60.0);
+        assert_eq!(fix.altitude_m, None);
+        assert!((fix.speed_mps.unwrap() - 22.4 * KNOTS_TO_MPS).abs() < 1e-4);
+
+        let fix = rmc("$GNRMC,001043.00,A,3352.2046,S,15112.3080,E,0.000,,010524,,,A*7D");
+        assert_close(fix.latitude, -(33.0 + 52.2046 / 60.0));
+        assert_eq!(fix.speed_mps, Some(0.0));
+    }
+
+    #[test]
+    fn negates_south_and_west() {
+        let fix = rmc("$GPRMC,225446,A,4916.45,N,12311.12,W,000.5,054.7,191194,020.3,E*68");
+        assert_close(fix.latitude, 49.0 + 16.45 / 60.0);
+        assert_close(fix.longitude, -(123.0 + 11.12 / 60.0));
+        assert_close(
+            parse_coordinate("3352.2046", "S").unwrap(),
+            -(33.0 + 52.2046 / 60.0),
+        );
+    }
+
+    #[test]
+    fn rejects_bad_or_missing_checksum() {
+        // Last digit of the latitude garbled
+        assert!(
+            parse_nmea("$GPGGA,123519,4807.039,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*47")
+                .is_none()
+        );
+        assert!(
+            parse_nmea("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,").is_none()
+        );
+        assert!(
+            parse_nmea("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*4")
+                .is_none()
+        );
+        assert!(
+            parse_nmea("$GPGGA,123519,4807.038,N,01131.000,E,1,08,0.9,545.4,M,46.9,M,,*+7")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn ignores_sentences_without_a_fix() {
+        assert!(parse_nmea("$GPGGA,123519,,,,,0,00,,,M,,M,,*6B").is_none());
+        assert!(parse_nmea("$GPRMC,123519,V,4807.038,N,01131.000,E,,,230394,,*0A").is_none());
+        assert!(
+            parse_nmea("$GPGSV,3,1,11,03,03,111,00,04,15,270,00,06,01,010,00,13,06,292,00*74")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_coordinates() {
+        for value in [
+            "",
+            "12",
+            "1.5",
+            "-4807.038",
+            "+4807.038",
+            "48a7.038",
+            "4807.03.8",
+            "4860.000",
+            "4807,038",
+            "48\u{e9}7.03",
+            "1e3.0",
+        ] {
+            assert_eq!(
+                parse_coordinate(value, "N"),
+                None,
+                "{value:?} should be rejected"
+            );
+        }
+        assert_eq!(parse_coordinate("4807.038", "X"), None);
+        assert_eq!(parse_coordinate("4807.038", ""), None);
+    }
+}
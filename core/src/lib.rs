@@ -1,27 +1,53 @@
+pub mod airtime;
 pub mod config;
+pub mod coverage;
 pub mod engine;
+pub mod events;
+pub mod geo;
 pub mod logging;
+pub mod node_id;
+pub mod scheduler;
 pub mod transport;
 
-pub use config::{Config, LnaControlTarget, TransportMode};
-pub use engine::{Engine, ProgressState};
-pub use logging::{clear_log_callback, set_log_callback};
-pub use transport::{IpTransport, SerialTransport, Transport};
+pub use config::{Config, LnaControlTarget, MeasurementMode, SerialFlowControl, TransportMode};
+pub use engine::{
+    ChannelUtilStats, Checkpoint, Engine, HeardNodeStats, LinkStats, ObserverStats, ProgressState,
+    RangeTestSummary, StopHandle,
+    doctor::{CheckStatus, PreflightCheck, PreflightReport},
+};
+pub use events::{EngineEvent, ProgressTracker, RejectReason};
+pub use logging::{
+    LogCallback, LogLevel, LogRecord, clear_log_callback, set_log_callback, set_log_level,
+};
+pub use node_id::{NodeId, NodeIdParseError};
+pub use scheduler::{Schedule, Scheduler};
+pub use transport::{HttpTransport, IpTransport, SerialTransport, Transport};
 
+/// Logs at `Info`, targeted at the calling module.
 #[macro_export]
 macro_rules! msnr_log {
-    ($($arg:tt)*) => {{
-        let msg = format!($($arg)*);
-        println!("{}", msg);
-        $crate::logging::emit(&msg);
-    }};
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Info, module_path!(), format!($($arg)*))
+    };
 }
 
 #[macro_export]
 macro_rules! msnr_log_err {
-    ($($arg:tt)*) => {{
-        let msg = format!($($arg)*);
-        eprintln!("{}", msg);
-        $crate::logging::emit(&msg);
-    }};
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Error, module_path!(), format!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! msnr_warn {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Warn, module_path!(), format!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! msnr_debug {
+    ($($arg:tt)*) => {
+        $crate::logging::log($crate::logging::LogLevel::Debug, module_path!(), format!($($arg)*))
+    };
 }
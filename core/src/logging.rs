@@ -1,12 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
 
-pub type LogCallback = Arc<dyn Fn(String) + Send + Sync + 'static>;
+/// Severity of a log record, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // `pad` so width specifiers line up the columns in session log files
+        f.pad(match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        })
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> anyhow::Result<Self> {
+        match input.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            _ => anyhow::bail!("unknown log level '{input}', expected debug, info, warn or error"),
+        }
+    }
+}
+
+/// One log line with its structured fields.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub timestamp: String,
+    pub level: LogLevel,
+    /// Module that logged, e.g. `msnr_core::engine`.
+    pub target: String,
+    /// Engine session that was running, if the line came from inside one.
+    pub session: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:<5} {}", self.timestamp, self.level, self.target)?;
+        if let Some(session) = &self.session {
+            write!(f, " session={}", session)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+pub type LogCallback = Arc<dyn Fn(&LogRecord) + Send + Sync + 'static>;
+
+/// Per-session fields and routing for every record logged inside `LogContext::scope`.
+#[derive(Clone)]
+pub struct LogContext {
+    session: Arc<str>,
+    file: Option<Arc<Mutex<std::fs::File>>>,
+    sink: Option<LogCallback>,
+}
+
+impl LogContext {
+    pub fn new(session: impl Into<Arc<str>>) -> Self {
+        Self {
+            session: session.into(),
+            file: None,
+            sink: None,
+        }
+    }
+
+    /// Sends records to `sink` instead of the global callback.
+    pub fn with_sink(mut self, sink: LogCallback) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Also appends every record, down to `Debug`, to `file`.
+    pub fn with_file(mut self, file: std::fs::File) -> Self {
+        self.file = Some(Arc::new(Mutex::new(file)));
+        self
+    }
+
+    pub fn session(&self) -> &str {
+        &self.session
+    }
+
+    /// Runs `future` with this context applied to everything it logs.
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        LOG_CONTEXT.scope(self, future).await
+    }
+}
+
+tokio::task_local! {
+    static LOG_CONTEXT: LogContext;
+}
+
+/// Carries the calling task's log context into `future`, for work handed to `tokio::spawn`,
+/// which would otherwise log without session, file or sink.
+pub fn in_current_context<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let context = LOG_CONTEXT.try_with(LogContext::clone).ok();
+    async move {
+        match context {
+            Some(context) => context.scope(future).await,
+            None => future.await,
+        }
+    }
+}
 
 fn log_storage() -> &'static Mutex<Option<LogCallback>> {
     static STORE: OnceLock<Mutex<Option<LogCallback>>> = OnceLock::new();
     STORE.get_or_init(|| Mutex::new(None))
 }
 
+/// Least severe level printed to the console.
+static MAX_VERBOSITY: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+/// Receives records logged outside any engine, or by an engine built without its own sink.
 pub fn set_log_callback(callback: LogCallback) {
     let mut guard = log_storage().lock().unwrap();
     *guard = Some(callback);
@@ -17,8 +138,46 @@ pub fn clear_log_callback() {
     *guard = None;
 }
 
-pub(crate) fn emit(message: &str) {
-    if let Some(cb) = log_storage().lock().unwrap().as_ref() {
-        cb(message.to_string());
+/// Sets the least severe level printed to the console; session log files, sinks and the
+/// callback always get everything and filter for themselves.
+pub fn set_log_level(level: LogLevel) {
+    MAX_VERBOSITY.store(level as u8, Ordering::Relaxed);
+}
+
+fn enabled(level: LogLevel) -> bool {
+    level as u8 >= MAX_VERBOSITY.load(Ordering::Relaxed)
+}
+
+#[doc(hidden)]
+pub fn log(level: LogLevel, target: &str, message: String) {
+    let context = LOG_CONTEXT.try_with(LogContext::clone).ok();
+    let record = LogRecord {
+        timestamp: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+        level,
+        target: target.to_string(),
+        session: context.as_ref().map(|context| context.session.to_string()),
+        message,
+    };
+
+    if let Some(file) = context.as_ref().and_then(|context| context.file.as_ref()) {
+        if let Ok(mut file) = file.lock() {
+            let _ = writeln!(file, "{}", record);
+        }
+    }
+
+    if enabled(level) {
+        if level >= LogLevel::Warn {
+            eprintln!("{}", record.message);
+        } else {
+            println!("{}", record.message);
+        }
+    }
+    match context.and_then(|context| context.sink) {
+        Some(sink) => sink(&record),
+        None => {
+            if let Some(cb) = log_storage().lock().unwrap().as_ref() {
+                cb(&record);
+            }
+        }
     }
 }
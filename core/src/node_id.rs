@@ -0,0 +1,238 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A Meshtastic node number, parsed once and formatted as `!xxxxxxxx`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NodeId(u32);
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum NodeIdParseError {
+    #[error("node ID is empty")]
+    Empty,
+    #[error("invalid node ID '{0}' (expected !xxxxxxxx, 0xXXXXXXXX or a decimal node number)")]
+    Invalid(String),
+}
+
+impl NodeId {
+    /// Destination the firmware resolves to the directly connected node.
+    pub const LOCAL: NodeId = NodeId(0);
+    pub const BROADCAST: NodeId = NodeId(u32::MAX);
+
+    pub const fn new(num: u32) -> Self {
+        Self(num)
+    }
+
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+
+    pub fn is_broadcast(self) -> bool {
+        self == Self::BROADCAST
+    }
+
+    pub fn is_local(self) -> bool {
+        self == Self::LOCAL
+    }
+}
+
+impl From<u32> for NodeId {
+    fn from(num: u32) -> Self {
+        Self(num)
+    }
+}
+
+impl From<NodeId> for u32 {
+    fn from(id: NodeId) -> Self {
+        id.0
+    }
+}
+
+impl FromStr for NodeId {
+    type Err = NodeIdParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let trimmed = input.trim();
+        if trimmed.is_empty() {
+            return Err(NodeIdParseError::Empty);
+        }
+
+        let parsed = if let Some(hex) = trimmed.strip_prefix('!') {
+            parse_digits(hex, 16)
+        } else if let Some(hex) = trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+        {
+            parse_digits(hex, 16)
+        } else if trimmed.eq_ignore_ascii_case("broadcast") {
+            Some(u32::MAX)
+        } else {
+            parse_digits(trimmed, 10)
+        };
+
+        parsed
+            .map(NodeId)
+            .ok_or_else(|| NodeIdParseError::Invalid(input.to_string()))
+    }
+}
+
+/// Digits only: `from_str_radix` would also take a leading sign, as in `!+ff`.
+fn parse_digits(digits: &str, radix: u32) -> Option<u32> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    u32::from_str_radix(digits, radix).ok()
+}
+
+impl TryFrom<String> for NodeId {
+    type Error = NodeIdParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<NodeId> for String {
+    fn from(id: NodeId) -> Self {
+        id.to_string()
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "!{:08x}", self.0)
+    }
+}
+
+/// Deserializes an optional node ID, treating an empty string (as sent by the GUI
+/// for unused fields) as `None` while still rejecting malformed IDs.
+pub(crate) fn deserialize_optional<'de, D>(deserializer: D) -> Result<Option<NodeId>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw: Option<String> = Option::deserialize(deserializer)?;
+    match raw.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<NodeId, NodeIdParseError> {
+        input.parse()
+    }
+
+    #[test]
+    fn parses_bang_hex() {
+        assert_eq!(parse("!a1b2c3d4"), Ok(NodeId::new(0xa1b2_c3d4)));
+        assert_eq!(parse("!A1B2C3D4"), Ok(NodeId::new(0xa1b2_c3d4)));
+        assert_eq!(parse("!ff"), Ok(NodeId::new(0xff)));
+    }
+
+    #[test]
+    fn parses_0x_hex() {
+        assert_eq!(parse("0xa1b2c3d4"), Ok(NodeId::new(0xa1b2_c3d4)));
+        assert_eq!(parse("0XA1B2C3D4"), Ok(NodeId::new(0xa1b2_c3d4)));
+    }
+
+    #[test]
+    fn parses_decimal() {
+        assert_eq!(parse("2712847316"), Ok(NodeId::new(2_712_847_316)));
+        assert_eq!(parse("0"), Ok(NodeId::LOCAL));
+    }
+
+    #[test]
+    fn parses_broadcast() {
+        assert_eq!(parse("broadcast"), Ok(NodeId::BROADCAST));
+        assert_eq!(parse("BROADCAST"), Ok(NodeId::BROADCAST));
+        assert!(parse("!ffffffff").unwrap().is_broadcast());
+    }
+
+    #[test]
+    fn trims_whitespace() {
+        assert_eq!(parse("  !a1b2c3d4\n"), Ok(NodeId::new(0xa1b2_c3d4)));
+        assert_eq!(parse("\t42 "), Ok(NodeId::new(42)));
+    }
+
+    #[test]
+    fn rejects_empty() {
+        assert_eq!(parse(""), Err(NodeIdParseError::Empty));
+        assert_eq!(parse("   "), Err(NodeIdParseError::Empty));
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        for input in [
+            "!",
+            "0x",
+            "!xyz",
+            "!1ffffffff",
+            "4294967296",
+            "-1",
+            "+5",
+            "!+ff",
+            "0x-1",
+            "12ab",
+            "node",
+        ] {
+            assert_eq!(
+                parse(input),
+                Err(NodeIdParseError::Invalid(input.to_string())),
+                "{input:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn displays_as_bang_hex() {
+        assert_eq!(NodeId::new(0xff).to_string(), "!000000ff");
+        assert_eq!(
+            parse(&NodeId::new(0xa1b2_c3d4).to_string()),
+            Ok(NodeId::new(0xa1b2_c3d4))
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Fields {
+        #[serde(default, deserialize_with = "deserialize_optional")]
+        node: Option<NodeId>,
+    }
+
+    #[test]
+    fn optional_round_trips_through_serde() {
+        for node in [Some(NodeId::new(0xa1b2_c3d4)), None] {
+            let json = serde_json::to_string(&Fields { node }).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Fields>(&json).unwrap(),
+                Fields { node }
+            );
+        }
+        assert_eq!(
+            serde_json::to_string(&Fields {
+                node: Some(NodeId::new(0xff))
+            })
+            .unwrap(),
+            r#"{"node":"!000000ff"}"#
+        );
+    }
+
+    #[test]
+    fn optional_treats_blank_as_none_and_rejects_garbage() {
+        let blank: Fields = serde_json::from_str(r#"{"node":"  "}"#).unwrap();
+        assert_eq!(blank, Fields { node: None });
+        let missing: Fields = serde_json::from_str("{}").unwrap();
+        assert_eq!(missing, Fields { node: None });
+        let decimal: Fields = serde_json::from_str(r#"{"node":"42"}"#).unwrap();
+        assert_eq!(
+            decimal,
+            Fields {
+                node: Some(NodeId::new(42))
+            }
+        );
+        assert!(serde_json::from_str::<Fields>(r#"{"node":"!nope"}"#).is_err());
+    }
+}
@@ -0,0 +1,586 @@
+use crate::config::Config;
+use crate::engine::{Engine, ProgressState, StopHandle};
+use crate::logging;
+use crate::{msnr_log, msnr_log_err};
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Longest single sleep while waiting for the next run, so wall-clock jumps
+/// (suspend, NTP, DST) are noticed promptly.
+const MAX_SLEEP_SECS: u64 = 60;
+/// Shortest repeat interval; anything shorter would start runs back to back.
+const MIN_REPEAT_EVERY_SECS: f64 = 60.0;
+
+/// Daily local-time window in which runs may take place; wraps past midnight when `end < start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Earliest instant at or after `at` that falls inside the window.
+    pub fn next_open<Tz: TimeZone>(&self, at: DateTime<Tz>) -> DateTime<Tz> {
+        if self.contains(at.time()) {
+            return at;
+        }
+        let mut opening = at.date_naive().and_time(self.start);
+        if opening <= at.naive_local() {
+            opening += TimeDelta::days(1);
+        }
+        first_valid_instant(opening, &at.timezone()).unwrap_or(at)
+    }
+
+    /// When the window containing `at` closes; `None` if `at` is outside the window.
+    pub fn closes_after<Tz: TimeZone>(&self, at: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        if !self.contains(at.time()) {
+            return None;
+        }
+        let mut closing = at.date_naive().and_time(self.end);
+        if closing <= at.naive_local() {
+            closing += TimeDelta::days(1);
+        }
+        first_valid_instant(closing, &at.timezone())
+    }
+}
+
+/// `local` in `tz`, or the first minute after it when it falls inside a DST gap.
+fn first_valid_instant<Tz: TimeZone>(local: NaiveDateTime, tz: &Tz) -> Option<DateTime<Tz>> {
+    // Gaps last an hour, or half an hour in a few zones
+    (0..=120).find_map(|minutes| {
+        (local + TimeDelta::minutes(minutes))
+            .and_local_timezone(tz.clone())
+            .earliest()
+    })
+}
+
+impl std::str::FromStr for TimeWindow {
+    type Err = anyhow::Error;
+
+    /// Parses `HH:MM-HH:MM`.
+    fn from_str(input: &str) -> Result<Self> {
+        let (start, end) = input
+            .split_once('-')
+            .ok_or_else(|| anyhow!("invalid window '{input}', expected HH:MM-HH:MM"))?;
+        Ok(Self {
+            start: NaiveTime::parse_from_str(start.trim(), "%H:%M")?,
+            end: NaiveTime::parse_from_str(end.trim(), "%H:%M")?,
+        })
+    }
+}
+
+/// When scheduled runs start.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// First run; `None` starts as soon as the window allows.
+    pub start_at: Option<DateTime<Local>>,
+    /// Start a new run this many hours after the previous one started; `None` runs once.
+    pub repeat_every_hours: Option<f64>,
+    /// Only run inside this daily window; a run still going when it closes is stopped.
+    pub window: Option<TimeWindow>,
+}
+
+impl Schedule {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(hours) = self.repeat_every_hours {
+            if !(hours > 0.0 && hours.is_finite()) {
+                anyhow::bail!(
+                    "repeat interval must be a positive number of hours, got {}",
+                    hours
+                );
+            }
+            if hours * 3600.0 < MIN_REPEAT_EVERY_SECS {
+                anyhow::bail!(
+                    "repeat interval of {} h is shorter than the {} s minimum",
+                    hours,
+                    MIN_REPEAT_EVERY_SECS
+                );
+            }
+        }
+        if let Some(window) = self.window {
+            if window.start == window.end {
+                anyhow::bail!("run window {} - {} is empty", window.start, window.end);
+            }
+        }
+        Ok(())
+    }
+
+    fn repeat_every(&self) -> Option<TimeDelta> {
+        self.repeat_every_hours
+            .map(|hours| TimeDelta::milliseconds((hours * 3_600_000.0) as i64))
+    }
+
+    fn within_window<Tz: TimeZone>(&self, at: DateTime<Tz>) -> DateTime<Tz> {
+        match self.window {
+            Some(window) => window.next_open(at),
+            None => at,
+        }
+    }
+
+    /// Start of the first run when nothing has run yet.
+    pub fn first_run(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let start = self.start_at.map_or(now, |start_at| start_at.max(now));
+        self.within_window(start)
+    }
+
+    /// Start of the run after one that started at `started`; `None` once the schedule is done.
+    ///
+    /// Slots missed while a run overran or the daemon was down are skipped rather
+    /// than run back to back.
+    pub fn next_after<Tz: TimeZone>(
+        &self,
+        started: DateTime<Tz>,
+        now: DateTime<Tz>,
+    ) -> Option<DateTime<Tz>> {
+        let every = self.repeat_every()?;
+        let mut next = started + every;
+        if next <= now {
+            let missed = (now.clone() - next.clone()).num_milliseconds()
+                / every.num_milliseconds().max(1)
+                + 1;
+            next += every * missed as i32;
+        }
+        Some(self.within_window(next))
+    }
+}
+
+/// Schedule progress, persisted after every change so a restarted daemon keeps its cadence.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleState {
+    pub schedule: Schedule,
+    /// `None` once a one-shot schedule has run.
+    pub next_run: Option<DateTime<Local>>,
+    pub runs_started: u32,
+    pub last_started: Option<DateTime<Local>>,
+    pub last_finished: Option<DateTime<Local>>,
+    pub last_output: Option<String>,
+    pub last_error: Option<String>,
+}
+
+impl ScheduleState {
+    fn new(schedule: Schedule, now: DateTime<Local>) -> Self {
+        Self {
+            next_run: Some(schedule.first_run(now)),
+            schedule,
+            runs_started: 0,
+            last_started: None,
+            last_finished: None,
+            last_output: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Launches `Engine` runs according to a `Schedule`, one output file per run.
+pub struct Scheduler {
+    config: Config,
+    state_path: PathBuf,
+    state: ScheduleState,
+    stop: StopHandle,
+}
+
+impl Scheduler {
+    /// Loads the state at `state_path` if it was written for the same schedule,
+    /// otherwise starts the schedule afresh.
+    pub fn new(config: Config, schedule: Schedule, state_path: impl Into<PathBuf>) -> Result<Self> {
+        config.validate()?;
+        schedule.validate()?;
+        let state_path = state_path.into();
+        let now = Local::now();
+
+        let state = match Self::load_state(&state_path) {
+            Ok(Some(state)) if state.schedule == schedule => {
+                if state.last_started > state.last_finished {
+                    msnr_log_err!(
+                        "Scheduler: previous run started {} did not finish (output {})",
+                        state
+                            .last_started
+                            .map(|t| t.to_rfc3339())
+                            .unwrap_or_default(),
+                        state.last_output.as_deref().unwrap_or("-")
+                    );
+                }
+                msnr_log!(
+                    "Scheduler: resuming {} after {} run(s)",
+                    state_path.display(),
+                    state.runs_started
+                );
+                state
+            }
+            Ok(Some(_)) => {
+                msnr_log!(
+                    "Scheduler: schedule changed, discarding {}",
+                    state_path.display()
+                );
+                ScheduleState::new(schedule, now)
+            }
+            Ok(None) => ScheduleState::new(schedule, now),
+            Err(e) => {
+                msnr_log_err!(
+                    "Scheduler: ignoring unreadable {}: {}",
+                    state_path.display(),
+                    e
+                );
+                ScheduleState::new(schedule, now)
+            }
+        };
+
+        let scheduler = Self {
+            config,
+            state_path,
+            state,
+            stop: StopHandle::default(),
+        };
+        scheduler.save_state()?;
+        Ok(scheduler)
+    }
+
+    pub fn state(&self) -> &ScheduleState {
+        &self.state
+    }
+
+    /// Ends the daemon: a run in progress is stopped cleanly and no further runs start.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Runs until the schedule is exhausted; a failed run is recorded and does not stop the daemon.
+    ///
+    /// `make_engine` builds the engine for each run from a copy of the config whose
+    /// `output_path` carries the run's start time.
+    pub async fn run<M, F>(&mut self, mut make_engine: M, on_progress: F) -> Result<()>
+    where
+        M: FnMut(Config) -> Result<Engine>,
+        F: Fn(ProgressState) + Clone + Send + Sync + 'static,
+    {
+        while let Some(next_run) = self.state.next_run {
+            if self.stop.is_stopped() {
+                msnr_log!("Scheduler: stopped");
+                break;
+            }
+            let now = Local::now();
+            if next_run > now {
+                let wait = (next_run - now)
+                    .to_std()
+                    .unwrap_or_default()
+                    .min(std::time::Duration::from_secs(MAX_SLEEP_SECS));
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = self.stop.stopped() => {}
+                }
+                continue;
+            }
+
+            let started = Local::now();
+            let mut run_config = self.config.clone();
+            run_config.output_path = timestamped_output_path(&self.config.output_path, started);
+
+            self.state.runs_started += 1;
+            self.state.last_started = Some(started);
+            self.state.last_output = Some(run_config.output_path.clone());
+            self.state.last_error = None;
+            // Advance before running so a crash mid-run does not repeat the slot on restart
+            self.state.next_run = self.state.schedule.next_after(started, started);
+            self.save_state()?;
+
+            msnr_log!(
+                "Scheduler: run {} started, writing {}",
+                self.state.runs_started,
+                run_config.output_path
+            );
+            let result = match make_engine(run_config) {
+                Ok(mut engine) => {
+                    let daemon_stop = self.stop.clone();
+                    let engine_stop = engine.stop_handle();
+                    // The window limits running, not just starting: stop the run when it closes
+                    let window_closes_in = self
+                        .state
+                        .schedule
+                        .window
+                        .and_then(|window| window.closes_after(started))
+                        .and_then(|closes| (closes - Local::now()).to_std().ok());
+                    let watcher = tokio::spawn(logging::in_current_context(async move {
+                        let window_closed = async {
+                            match window_closes_in {
+                                Some(wait) => tokio::time::sleep(wait).await,
+                                None => std::future::pending().await,
+                            }
+                        };
+                        tokio::select! {
+                            _ = daemon_stop.stopped() => {}
+                            _ = window_closed => {
+                                msnr_log!("Scheduler: run window closed, stopping the run");
+                            }
+                        }
+                        engine_stop.stop();
+                    }));
+                    let result = engine.run(on_progress.clone()).await;
+                    watcher.abort();
+                    result
+                }
+                Err(e) => Err(e),
+            };
+
+            let finished = Local::now();
+            self.state.last_finished = Some(finished);
+            if let Err(e) = &result {
+                msnr_log_err!("Scheduler: run {} failed: {}", self.state.runs_started, e);
+                self.state.last_error = Some(e.to_string());
+            }
+            // An overrunning run pushes the next slot past the ones it overlapped
+            if let Some(next) = self.state.next_run {
+                if next <= finished {
+                    self.state.next_run = self.state.schedule.next_after(started, finished);
+                }
+            }
+            self.save_state()?;
+
+            match self.state.next_run {
+                Some(next) => msnr_log!("Scheduler: next run at {}", next.to_rfc3339()),
+                None => msnr_log!("Scheduler: schedule complete"),
+            }
+        }
+        Ok(())
+    }
+
+    fn load_state(path: &Path) -> Result<Option<ScheduleState>> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes through a temporary file so an interrupted write never leaves a truncated state.
+    fn save_state(&self) -> Result<()> {
+        let tmp_path = self.state_path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&self.state)?)?;
+        std::fs::rename(&tmp_path, &self.state_path)?;
+        Ok(())
+    }
+}
+
+/// `results.csv` started at 2024-05-01 03:00:00 becomes `results-20240501-030000.csv`.
+pub fn timestamped_output_path(output_path: &str, started: DateTime<Local>) -> String {
+    let path = Path::new(output_path);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("results");
+    let file_name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}-{}.{}", stem, started.format("%Y%m%d-%H%M%S"), ext),
+        None => format!("{}-{}", stem, started.format("%Y%m%d-%H%M%S")),
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Parses an RFC 3339 timestamp or a local `YYYY-MM-DD HH:MM`.
+pub fn parse_start_at(input: &str) -> Result<DateTime<Local>> {
+    let input = input.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Local));
+    }
+    let naive = chrono::NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M"))
+        .map_err(|_| {
+            anyhow!("invalid start time '{input}', expected YYYY-MM-DD HH:MM or RFC 3339")
+        })?;
+    naive
+        .and_local_timezone(Local)
+        .earliest()
+        .ok_or_else(|| anyhow!("start time '{input}' does not exist in the local time zone"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, LocalResult, NaiveDate, Utc};
+
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, minute, 0).unwrap()
+    }
+
+    fn window(input: &str) -> TimeWindow {
+        input.parse().unwrap()
+    }
+
+    /// UTC+1 until 2024-03-31 01:00 UTC, then UTC+2: local 02:00..03:00 that night never happens.
+    #[derive(Debug, Clone, Copy)]
+    struct SpringForward;
+
+    const SPRING_FORWARD_UTC: i64 = 1_711_846_800;
+
+    impl SpringForward {
+        fn winter() -> FixedOffset {
+            FixedOffset::east_opt(3600).unwrap()
+        }
+
+        fn summer() -> FixedOffset {
+            FixedOffset::east_opt(7200).unwrap()
+        }
+    }
+
+    impl TimeZone for SpringForward {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            SpringForward
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let as_winter = (*local - TimeDelta::hours(1)).and_utc().timestamp();
+            let as_summer = (*local - TimeDelta::hours(2)).and_utc().timestamp();
+            if as_winter < SPRING_FORWARD_UTC {
+                LocalResult::Single(Self::winter())
+            } else if as_summer >= SPRING_FORWARD_UTC {
+                LocalResult::Single(Self::summer())
+            } else {
+                LocalResult::None
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            if utc.and_utc().timestamp() < SPRING_FORWARD_UTC {
+                Self::winter()
+            } else {
+                Self::summer()
+            }
+        }
+    }
+
+    #[test]
+    fn next_open_inside_window_is_immediate() {
+        assert_eq!(
+            window("08:00-18:00").next_open(utc(1, 12, 0)),
+            utc(1, 12, 0)
+        );
+        assert_eq!(window("08:00-18:00").next_open(utc(1, 8, 0)), utc(1, 8, 0));
+    }
+
+    #[test]
+    fn next_open_waits_for_the_next_opening() {
+        assert_eq!(window("08:00-18:00").next_open(utc(1, 6, 0)), utc(1, 8, 0));
+        assert_eq!(window("08:00-18:00").next_open(utc(1, 18, 0)), utc(2, 8, 0));
+    }
+
+    #[test]
+    fn next_open_wraps_past_midnight() {
+        let night = window("22:00-06:00");
+        assert_eq!(night.next_open(utc(1, 23, 30)), utc(1, 23, 30));
+        assert_eq!(night.next_open(utc(2, 3, 0)), utc(2, 3, 0));
+        assert_eq!(night.next_open(utc(2, 6, 0)), utc(2, 22, 0));
+        assert_eq!(night.next_open(utc(2, 12, 0)), utc(2, 22, 0));
+    }
+
+    #[test]
+    fn next_open_in_dst_gap_starts_when_the_clock_jumps() {
+        let at = SpringForward
+            .with_ymd_and_hms(2024, 3, 30, 12, 0, 0)
+            .unwrap();
+        let opened = window("02:30-05:00").next_open(at);
+        assert_eq!(opened.timestamp(), SPRING_FORWARD_UTC);
+        assert_eq!(opened.time(), NaiveTime::from_hms_opt(3, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn closes_after_wraps_past_midnight() {
+        let night = window("22:00-06:00");
+        assert_eq!(night.closes_after(utc(1, 23, 0)), Some(utc(2, 6, 0)));
+        assert_eq!(night.closes_after(utc(2, 3, 0)), Some(utc(2, 6, 0)));
+        assert_eq!(night.closes_after(utc(2, 12, 0)), None);
+        assert_eq!(
+            window("08:00-18:00").closes_after(utc(1, 9, 0)),
+            Some(utc(1, 18, 0))
+        );
+    }
+
+    fn every_two_hours(window: Option<TimeWindow>) -> Schedule {
+        Schedule {
+            start_at: None,
+            repeat_every_hours: Some(2.0),
+            window,
+        }
+    }
+
+    #[test]
+    fn next_after_follows_the_cadence() {
+        let schedule = every_two_hours(None);
+        assert_eq!(
+            schedule.next_after(utc(1, 0, 0), utc(1, 0, 0)),
+            Some(utc(1, 2, 0))
+        );
+        assert_eq!(
+            schedule.next_after(utc(1, 0, 0), utc(1, 1, 30)),
+            Some(utc(1, 2, 0))
+        );
+    }
+
+    #[test]
+    fn next_after_skips_missed_slots() {
+        let schedule = every_two_hours(None);
+        assert_eq!(
+            schedule.next_after(utc(1, 0, 0), utc(1, 2, 0)),
+            Some(utc(1, 4, 0))
+        );
+        assert_eq!(
+            schedule.next_after(utc(1, 0, 0), utc(1, 5, 30)),
+            Some(utc(1, 6, 0))
+        );
+        assert_eq!(
+            schedule.next_after(utc(1, 0, 0), utc(3, 0, 1)),
+            Some(utc(3, 2, 0))
+        );
+    }
+
+    #[test]
+    fn next_after_defers_to_the_window() {
+        let schedule = every_two_hours(Some(window("08:00-18:00")));
+        assert_eq!(
+            schedule.next_after(utc(1, 17, 0), utc(1, 17, 30)),
+            Some(utc(2, 8, 0))
+        );
+    }
+
+    #[test]
+    fn validate_rejects_intervals_under_a_minute() {
+        assert!(every_two_hours(None).validate().is_ok());
+        for hours in [0.0, -1.0, 1e-9, 0.5 / 60.0, f64::NAN, f64::INFINITY] {
+            let schedule = Schedule {
+                start_at: None,
+                repeat_every_hours: Some(hours),
+                window: None,
+            };
+            assert!(schedule.validate().is_err(), "{hours} h should be rejected");
+        }
+    }
+
+    #[test]
+    fn next_after_runs_once_without_repeat() {
+        let schedule = Schedule {
+            start_at: None,
+            repeat_every_hours: None,
+            window: None,
+        };
+        assert_eq!(schedule.next_after(utc(1, 0, 0), utc(1, 0, 0)), None);
+    }
+}
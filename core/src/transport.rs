@@ -1,18 +1,24 @@
+use crate::node_id::NodeId;
 use anyhow::Result;
 use async_trait::async_trait;
 
+pub mod http;
 pub mod ip;
 pub mod serial;
 
+pub use http::HttpTransport;
 pub use ip::IpTransport;
-pub use serial::SerialTransport;
+pub use serial::{
+    DetectedSerialPort, SerialTransport, discover_serial_ports, probe_serial_ports,
+    resolve_serial_port,
+};
 
-#[derive(Debug, Clone)]
-pub struct TracerouteResult {
-    pub hop: u32,
-    pub node_id: String,
-    pub snr: f32,
-    pub rssi: i32,
+/// A traceroute that has been handed to the radio; the reply carries `packet_id` in
+/// `Data.request_id`.
+#[derive(Debug, Clone, Copy)]
+pub struct TracerouteRequest {
+    pub packet_id: u32,
+    pub target: NodeId,
 }
 
 use meshtastic::packet::PacketReceiver;
@@ -21,15 +27,15 @@ use meshtastic::packet::PacketReceiver;
 pub trait Transport: Send + Sync {
     async fn connect(&mut self) -> Result<PacketReceiver>;
     async fn disconnect(&mut self) -> Result<()>;
-    async fn set_lna(&mut self, node_id: &str, enable: bool) -> Result<()>;
+    async fn set_lna(&mut self, node_id: NodeId, enable: bool) -> Result<()>;
     async fn set_identity(&mut self, private_key: Vec<u8>) {
         let _ = private_key;
     } // Default impl does nothing
-    async fn send_packet(&mut self, dest: &str, port: i32, payload: Vec<u8>) -> Result<()>;
+    async fn send_packet(&mut self, dest: NodeId, port: i32, payload: Vec<u8>) -> Result<()>;
     async fn send_admin(
         &mut self,
-        dest: &str,
+        dest: NodeId,
         admin_msg: meshtastic::protobufs::AdminMessage,
     ) -> Result<()>;
-    async fn run_traceroute(&mut self, target_node_id: &str) -> Result<Vec<TracerouteResult>>;
+    async fn run_traceroute(&mut self, target: NodeId) -> Result<TracerouteRequest>;
 }
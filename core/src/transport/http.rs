@@ -0,0 +1,315 @@
+use super::{TracerouteRequest, Transport};
+use crate::logging;
+use crate::node_id::NodeId;
+use crate::{msnr_debug, msnr_log, msnr_log_err};
+use anyhow::Result;
+use async_trait::async_trait;
+use meshtastic::protobufs::{Data, FromRadio, MeshPacket, PortNum, ToRadio, mesh_packet, to_radio};
+use prost::Message;
+use rand::Rng;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const FROM_RADIO_POLL_INTERVAL_MS: u64 = 500;
+/// Failed polls back off by doubling up to this, so an unreachable node is not hammered.
+const FROM_RADIO_MAX_BACKOFF_MS: u64 = 30_000;
+/// While polls keep failing, the failure is logged at most this often.
+const POLL_ERROR_LOG_INTERVAL_SECS: u64 = 60;
+const HTTP_REQUEST_TIMEOUT_SECS: u64 = 10;
+const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+pub struct HttpTransport {
+    base_url: String,
+    client: reqwest::Client,
+    poll_task: Option<JoinHandle<()>>,
+}
+
+impl HttpTransport {
+    /// `base_url` is the node's web root, e.g. `http://meshtastic.local` or `https://10.0.0.5`.
+    pub fn new(base_url: String) -> Result<Self> {
+        // ESP32 firmware serves HTTPS with a self-signed certificate.
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(HTTP_REQUEST_TIMEOUT_SECS))
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Could not create HTTP client: {}", e))?;
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+            poll_task: None,
+        })
+    }
+
+    fn to_radio_url(&self) -> String {
+        format!("{}/api/v1/toradio", self.base_url)
+    }
+
+    fn from_radio_url(&self) -> String {
+        format!("{}/api/v1/fromradio?all=false", self.base_url)
+    }
+
+    async fn put_to_radio(&self, to_radio: ToRadio) -> Result<()> {
+        self.client
+            .put(self.to_radio_url())
+            .header(reqwest::header::CONTENT_TYPE, PROTOBUF_CONTENT_TYPE)
+            .body(to_radio.encode_to_vec())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    fn ensure_connected(&self) -> Result<()> {
+        if self.poll_task.is_some() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!("Not connected"))
+        }
+    }
+}
+
+fn generate_rand_id() -> u32 {
+    let mut rng = rand::rng();
+    rng.random()
+}
+
+fn current_epoch_secs_u32() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as u32
+}
+
+/// Fetches a single `FromRadio` from the node; `None` means the queue is drained.
+async fn poll_from_radio(client: &reqwest::Client, url: &str) -> Result<Option<FromRadio>> {
+    let body = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, PROTOBUF_CONTENT_TYPE)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    if body.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(FromRadio::decode(body.as_ref())?))
+}
+
+use meshtastic::packet::PacketReceiver;
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn connect(&mut self) -> Result<PacketReceiver> {
+        msnr_log!("Connecting to {} via HTTP API...", self.base_url);
+
+        let want_config = ToRadio {
+            payload_variant: Some(to_radio::PayloadVariant::WantConfigId(generate_rand_id())),
+        };
+        self.put_to_radio(want_config).await?;
+        msnr_log!("HTTP API reachable!");
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let client = self.client.clone();
+        let url = self.from_radio_url();
+
+        let poll_task = tokio::spawn(logging::in_current_context(async move {
+            let mut delay = Duration::from_millis(FROM_RADIO_POLL_INTERVAL_MS);
+            let mut failures: u32 = 0;
+            let mut last_error_log: Option<Instant> = None;
+            loop {
+                match poll_from_radio(&client, &url).await {
+                    Ok(packet) => {
+                        if failures > 0 {
+                            msnr_log!(
+                                "HTTP fromradio poll recovered after {} failure(s)",
+                                failures
+                            );
+                            failures = 0;
+                            last_error_log = None;
+                            delay = Duration::from_millis(FROM_RADIO_POLL_INTERVAL_MS);
+                        }
+                        if let Some(packet) = packet {
+                            if tx.send(packet).is_err() {
+                                break;
+                            }
+                            // Keep draining while the node has queued packets.
+                            continue;
+                        }
+                    }
+                    Err(e) => {
+                        failures += 1;
+                        let log_due = last_error_log.is_none_or(|logged| {
+                            logged.elapsed() >= Duration::from_secs(POLL_ERROR_LOG_INTERVAL_SECS)
+                        });
+                        if log_due {
+                            msnr_log_err!(
+                                "HTTP fromradio poll failed ({} in a row, retrying in {} ms): {}",
+                                failures,
+                                delay.as_millis(),
+                                e
+                            );
+                            last_error_log = Some(Instant::now());
+                        } else {
+                            msnr_debug!("HTTP fromradio poll failed: {}", e);
+                        }
+                        tokio::time::sleep(delay).await;
+                        delay = (delay * 2).min(Duration::from_millis(FROM_RADIO_MAX_BACKOFF_MS));
+                        continue;
+                    }
+                }
+                tokio::time::sleep(delay).await;
+            }
+        }));
+
+        self.poll_task = Some(poll_task);
+        Ok(rx)
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        msnr_log!("Disconnecting from {}", self.base_url);
+        if let Some(task) = self.poll_task.take() {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    async fn set_lna(&mut self, node_id: NodeId, enable: bool) -> Result<()> {
+        self.ensure_connected()?;
+        msnr_log!("Setting LNA for {} to {}", node_id, enable);
+
+        // Construct HardwareMessage to toggle GPIO
+        // Assuming LNA is on GPIO 1 (needs configuration)
+        let gpio_mask = 1 << 1;
+        let gpio_value = if enable { gpio_mask } else { 0 };
+
+        let hardware_msg = meshtastic::protobufs::HardwareMessage {
+            r#type: meshtastic::protobufs::hardware_message::Type::WriteGpios as i32,
+            gpio_mask,
+            gpio_value,
+        };
+
+        let payload = hardware_msg.encode_to_vec();
+
+        let mesh_packet = MeshPacket {
+            from: 0,
+            to: node_id.as_u32(),
+            id: generate_rand_id(),
+            rx_time: current_epoch_secs_u32(),
+            want_ack: true,
+            payload_variant: Some(mesh_packet::PayloadVariant::Decoded(Data {
+                portnum: PortNum::RemoteHardwareApp as i32,
+                payload,
+                want_response: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        self.put_to_radio(ToRadio {
+            payload_variant: Some(to_radio::PayloadVariant::Packet(mesh_packet)),
+        })
+        .await
+    }
+
+    async fn send_packet(&mut self, dest: NodeId, port: i32, payload: Vec<u8>) -> Result<()> {
+        self.ensure_connected()?;
+
+        let mesh_packet = MeshPacket {
+            from: 0,
+            to: dest.as_u32(),
+            id: generate_rand_id(),
+            rx_time: current_epoch_secs_u32(),
+            want_ack: true,
+            payload_variant: Some(mesh_packet::PayloadVariant::Decoded(Data {
+                portnum: port,
+                payload,
+                want_response: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        self.put_to_radio(ToRadio {
+            payload_variant: Some(to_radio::PayloadVariant::Packet(mesh_packet)),
+        })
+        .await
+    }
+
+    async fn send_admin(
+        &mut self,
+        dest: NodeId,
+        admin_msg: meshtastic::protobufs::AdminMessage,
+    ) -> Result<()> {
+        self.ensure_connected()?;
+
+        let data = Data {
+            portnum: PortNum::AdminApp as i32,
+            payload: admin_msg.encode_to_vec(),
+            want_response: true,
+            dest: dest.as_u32(),
+            source: 0,
+            ..Default::default()
+        };
+
+        let mesh_packet = MeshPacket {
+            from: 0,
+            to: dest.as_u32(),
+            id: generate_rand_id(),
+            rx_time: current_epoch_secs_u32(),
+            want_ack: true,
+            priority: mesh_packet::Priority::Reliable as i32,
+            // Same firmware-side PKI signing as the TCP transport
+            pki_encrypted: true,
+            payload_variant: Some(mesh_packet::PayloadVariant::Decoded(data)),
+            ..Default::default()
+        };
+
+        msnr_debug!("Sending Admin PKI Packet to {}", dest);
+        self.put_to_radio(ToRadio {
+            payload_variant: Some(to_radio::PayloadVariant::Packet(mesh_packet)),
+        })
+        .await
+    }
+
+    async fn run_traceroute(&mut self, target: NodeId) -> Result<TracerouteRequest> {
+        self.ensure_connected()?;
+        msnr_debug!("Sending Traceroute to {}", target);
+
+        let route_discovery = meshtastic::protobufs::RouteDiscovery {
+            route: vec![],
+            route_back: vec![],
+            snr_back: vec![],
+            snr_towards: vec![],
+        };
+
+        let packet_id = generate_rand_id();
+        let mesh_packet = MeshPacket {
+            from: 0,
+            to: target.as_u32(),
+            id: packet_id,
+            rx_time: current_epoch_secs_u32(),
+            want_ack: true,
+            hop_limit: 6,
+            payload_variant: Some(mesh_packet::PayloadVariant::Decoded(Data {
+                portnum: PortNum::TracerouteApp as i32,
+                payload: route_discovery.encode_to_vec(),
+                want_response: true,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        self.put_to_radio(ToRadio {
+            payload_variant: Some(to_radio::PayloadVariant::Packet(mesh_packet)),
+        })
+        .await?;
+
+        // Replies arrive on the fromradio poll stream and are matched via `Data.request_id`.
+        Ok(TracerouteRequest { packet_id, target })
+    }
+}
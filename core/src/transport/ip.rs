@@ -1,5 +1,6 @@
-use super::{TracerouteResult, Transport};
-use crate::msnr_log;
+use super::{TracerouteRequest, Transport};
+use crate::node_id::NodeId;
+use crate::{msnr_debug, msnr_log};
 use anyhow::Result;
 use async_trait::async_trait;
 use meshtastic::api::{ConnectedStreamApi, StreamApi, StreamHandle, state};
@@ -71,7 +72,7 @@ impl Transport for IpTransport {
         Ok(())
     }
 
-    async fn set_lna(&mut self, node_id: &str, enable: bool) -> Result<()> {
+    async fn set_lna(&mut self, node_id: NodeId, enable: bool) -> Result<()> {
         if let Some(api) = &mut self.api {
             msnr_log!("Setting LNA for {} to {}", node_id, enable);
 
@@ -88,19 +89,9 @@ impl Transport for IpTransport {
 
             let payload = hardware_msg.encode_to_vec();
 
-            // Parse destination node_id (assuming decimal string or hex)
-            // For now, if node_id is "broadcast" or empty, use broadcast, else parse
-            let dest = if node_id.is_empty() || node_id == "broadcast" {
-                u32::MAX
-            } else if node_id.starts_with('!') {
-                u32::from_str_radix(&node_id[1..], 16).unwrap_or(u32::MAX)
-            } else {
-                node_id.parse::<u32>().unwrap_or(u32::MAX)
-            };
-
             let mesh_packet = MeshPacket {
                 from: 0,
-                to: dest,
+                to: node_id.as_u32(),
                 id: generate_rand_id(),
                 rx_time: current_epoch_secs_u32(),
                 want_ack: true,
@@ -125,16 +116,8 @@ impl Transport for IpTransport {
         }
     }
 
-    async fn send_packet(&mut self, dest_str: &str, port: i32, payload: Vec<u8>) -> Result<()> {
+    async fn send_packet(&mut self, dest: NodeId, port: i32, payload: Vec<u8>) -> Result<()> {
         if let Some(api) = &mut self.api {
-            let dest = if dest_str.is_empty() || dest_str == "broadcast" {
-                u32::MAX
-            } else if dest_str.starts_with('!') {
-                u32::from_str_radix(&dest_str[1..], 16).unwrap_or(u32::MAX)
-            } else {
-                dest_str.parse::<u32>().unwrap_or(u32::MAX)
-            };
-
             let data = Data {
                 portnum: port,
                 payload,
@@ -144,7 +127,7 @@ impl Transport for IpTransport {
 
             let mesh_packet = MeshPacket {
                 from: 0,
-                to: dest,
+                to: dest.as_u32(),
                 id: generate_rand_id(),
                 rx_time: current_epoch_secs_u32(),
                 want_ack: true,
@@ -165,16 +148,10 @@ impl Transport for IpTransport {
 
     async fn send_admin(
         &mut self,
-        dest_str: &str,
+        dest: NodeId,
         admin_msg: meshtastic::protobufs::AdminMessage,
     ) -> Result<()> {
         if let Some(api) = &mut self.api {
-            let dest = if dest_str.starts_with('!') {
-                u32::from_str_radix(&dest_str[1..], 16).unwrap_or(u32::MAX)
-            } else {
-                dest_str.parse::<u32>().unwrap_or(u32::MAX)
-            };
-
             // Encode AdminMessage
             let data_payload = admin_msg.encode_to_vec();
 
@@ -182,14 +159,14 @@ impl Transport for IpTransport {
                 portnum: PortNum::AdminApp as i32,
                 payload: data_payload,
                 want_response: true,
-                dest,
+                dest: dest.as_u32(),
                 source: 0,
                 ..Default::default()
             };
 
             let mesh_packet = MeshPacket {
                 from: 0,
-                to: dest,
+                to: dest.as_u32(),
                 id: generate_rand_id(),
                 rx_time: current_epoch_secs_u32(),
                 want_ack: true,
@@ -204,7 +181,7 @@ impl Transport for IpTransport {
                 payload_variant: Some(to_radio::PayloadVariant::Packet(mesh_packet)),
             };
 
-            msnr_log!("Sending Admin PKI Packet to {}", dest_str);
+            msnr_debug!("Sending Admin PKI Packet to {}", dest);
             api.send_to_radio_packet(to_radio.payload_variant).await?;
             Ok(())
         } else {
@@ -212,15 +189,9 @@ impl Transport for IpTransport {
         }
     }
 
-    async fn run_traceroute(&mut self, target_node_id: &str) -> Result<Vec<TracerouteResult>> {
+    async fn run_traceroute(&mut self, target: NodeId) -> Result<TracerouteRequest> {
         if let Some(api) = &mut self.api {
-            msnr_log!("Sending Traceroute to {}", target_node_id);
-
-            let dest = if target_node_id.starts_with('!') {
-                u32::from_str_radix(&target_node_id[1..], 16).unwrap_or(u32::MAX)
-            } else {
-                target_node_id.parse::<u32>().unwrap_or(u32::MAX)
-            };
+            msnr_debug!("Sending Traceroute to {}", target);
 
             let route_discovery = meshtastic::protobufs::RouteDiscovery {
                 route: vec![],
@@ -231,10 +202,11 @@ impl Transport for IpTransport {
 
             let payload = route_discovery.encode_to_vec();
 
+            let packet_id = generate_rand_id();
             let mesh_packet = MeshPacket {
                 from: 0,
-                to: dest,
-                id: generate_rand_id(),
+                to: target.as_u32(),
+                id: packet_id,
                 rx_time: current_epoch_secs_u32(),
                 want_ack: true,
                 hop_limit: 6,
@@ -253,13 +225,10 @@ impl Transport for IpTransport {
 
             api.send_to_radio_packet(to_radio.payload_variant).await?;
 
-            // TODO: Implement listening loop to capture RouteDiscovery response
-            // For now, we just send the request. Capturing response requires
-            // access to the read channel which is consumed by the main loop or needs a subscription mechanism.
-            // Since `api` is `ConnectedStreamApi`, we don't have direct access to `rx` here as it was returned in `connect`.
-            // We might need to restructure `IpTransport` to hold the `rx` or use a shared channel.
+            // The reply arrives on the receiver returned by `connect`; the engine
+            // matches it to this request through `Data.request_id`.
 
-            Ok(vec![])
+            Ok(TracerouteRequest { packet_id, target })
         } else {
             Err(anyhow::anyhow!("Not connected"))
         }
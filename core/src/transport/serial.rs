@@ -1,30 +1,181 @@
-use super::{TracerouteResult, Transport};
-use crate::msnr_log;
+use super::{TracerouteRequest, Transport};
+use crate::config::SerialFlowControl;
+use crate::node_id::NodeId;
+use crate::{msnr_debug, msnr_log, msnr_warn};
 use anyhow::Result;
 use async_trait::async_trait;
 use meshtastic::api::{ConnectedStreamApi, StreamApi, StreamHandle, state};
-use meshtastic::protobufs::{Data, MeshPacket, PortNum, ToRadio, mesh_packet, to_radio};
+use meshtastic::protobufs::{
+    Data, MeshPacket, PortNum, ToRadio, from_radio, mesh_packet, to_radio,
+};
 use prost::Message;
 use rand::Rng;
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio_serial::SerialPortBuilderExt;
 
+const DEFAULT_BAUD_RATE: u32 = 115200;
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// USB bridges and native-USB boards that Meshtastic hardware ships with.
+const MESHTASTIC_USB_IDS: &[(u16, Option<u16>, &str)] = &[
+    (0x10c4, Some(0xea60), "CP210x"),
+    (0x1a86, Some(0x55d4), "CH9102"),
+    (0x1a86, Some(0x7523), "CH340"),
+    (0x239a, None, "nRF52 native USB (Adafruit/RAK)"),
+    (0x2886, None, "nRF52 native USB (Seeed)"),
+    (0x303a, None, "ESP32-S3 native USB"),
+];
+
 pub struct SerialTransport {
     port_name: String,
     baud_rate: u32,
+    flow_control: SerialFlowControl,
     api: Option<ConnectedStreamApi<state::Configured>>,
 }
 
 impl SerialTransport {
     pub fn new(port_name: String) -> Self {
+        Self::with_settings(port_name, DEFAULT_BAUD_RATE, SerialFlowControl::None)
+    }
+
+    pub fn with_settings(
+        port_name: String,
+        baud_rate: u32,
+        flow_control: SerialFlowControl,
+    ) -> Self {
         Self {
             port_name,
-            baud_rate: 115200,
+            baud_rate,
+            flow_control,
             api: None,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectedSerialPort {
+    pub port_name: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    /// Name of the matched USB chip when VID/PID look like Meshtastic hardware.
+    pub chip: Option<String>,
+    /// Filled in by `probe_serial_ports` when the device answered with `my_info`.
+    pub node_num: Option<u32>,
+}
+
+impl DetectedSerialPort {
+    pub fn is_likely_meshtastic(&self) -> bool {
+        self.chip.is_some()
+    }
+}
+
+fn identify_chip(vid: u16, pid: u16) -> Option<&'static str> {
+    MESHTASTIC_USB_IDS
+        .iter()
+        .find(|(known_vid, known_pid, _)| {
+            *known_vid == vid && known_pid.is_none_or(|known| known == pid)
+        })
+        .map(|(_, _, name)| *name)
+}
+
+/// Lists serial ports with their USB metadata, likely Meshtastic devices first.
+pub fn discover_serial_ports() -> Vec<DetectedSerialPort> {
+    let ports = serialport::available_ports().unwrap_or_default();
+    let mut detected: Vec<DetectedSerialPort> = ports
+        .into_iter()
+        .map(|port| match port.port_type {
+            serialport::SerialPortType::UsbPort(usb) => DetectedSerialPort {
+                port_name: port.port_name,
+                vid: Some(usb.vid),
+                pid: Some(usb.pid),
+                manufacturer: usb.manufacturer,
+                product: usb.product,
+                serial_number: usb.serial_number,
+                chip: identify_chip(usb.vid, usb.pid).map(str::to_string),
+                node_num: None,
+            },
+            _ => DetectedSerialPort {
+                port_name: port.port_name,
+                vid: None,
+                pid: None,
+                manufacturer: None,
+                product: None,
+                serial_number: None,
+                chip: None,
+                node_num: None,
+            },
+        })
+        .collect();
+
+    detected.sort_by_key(|port| !port.is_likely_meshtastic());
+    detected
+}
+
+/// Opens each likely Meshtastic port and records the node number it reports.
+pub async fn probe_serial_ports(
+    mut ports: Vec<DetectedSerialPort>,
+    baud_rate: u32,
+) -> Vec<DetectedSerialPort> {
+    for port in ports.iter_mut().filter(|p| p.is_likely_meshtastic()) {
+        match probe_my_node_num(&port.port_name, baud_rate).await {
+            Ok(node_num) => port.node_num = node_num,
+            Err(e) => msnr_warn!("Probe of {} failed: {}", port.port_name, e),
+        }
+    }
+    ports
+}
+
+async fn probe_my_node_num(port_name: &str, baud_rate: u32) -> Result<Option<u32>> {
+    msnr_log!("Probing {} for my_info...", port_name);
+    let port = tokio_serial::new(port_name, baud_rate).open_native_async()?;
+
+    let stream_api = StreamApi::new();
+    let (mut rx, connected_api) = stream_api.connect(StreamHandle::from_stream(port)).await;
+    let configured_api = connected_api.configure(generate_rand_id()).await?;
+
+    let node_num = tokio::time::timeout(Duration::from_secs(PROBE_TIMEOUT_SECS), async {
+        while let Some(packet) = rx.recv().await {
+            if let Some(from_radio::PayloadVariant::MyInfo(info)) = packet.payload_variant {
+                return Some(info.my_node_num);
+            }
+        }
+        None
+    })
+    .await
+    .unwrap_or(None);
+
+    configured_api.disconnect().await?;
+    Ok(node_num)
+}
+
+/// Picks the port to open: a matching USB serial number wins over the configured name.
+pub fn resolve_serial_port(port_name: Option<&str>, serial_number: Option<&str>) -> Result<String> {
+    if let Some(wanted) = serial_number.filter(|s| !s.is_empty()) {
+        return discover_serial_ports()
+            .into_iter()
+            .find(|port| port.serial_number.as_deref() == Some(wanted))
+            .map(|port| port.port_name)
+            .ok_or_else(|| anyhow::anyhow!("No serial port with serial number {}", wanted));
+    }
+
+    port_name
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("Serial port not specified"))
+}
+
+fn to_tokio_flow_control(flow_control: SerialFlowControl) -> tokio_serial::FlowControl {
+    match flow_control {
+        SerialFlowControl::None => tokio_serial::FlowControl::None,
+        SerialFlowControl::Software => tokio_serial::FlowControl::Software,
+        SerialFlowControl::Hardware => tokio_serial::FlowControl::Hardware,
+    }
+}
+
 fn generate_rand_id() -> u32 {
     let mut rng = rand::rng();
     rng.random()
@@ -42,9 +193,16 @@ use meshtastic::packet::PacketReceiver;
 #[async_trait]
 impl Transport for SerialTransport {
     async fn connect(&mut self) -> Result<PacketReceiver> {
-        msnr_log!("Opening serial port {}", self.port_name);
+        msnr_log!(
+            "Opening serial port {} ({} baud, flow control {:?})",
+            self.port_name,
+            self.baud_rate,
+            self.flow_control
+        );
 
-        let port = tokio_serial::new(&self.port_name, self.baud_rate).open_native_async()?;
+        let port = tokio_serial::new(&self.port_name, self.baud_rate)
+            .flow_control(to_tokio_flow_control(self.flow_control))
+            .open_native_async()?;
 
         let stream_handle = StreamHandle::from_stream(port);
 
@@ -68,7 +226,7 @@ impl Transport for SerialTransport {
 
     async fn send_admin(
         &mut self,
-        _dest: &str,
+        _dest: NodeId,
         _admin_msg: meshtastic::protobufs::AdminMessage,
     ) -> Result<()> {
         Err(anyhow::anyhow!(
@@ -76,7 +234,7 @@ impl Transport for SerialTransport {
         ))
     }
 
-    async fn set_lna(&mut self, node_id: &str, enable: bool) -> Result<()> {
+    async fn set_lna(&mut self, node_id: NodeId, enable: bool) -> Result<()> {
         if let Some(api) = &mut self.api {
             msnr_log!("Setting LNA for {} to {}", node_id, enable);
 
@@ -93,19 +251,9 @@ impl Transport for SerialTransport {
 
             let payload = hardware_msg.encode_to_vec();
 
-            // Parse destination node_id (assuming decimal string or hex)
-            // For now, if node_id is "broadcast" or empty, use broadcast, else parse
-            let dest = if node_id.is_empty() || node_id == "broadcast" {
-                u32::MAX
-            } else if node_id.starts_with('!') {
-                u32::from_str_radix(&node_id[1..], 16).unwrap_or(u32::MAX)
-            } else {
-                node_id.parse::<u32>().unwrap_or(u32::MAX)
-            };
-
             let mesh_packet = MeshPacket {
                 from: 0,
-                to: dest,
+                to: node_id.as_u32(),
                 id: generate_rand_id(),
                 rx_time: current_epoch_secs_u32(),
                 want_ack: true,
@@ -130,19 +278,11 @@ impl Transport for SerialTransport {
         }
     }
 
-    async fn send_packet(&mut self, dest_str: &str, port: i32, payload: Vec<u8>) -> Result<()> {
+    async fn send_packet(&mut self, dest: NodeId, port: i32, payload: Vec<u8>) -> Result<()> {
         if let Some(api) = &mut self.api {
-            let dest = if dest_str.is_empty() || dest_str == "broadcast" {
-                u32::MAX
-            } else if dest_str.starts_with('!') {
-                u32::from_str_radix(&dest_str[1..], 16).unwrap_or(u32::MAX)
-            } else {
-                dest_str.parse::<u32>().unwrap_or(u32::MAX)
-            };
-
             let mesh_packet = MeshPacket {
                 from: 0,
-                to: dest,
+                to: dest.as_u32(),
                 id: generate_rand_id(),
                 rx_time: current_epoch_secs_u32(),
                 want_ack: true,
@@ -166,15 +306,9 @@ impl Transport for SerialTransport {
         }
     }
 
-    async fn run_traceroute(&mut self, target_node_id: &str) -> Result<Vec<TracerouteResult>> {
+    async fn run_traceroute(&mut self, target: NodeId) -> Result<TracerouteRequest> {
         if let Some(api) = &mut self.api {
-            msnr_log!("Sending Traceroute to {}", target_node_id);
-
-            let dest = if target_node_id.starts_with('!') {
-                u32::from_str_radix(&target_node_id[1..], 16).unwrap_or(u32::MAX)
-            } else {
-                target_node_id.parse::<u32>().unwrap_or(u32::MAX)
-            };
+            msnr_debug!("Sending Traceroute to {}", target);
 
             let route_discovery = meshtastic::protobufs::RouteDiscovery {
                 route: vec![],
@@ -185,10 +319,11 @@ impl Transport for SerialTransport {
 
             let payload = route_discovery.encode_to_vec();
 
+            let packet_id = generate_rand_id();
             let mesh_packet = MeshPacket {
                 from: 0,
-                to: dest,
-                id: generate_rand_id(),
+                to: target.as_u32(),
+                id: packet_id,
                 rx_time: current_epoch_secs_u32(),
                 want_ack: true,
                 hop_limit: 6,
@@ -207,9 +342,10 @@ impl Transport for SerialTransport {
 
             api.send_to_radio_packet(to_radio.payload_variant).await?;
 
-            // TODO: Implement listening loop
+            // The reply arrives on the receiver returned by `connect`; the engine
+            // matches it to this request through `Data.request_id`.
 
-            Ok(vec![])
+            Ok(TracerouteRequest { packet_id, target })
         } else {
             Err(anyhow::anyhow!("Not connected"))
         }